@@ -0,0 +1,8 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use dmi_assistant::cli::{Cli, run};
+
+fn main() -> ExitCode {
+    run(Cli::parse())
+}
@@ -0,0 +1,837 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use dmi::icon::{Icon, IconState};
+use image::ImageFormat;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{
+    dmi_edit::{
+        DMIEditError, MergeConflictPolicy, merge_dmi_files, split_dmi_file,
+    },
+    dmi_lint::{
+        DEFAULT_MAX_ICON_SIZE, LintIssue, LintKind, LintSeverity, count_issues,
+        lint_icon,
+    },
+    dmi_merge::{DMIMergeError, MergeSide, merge_three_way},
+    dmi_model::StateSummary,
+    dmi_utils::{
+        Directions, get_state_frame, load_dmi, mismatched_delays, save_dmi,
+    },
+};
+
+/// Everything went fine.
+pub const EXIT_OK: u8 = 0;
+/// Requested state, direction, frame or search result does not exist.
+pub const EXIT_NOT_FOUND: u8 = 1;
+/// Wrong command line usage. Also returned by clap itself.
+pub const EXIT_USAGE: u8 = 2;
+/// DMI could not be read, parsed or written.
+pub const EXIT_IO: u8 = 3;
+/// DMIs can not be merged or split as requested, e.g. sizes differ or states
+/// conflict.
+pub const EXIT_INVALID: u8 = 4;
+/// Three-way merge left conflicts, which were resolved with our version.
+pub const EXIT_CONFLICT: u8 = 5;
+/// Lint found errors, or warnings with `--deny-warnings`.
+pub const EXIT_LINT: u8 = 6;
+
+const DEFAULT_RECURSION_DEPTH: usize = 20;
+
+/// Headless companion of the DMI Assistant.
+#[derive(Debug, Parser)]
+#[command(name = "DMIAssistantCLI", version, about)]
+pub struct Cli {
+    /// Print machine-readable JSON instead of human-readable text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List state names of the DMI
+    List { file: PathBuf },
+    /// Show dimensions and metadata of every state of the DMI
+    Info { file: PathBuf },
+    /// Recursively search DMIs in the directory by state name
+    Search {
+        dir: PathBuf,
+        query: String,
+        /// Maximum recursion depth
+        #[arg(long, default_value_t = DEFAULT_RECURSION_DEPTH)]
+        depth: usize,
+    },
+    /// Extract a single frame of the state into a PNG
+    Extract {
+        file: PathBuf,
+        state: String,
+        /// Direction, either full (`south`) or short (`S`) name
+        #[arg(long, default_value = "S")]
+        dir: Directions,
+        /// Frame index, starting from 0
+        #[arg(long, default_value_t = 0)]
+        frame: u32,
+        /// Output file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Merge states of several DMIs of the same size into one
+    Merge {
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+        /// What to do with states of the same name: rename, keep-first,
+        /// keep-last or fail
+        #[arg(long, default_value = "rename")]
+        on_conflict: MergeConflictPolicy,
+        /// Output file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Three-way merge of DMIs. Works as a git merge driver:
+    /// `DMIAssistantCLI merge3 %O %A %B` writes the result into %A
+    Merge3 {
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+        /// Output file, defaults to `ours`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Resolve conflicting states with this side: base, ours or theirs.
+        /// Without it conflicts are left as ours and reported
+        #[arg(long)]
+        prefer: Option<MergeSide>,
+    },
+    /// Move the listed states out of the DMI into a new one
+    Split {
+        file: PathBuf,
+        #[arg(required = true)]
+        states: Vec<String>,
+        /// Output file for the moved states
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Check DMIs and directories of DMIs for broken or suspicious states
+    Lint {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Maximum recursion depth
+        #[arg(long, default_value_t = DEFAULT_RECURSION_DEPTH)]
+        depth: usize,
+        /// Report icons bigger than this in any dimension
+        #[arg(long, default_value_t = DEFAULT_MAX_ICON_SIZE)]
+        max_size: u32,
+        /// Fail on warnings too
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct IconInfo {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    states: Vec<StateSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct MergeConflictInfo {
+    state: String,
+    movement: bool,
+    description: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LintResult {
+    path: PathBuf,
+    issues: Vec<LintIssue>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchHit {
+    path: PathBuf,
+    state: String,
+}
+
+/// Error which ends the command with a specific exit code.
+struct CliError {
+    code: u8,
+    message: String,
+}
+
+impl CliError {
+    fn new<T: Into<String>>(code: u8, message: T) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn run(cli: Cli) -> ExitCode {
+    let result = match cli.command {
+        Command::List { file } => list(&file, cli.json),
+        Command::Info { file } => info(&file, cli.json),
+        Command::Search { dir, query, depth } => {
+            search(&dir, &query, depth, cli.json)
+        }
+        Command::Extract {
+            file,
+            state,
+            dir,
+            frame,
+            output,
+        } => extract(&file, &state, dir, frame, &output),
+        Command::Merge {
+            inputs,
+            on_conflict,
+            output,
+        } => merge(&inputs, on_conflict, &output, cli.json),
+        Command::Split {
+            file,
+            states,
+            output,
+        } => split(&file, &states, &output),
+        Command::Merge3 {
+            base,
+            ours,
+            theirs,
+            output,
+            prefer,
+        } => merge3(
+            &base,
+            &ours,
+            &theirs,
+            output.as_deref().unwrap_or(&ours),
+            prefer,
+            cli.json,
+        ),
+        Command::Lint {
+            paths,
+            depth,
+            max_size,
+            deny_warnings,
+        } => lint(&paths, depth, max_size, deny_warnings, cli.json),
+    };
+
+    match result {
+        Ok(()) => ExitCode::from(EXIT_OK),
+        Err(err) => {
+            eprintln!("error: {}", err.message);
+            ExitCode::from(err.code)
+        }
+    }
+}
+
+fn open_icon(file: &Path) -> Result<Icon, CliError> {
+    load_dmi(file).map_err(|err| {
+        CliError::new(
+            EXIT_IO,
+            format!("failed to load {}: {}", file.to_string_lossy(), err),
+        )
+    })
+}
+
+impl From<DMIEditError> for CliError {
+    fn from(err: DMIEditError) -> Self {
+        let code = match err {
+            DMIEditError::NoSuchState(_) => EXIT_NOT_FOUND,
+            DMIEditError::Parsing(_) => EXIT_IO,
+            _ => EXIT_INVALID,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+impl From<DMIMergeError> for CliError {
+    fn from(err: DMIMergeError) -> Self {
+        Self::new(EXIT_INVALID, err.to_string())
+    }
+}
+
+fn find_state<'a>(
+    icon: &'a Icon,
+    name: &str,
+) -> Result<&'a IconState, CliError> {
+    icon.states
+        .iter()
+        .find(|state| state.name == name)
+        .ok_or_else(|| {
+            CliError::new(EXIT_NOT_FOUND, format!("no state named {:?}", name))
+        })
+}
+
+/// DMI files in the directory and its subdirectories.
+fn dmi_files_in(dir: &Path, depth: usize) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .max_depth(depth)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && entry.path().extension() == Some(OsStr::new("dmi"))
+        })
+        .map(|entry| entry.into_path())
+}
+
+/// Pretty JSON with a trailing newline.
+fn to_json<T: Serialize>(value: &T) -> Result<String, CliError> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .map_err(|err| CliError::new(EXIT_IO, err.to_string()))
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), CliError> {
+    print!("{}", to_json(value)?);
+    Ok(())
+}
+
+fn list(file: &Path, json: bool) -> Result<(), CliError> {
+    print!("{}", list_output(&open_icon(file)?, json)?);
+    Ok(())
+}
+
+/// What `list` prints for the icon.
+fn list_output(icon: &Icon, json: bool) -> Result<String, CliError> {
+    let names: Vec<&String> =
+        icon.states.iter().map(|state| &state.name).collect();
+    if json {
+        return to_json(&names);
+    }
+    let mut output = String::new();
+    for name in names {
+        let _ = writeln!(output, "{}", name);
+    }
+    Ok(output)
+}
+
+fn info(file: &Path, json: bool) -> Result<(), CliError> {
+    print!("{}", info_output(file, &open_icon(file)?, json)?);
+    Ok(())
+}
+
+/// What `info` prints for the icon loaded from `file`.
+fn info_output(
+    file: &Path,
+    icon: &Icon,
+    json: bool,
+) -> Result<String, CliError> {
+    let info = IconInfo {
+        path: file.to_path_buf(),
+        width: icon.width,
+        height: icon.height,
+        states: icon.states.iter().map(StateSummary::from).collect(),
+    };
+    if json {
+        return to_json(&info);
+    }
+    let mut output = String::new();
+    let _ = writeln!(output, "path: {}", info.path.to_string_lossy());
+    let _ = writeln!(output, "size: {}x{}", info.width, info.height);
+    let _ = writeln!(output, "states: {}", info.states.len());
+    for state in &info.states {
+        let _ = writeln!(
+            output,
+            "  {:?} dirs={} frames={} delay={:?} loop={} rewind={} movement={}",
+            state.name,
+            state.dirs,
+            state.frames,
+            state.delay.as_deref().unwrap_or_default(),
+            if state.loop_count == 0 {
+                "indefinitely".to_string()
+            } else {
+                state.loop_count.to_string()
+            },
+            state.rewind,
+            state.movement
+        );
+    }
+    Ok(output)
+}
+
+fn search(
+    dir: &Path,
+    query: &str,
+    depth: usize,
+    json: bool,
+) -> Result<(), CliError> {
+    if !dir.is_dir() {
+        return Err(CliError::new(
+            EXIT_USAGE,
+            format!("{} is not a directory", dir.to_string_lossy()),
+        ));
+    }
+    let mut hits: Vec<SearchHit> = Vec::new();
+    for path in dmi_files_in(dir, depth) {
+        let icon = match load_dmi(&path) {
+            Ok(icon) => icon,
+            Err(err) => {
+                eprintln!(
+                    "warning: skipping {}: {}",
+                    path.to_string_lossy(),
+                    err
+                );
+                continue;
+            }
+        };
+        hits.extend(
+            icon.states
+                .into_iter()
+                .filter(|state| state.name.contains(query))
+                .map(|state| SearchHit {
+                    path: path.clone(),
+                    state: state.name,
+                }),
+        );
+    }
+
+    if json {
+        print_json(&hits)?;
+    } else {
+        for hit in &hits {
+            println!("{}\t{}", hit.path.to_string_lossy(), hit.state);
+        }
+    }
+    if hits.is_empty() {
+        return Err(CliError::new(EXIT_NOT_FOUND, "nothing found"));
+    }
+    Ok(())
+}
+
+fn extract(
+    file: &Path,
+    state_name: &str,
+    dir: Directions,
+    frame: u32,
+    output: &Path,
+) -> Result<(), CliError> {
+    let icon = open_icon(file)?;
+    let state = find_state(&icon, state_name)?;
+    let image = get_state_frame(state, dir, frame).ok_or_else(|| {
+        CliError::new(
+            EXIT_NOT_FOUND,
+            format!(
+                "state {:?} has no frame {} for direction {} (dirs: {}, frames: {})",
+                state_name, frame, dir, state.dirs, state.frames
+            ),
+        )
+    })?;
+    // The output is a PNG whatever its extension is
+    image
+        .to_rgba8()
+        .save_with_format(output, ImageFormat::Png)
+        .map_err(|err| {
+            CliError::new(
+                EXIT_IO,
+                format!("failed to save {}: {}", output.to_string_lossy(), err),
+            )
+        })
+}
+
+fn merge(
+    inputs: &[PathBuf],
+    policy: MergeConflictPolicy,
+    output: &Path,
+    json: bool,
+) -> Result<(), CliError> {
+    let conflicts = merge_dmi_files(inputs, output, policy)?;
+    if json {
+        return print_json(&conflicts);
+    }
+    for conflict in conflicts {
+        println!("{}", conflict);
+    }
+    Ok(())
+}
+
+fn split(
+    file: &Path,
+    states: &[String],
+    output: &Path,
+) -> Result<(), CliError> {
+    split_dmi_file(file, states, output)?;
+    Ok(())
+}
+
+fn merge3(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    output: &Path,
+    prefer: Option<MergeSide>,
+    json: bool,
+) -> Result<(), CliError> {
+    let ours = open_icon(ours)?;
+    // Git passes an empty base when both sides added the file
+    let base = if !fs::metadata(base).is_ok_and(|meta| meta.len() > 0) {
+        Icon {
+            width: ours.width,
+            height: ours.height,
+            states: Vec::new(),
+            ..Default::default()
+        }
+    } else {
+        open_icon(base)?
+    };
+    let merge = merge_three_way(base, ours, open_icon(theirs)?)?;
+    let conflicts: Vec<MergeConflictInfo> = merge
+        .conflicts()
+        .map(|conflict| MergeConflictInfo {
+            state: conflict.key.name.clone(),
+            movement: conflict.key.movement,
+            description: conflict.describe(),
+        })
+        .collect();
+    let icon = merge
+        .resolve(&HashMap::new(), Some(prefer.unwrap_or(MergeSide::Ours)))?;
+    let fitted = mismatched_delays(&icon);
+    if !fitted.is_empty() {
+        eprintln!(
+            "warning: delays of {} do not match the frame count and were \
+             fitted to it",
+            fitted.join(", ")
+        );
+    }
+    save_dmi(&icon, output).map_err(|err| {
+        CliError::new(
+            EXIT_IO,
+            format!("failed to save {}: {}", output.to_string_lossy(), err),
+        )
+    })?;
+
+    if json {
+        print_json(&conflicts)?;
+    } else {
+        for conflict in merge.conflicts() {
+            println!("{:?}: {}", conflict.key.to_string(), conflict.describe());
+        }
+    }
+    if prefer.is_none() && !conflicts.is_empty() {
+        return Err(CliError::new(
+            EXIT_CONFLICT,
+            format!(
+                "{} conflicting states were left as ours, resolve them with \
+                 `DMIAssistant --merge` or `--prefer`",
+                conflicts.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn lint(
+    paths: &[PathBuf],
+    depth: usize,
+    max_size: u32,
+    deny_warnings: bool,
+    json: bool,
+) -> Result<(), CliError> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(dmi_files_in(path, depth));
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut results: Vec<LintResult> = Vec::with_capacity(files.len());
+    for path in files {
+        let issues = match load_dmi(&path) {
+            Ok(icon) => lint_icon(&icon, max_size),
+            Err(err) => vec![LintIssue {
+                kind: LintKind::UnreadableFile,
+                severity: LintSeverity::Error,
+                state: None,
+                message: format!("failed to load: {}", err),
+            }],
+        };
+        results.push(LintResult { issues, path });
+    }
+
+    if json {
+        print_json(&results)?;
+    } else {
+        for result in &results {
+            for issue in &result.issues {
+                println!("{}: {}", result.path.to_string_lossy(), issue);
+            }
+        }
+    }
+
+    let (errors, warnings) = results
+        .iter()
+        .map(|result| count_issues(&result.issues))
+        .fold((0, 0), |total, count| {
+            (total.0 + count.0, total.1 + count.1)
+        });
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        return Err(CliError::new(
+            EXIT_LINT,
+            format!(
+                "{} errors and {} warnings in {} DMIs",
+                errors,
+                warnings,
+                results.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use dmi::icon::Looping;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "dmi_assistant_cli_{}_{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Color of the image `index` of the fixture's `walk` state.
+    fn walk_color(index: u8) -> Rgba<u8> {
+        Rgba([index * 30, 0, 0, 255])
+    }
+
+    /// Writes a DMI with a 4 dir animated `walk` and a single frame `stand`.
+    fn fixture(dir: &Path) -> PathBuf {
+        let solid = |color| {
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(32, 32, color))
+        };
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![
+                IconState {
+                    name: "walk".to_string(),
+                    dirs: 4,
+                    frames: 2,
+                    images: (0..8)
+                        .map(|index| solid(walk_color(index)))
+                        .collect(),
+                    delay: Some(vec![1.0, 2.0]),
+                    loop_flag: Looping::NTimes(NonZeroU32::new(3).unwrap()),
+                    ..Default::default()
+                },
+                IconState {
+                    name: "stand".to_string(),
+                    dirs: 1,
+                    frames: 1,
+                    images: vec![solid(Rgba([0, 255, 0, 255]))],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let path = dir.join("fixture.dmi");
+        save_dmi(&icon, &path).unwrap();
+        path
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(["DMIAssistantCLI"].iter().chain(args).copied())
+    }
+
+    #[test]
+    fn parses_extract_arguments() {
+        let Command::Extract {
+            file,
+            state,
+            dir,
+            frame,
+            output,
+        } = parse(&["extract", "a.dmi", "walk", "-o", "out.png"])
+            .unwrap()
+            .command
+        else {
+            panic!("not an extract");
+        };
+        assert_eq!(file, PathBuf::from("a.dmi"));
+        assert_eq!(state, "walk");
+        assert_eq!((dir, frame), (Directions::South, 0));
+        assert_eq!(output, PathBuf::from("out.png"));
+
+        let cli = parse(&[
+            "extract",
+            "a.dmi",
+            "walk",
+            "--dir",
+            "northeast",
+            "--frame",
+            "2",
+            "--output",
+            "out.png",
+            "--json",
+        ])
+        .unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Command::Extract {
+                dir: Directions::NorthEast,
+                frame: 2,
+                ..
+            }
+        ));
+
+        assert!(
+            parse(&["extract", "a.dmi", "walk", "--dir", "up", "-o", "x"])
+                .is_err()
+        );
+        assert!(parse(&["extract", "a.dmi", "walk"]).is_err());
+    }
+
+    #[test]
+    fn parses_merge_arguments() {
+        let cli = parse(&["merge", "a.dmi", "b.dmi", "-o", "c.dmi"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Merge {
+                on_conflict: MergeConflictPolicy::Rename,
+                ..
+            }
+        ));
+        let cli = parse(&[
+            "merge",
+            "a.dmi",
+            "b.dmi",
+            "c.dmi",
+            "--on-conflict",
+            "keep-last",
+            "-o",
+            "d.dmi",
+        ])
+        .unwrap();
+        let Command::Merge {
+            inputs,
+            on_conflict,
+            ..
+        } = cli.command
+        else {
+            panic!("not a merge");
+        };
+        assert_eq!(inputs.len(), 3);
+        assert_eq!(on_conflict, MergeConflictPolicy::KeepLast);
+        assert!(parse(&["merge", "a.dmi", "-o", "c.dmi"]).is_err());
+        assert!(
+            parse(&[
+                "merge",
+                "a.dmi",
+                "b.dmi",
+                "--on-conflict",
+                "x",
+                "-o",
+                "c"
+            ])
+            .is_err()
+        );
+
+        let cli =
+            parse(&["merge3", "o", "a", "b", "--prefer", "theirs"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Merge3 {
+                output: None,
+                prefer: Some(MergeSide::Theirs),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_lint_arguments() {
+        let cli = parse(&["--json", "lint", "icons"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Command::Lint {
+                depth: DEFAULT_RECURSION_DEPTH,
+                max_size: DEFAULT_MAX_ICON_SIZE,
+                deny_warnings: false,
+                ..
+            }
+        ));
+        assert!(parse(&["lint"]).is_err());
+        assert!(parse(&["split", "a.dmi", "-o", "b.dmi"]).is_err());
+    }
+
+    #[test]
+    fn lists_and_describes_states() {
+        let dir = temp_dir("info");
+        let path = fixture(&dir);
+        let icon = open_icon(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        let icon = icon.ok().unwrap();
+
+        assert_eq!(list_output(&icon, false).ok().unwrap(), "walk\nstand\n");
+        let names: Vec<String> =
+            serde_json::from_str(&list_output(&icon, true).ok().unwrap())
+                .unwrap();
+        assert_eq!(names, ["walk", "stand"]);
+
+        let info = info_output(Path::new("icons/a.dmi"), &icon, false)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            info,
+            "path: icons/a.dmi\n\
+             size: 32x32\n\
+             states: 2\n  \
+             \"walk\" dirs=4 frames=2 delay=[1.0, 2.0] loop=3 rewind=false \
+             movement=false\n  \
+             \"stand\" dirs=1 frames=1 delay=[] loop=indefinitely \
+             rewind=false movement=false\n"
+        );
+        let info: serde_json::Value = serde_json::from_str(
+            &info_output(Path::new("icons/a.dmi"), &icon, true)
+                .ok()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(info["width"], 32);
+        assert_eq!(info["states"][0]["loop_count"], 3);
+        assert_eq!(info["states"][1]["name"], "stand");
+    }
+
+    #[test]
+    fn extracts_frames_as_png() {
+        let dir = temp_dir("extract");
+        let path = fixture(&dir);
+        let output = dir.join("frame.out");
+        let extracted = extract(&path, "walk", Directions::North, 1, &output);
+        let written = fs::read(&output);
+        let missing = extract(&path, "walk", Directions::North, 2, &output);
+        let no_state = extract(&path, "run", Directions::South, 0, &output);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(extracted.is_ok());
+        let written = written.unwrap();
+        assert_eq!(image::guess_format(&written).unwrap(), ImageFormat::Png);
+        let frame = image::load_from_memory(&written).unwrap().to_rgba8();
+        assert_eq!(*frame.get_pixel(0, 0), walk_color(5));
+        assert!(missing.is_err_and(|err| err.code == EXIT_NOT_FOUND));
+        assert!(no_state.is_err_and(|err| err.code == EXIT_NOT_FOUND));
+    }
+}
@@ -0,0 +1,818 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use log::warn;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use regex::Regex;
+use serde::Serialize;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+/// `icon = 'file.dmi'` and other vars, ending with `icon`.
+static ICON_VAR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b(\w*icon)\s*=\s*'([^']+\.dmi)'"#).unwrap()
+});
+/// `icon_state = "name"` and other vars, ending with `icon_state`.
+static STATE_VAR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b(\w*icon_state)\s*(?:==?|!=)\s*"((?:[^"\\]|\\.)*)""#)
+        .unwrap()
+});
+/// `icon('file.dmi', "name")`, `image('file.dmi', loc, "name")` and
+/// `mutable_appearance('file.dmi', "name")`.
+static ICON_CALL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"\b(?:icon|image|mutable_appearance)\s*\(\s*(?:icon\s*=\s*)?'([^']+\.dmi)'\s*,\s*(?:[^,"()]+,\s*)?(?:icon_state\s*=\s*)?"((?:[^"\\]|\\.)*)""#,
+    )
+    .unwrap()
+});
+/// Type with the edited vars, `/obj/item{`, which is still open.
+static TYPE_BRACE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(/[\w/]+)\s*\{[^{}]*$"#).unwrap());
+/// Any DMI in single quotes.
+static DMI_LITERAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"'([^']+\.dmi)'"#).unwrap());
+/// `#include "file"`, the library ones in `<>` are skipped.
+static INCLUDE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^\s*#\s*include\s+"([^"]+)""#).unwrap());
+/// `#define FILE_DIR folder`, the folder may be quoted.
+static FILE_DIR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*#\s*define\s+FILE_DIR\s+"?([^"]*?)"?\s*$"#).unwrap()
+});
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DMSourceError {
+    #[error("Project root {0} is not a folder")]
+    NotAFolder(PathBuf),
+    #[error("{0} is neither a folder nor a .dme")]
+    NotAProject(PathBuf),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// BYOND project: its code, maps and where its resources are looked for.
+#[derive(Debug, Clone, Default)]
+pub struct DMProject {
+    pub name: String,
+    pub root: PathBuf,
+    /// Environment, which includes the code, None for a plain folder
+    pub dme: Option<PathBuf>,
+    /// Folders, where `'file.dmi'` is looked for after the root
+    pub file_dirs: Vec<PathBuf>,
+    /// `.dm` and `.dmm` files
+    pub sources: Vec<PathBuf>,
+    /// DMIs of the project
+    pub dmis: Vec<PathBuf>,
+}
+
+impl DMProject {
+    /// Opens the `.dme` or the folder. The folder with a single `.dme` is
+    /// opened as that environment.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self, DMSourceError> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let environments: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension() == Some(OsStr::new("dme")))
+                .collect();
+            match environments.as_slice() {
+                [dme] => Self::from_dme(dme),
+                _ => Self::from_root(path),
+            }
+        } else if path.extension() == Some(OsStr::new("dme")) {
+            Self::from_dme(path)
+        } else {
+            Err(DMSourceError::NotAProject(path.to_path_buf()))
+        }
+    }
+
+    /// Project of every file, included by the `.dme`, directly or through
+    /// other included files. Its DMIs are the included ones and the ones in
+    /// the `FILE_DIR` folders, see [`Self::include_referenced`] for the rest.
+    pub fn from_dme<T: AsRef<Path>>(dme: T) -> Result<Self, DMSourceError> {
+        let dme = fs::canonicalize(dme.as_ref())?;
+        let Some(root) = dme.parent().map(Path::to_path_buf) else {
+            return Err(DMSourceError::NotAProject(dme));
+        };
+        let mut project = Self {
+            name: dme
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            root,
+            dme: Some(dme.clone()),
+            ..Default::default()
+        };
+
+        let mut seen: HashSet<PathBuf> = HashSet::from([dme.clone()]);
+        let mut queue: Vec<PathBuf> = vec![dme.clone()];
+        while let Some(file) = queue.pop() {
+            let content = match fs::read(&file) {
+                Ok(content) => content,
+                Err(err) if file == dme => return Err(err.into()),
+                Err(err) => {
+                    warn!("Failed to read {}: {}", file.to_string_lossy(), err);
+                    continue;
+                }
+            };
+            project.sources.push(file.clone());
+            let directory =
+                file.parent().unwrap_or(&project.root).to_path_buf();
+            let mut included_files: Vec<PathBuf> = Vec::new();
+            for line in String::from_utf8_lossy(&content).lines() {
+                if let Some(captures) = FILE_DIR.captures(line) {
+                    let file_dir =
+                        project.root.join(captures[1].replace('\\', "/"));
+                    if let Ok(file_dir) = fs::canonicalize(file_dir)
+                        && !project.file_dirs.contains(&file_dir)
+                    {
+                        project.file_dirs.push(file_dir);
+                    }
+                    continue;
+                }
+                let Some(captures) = INCLUDE.captures(line) else {
+                    continue;
+                };
+                // Relative to the including file, then to the environment
+                let included = captures[1].replace('\\', "/");
+                let Some(included) = [&directory, &project.root]
+                    .into_iter()
+                    .map(|dir| dir.join(&included))
+                    .find(|path| path.is_file())
+                    .and_then(|path| fs::canonicalize(path).ok())
+                else {
+                    warn!(
+                        "{} includes missing {}",
+                        file.to_string_lossy(),
+                        included
+                    );
+                    continue;
+                };
+                if !seen.insert(included.clone()) {
+                    continue;
+                }
+                match included.extension().and_then(OsStr::to_str) {
+                    Some("dm" | "dme") => included_files.push(included),
+                    Some("dmm") => project.sources.push(included),
+                    Some("dmi") => project.dmis.push(included),
+                    _ => {}
+                }
+            }
+            // Popped in the include order
+            queue.extend(included_files.into_iter().rev());
+        }
+
+        for file_dir in &project.file_dirs {
+            project.dmis.extend(
+                WalkDir::new(file_dir)
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.file_type().is_file())
+                    .map(|entry| entry.into_path())
+                    .filter(|path| path.extension() == Some(OsStr::new("dmi"))),
+            );
+        }
+        project.dmis.sort();
+        project.dmis.dedup();
+        Ok(project)
+    }
+
+    /// Project of every `.dm`, `.dmm` and `.dmi` file in the folder.
+    pub fn from_root<T: AsRef<Path>>(root: T) -> Result<Self, DMSourceError> {
+        let root = fs::canonicalize(root.as_ref())?;
+        if !root.is_dir() {
+            return Err(DMSourceError::NotAFolder(root));
+        }
+        let mut sources = Vec::new();
+        let mut dmis = Vec::new();
+        for entry in WalkDir::new(&root).into_iter().filter_map(Result::ok) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.into_path();
+            match path.extension().and_then(OsStr::to_str) {
+                Some("dm" | "dmm") => sources.push(path),
+                Some("dmi") => dmis.push(path),
+                _ => {}
+            }
+        }
+        // Sorted for `contains_dmi`
+        dmis.sort();
+        Ok(Self {
+            name: root
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            root,
+            dme: None,
+            file_dirs: Vec::new(),
+            sources,
+            dmis,
+        })
+    }
+
+    /// The `.dme` or the root folder, as it was opened.
+    pub fn path(&self) -> &Path {
+        self.dme.as_deref().unwrap_or(&self.root)
+    }
+
+    /// Adds the DMIs, which the code uses, to the project ones.
+    pub fn include_referenced(&mut self, references: &DMReferences) {
+        self.dmis.extend(references.dmis.iter().cloned());
+        self.dmis.sort();
+        self.dmis.dedup();
+    }
+
+    /// The DMI belongs to the project.
+    pub fn contains_dmi(&self, path: &Path) -> bool {
+        self.dmis
+            .binary_search_by(|dmi| dmi.as_path().cmp(path))
+            .is_ok()
+    }
+
+    /// Path of the file, relative to the root, with `/` separators, as the
+    /// code has it. Files outside of the project keep the full path.
+    pub fn relative_path(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Finds the DMI, as it is written in the code, in the root and then in
+    /// the `file_dirs`.
+    pub fn resolve_dmi(&self, icon: &str) -> Option<PathBuf> {
+        let icon = icon.replace('\\', "/");
+        std::iter::once(&self.root)
+            .chain(&self.file_dirs)
+            .map(|dir| dir.join(&icon))
+            .find(|path| path.is_file())
+            .and_then(|path| fs::canonicalize(path).ok())
+    }
+}
+
+/// Use of a DMI or a state in the code or a map.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DMReference {
+    pub file: PathBuf,
+    /// Starting from 1
+    pub line: usize,
+    /// DMI as it is written in the code
+    pub icon: Option<String>,
+    /// Found DMI file, None if it does not exist
+    pub dmi: Option<PathBuf>,
+    pub state: Option<String>,
+    /// State name is built at runtime, `state` has its constant prefix
+    pub dynamic: bool,
+    /// `icon` was taken from the type or its parents, not from the same line
+    pub inherited: bool,
+    /// Trimmed line of the code
+    pub code: String,
+}
+
+impl DMReference {
+    /// `file:line`, as editors understand it.
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file.to_string_lossy(), self.line)
+    }
+}
+
+/// Every reference in the project, indexed by DMI and state.
+#[derive(Debug, Clone, Default)]
+pub struct DMReferences {
+    pub references: Vec<DMReference>,
+    /// Indices of `references` by the found DMI and the state
+    by_state: HashMap<PathBuf, HashMap<String, Vec<usize>>>,
+    /// Indices of the references to states of unknown DMIs
+    unlinked: HashMap<String, Vec<usize>>,
+    /// Indices of the references to states, which names are built at runtime
+    dynamic: Vec<usize>,
+    /// Every found DMI, referenced in any way
+    dmis: HashSet<PathBuf>,
+}
+
+impl DMReferences {
+    /// Scans all project sources on all CPU cores.
+    pub fn scan(project: &DMProject) -> Self {
+        let scanned: Vec<ScannedFile> = project
+            .sources
+            .par_iter()
+            .filter_map(|path| match fs::read(path) {
+                Ok(content) => {
+                    Some(scan_file(path, &String::from_utf8_lossy(&content)))
+                }
+                Err(err) => {
+                    warn!("Failed to read {}: {}", path.to_string_lossy(), err);
+                    None
+                }
+            })
+            .collect();
+
+        // Icons are inherited from the parent types, which may be anywhere
+        let mut type_icons: HashMap<String, String> = HashMap::new();
+        for file in &scanned {
+            type_icons.extend(file.type_icons.clone());
+        }
+        let mut resolved: HashMap<String, Option<PathBuf>> = HashMap::new();
+        let mut references = Self::default();
+        for file in scanned {
+            for (mut reference, owner_type) in file.references {
+                if reference.icon.is_none()
+                    && let Some(owner_type) = owner_type
+                {
+                    reference.icon = inherited_icon(&type_icons, &owner_type);
+                    reference.inherited = reference.icon.is_some();
+                }
+                reference.dmi = reference.icon.as_ref().and_then(|icon| {
+                    resolved
+                        .entry(icon.clone())
+                        .or_insert_with(|| project.resolve_dmi(icon))
+                        .clone()
+                });
+                references.push(reference);
+            }
+        }
+        references
+    }
+
+    fn push(&mut self, reference: DMReference) {
+        let index = self.references.len();
+        if let Some(dmi) = &reference.dmi {
+            self.dmis.insert(dmi.clone());
+        }
+        if reference.dynamic {
+            self.dynamic.push(index);
+        } else if let Some(state) = &reference.state {
+            match &reference.dmi {
+                Some(dmi) => self
+                    .by_state
+                    .entry(dmi.clone())
+                    .or_default()
+                    .entry(state.clone())
+                    .or_default()
+                    .push(index),
+                None if reference.icon.is_none() => {
+                    self.unlinked.entry(state.clone()).or_default().push(index)
+                }
+                None => {}
+            }
+        }
+        self.references.push(reference);
+    }
+
+    /// References to the state of the DMI, given by its canonical path, in
+    /// the scanning order. References to states of the same name, which DMI
+    /// is unknown, are included, they may be the same state. So are the
+    /// names, built at runtime, which start like the state. Unknown DMIs with
+    /// the names, built entirely at runtime, would match any state and are
+    /// left out.
+    pub fn of_state(&self, dmi: &Path, state: &str) -> Vec<&DMReference> {
+        let dynamic = self.dynamic.iter().filter(|index| {
+            let reference = &self.references[**index];
+            let prefix = reference.state.as_deref().unwrap_or_default();
+            let same_dmi = match &reference.dmi {
+                Some(referenced) => referenced == dmi,
+                None => reference.icon.is_none() && !prefix.is_empty(),
+            };
+            same_dmi && state.starts_with(prefix)
+        });
+        let mut indices: Vec<usize> = self
+            .by_state
+            .get(dmi)
+            .and_then(|states| states.get(state))
+            .into_iter()
+            .chain(self.unlinked.get(state))
+            .flatten()
+            .chain(dynamic)
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices
+            .into_iter()
+            .map(|index| &self.references[index])
+            .collect()
+    }
+
+    /// The DMI, given by its canonical path, is used anywhere in the code.
+    pub fn references_dmi(&self, dmi: &Path) -> bool {
+        self.dmis.contains(dmi)
+    }
+}
+
+impl FromIterator<DMReference> for DMReferences {
+    /// Indexes references with already resolved DMIs.
+    fn from_iter<T: IntoIterator<Item = DMReference>>(iter: T) -> Self {
+        let mut references = Self::default();
+        for reference in iter {
+            references.push(reference);
+        }
+        references
+    }
+}
+
+/// References of a single file with the types they are in, and icons of
+/// the types, defined in the file.
+#[derive(Debug, Default)]
+struct ScannedFile {
+    references: Vec<(DMReference, Option<String>)>,
+    type_icons: HashMap<String, String>,
+}
+
+/// Goes through the lines, keeping track of the type, which is being
+/// defined. Only the common layout with full type paths at the line start
+/// is understood.
+fn scan_file(path: &Path, content: &str) -> ScannedFile {
+    // Icons, edited on the map, do not change the type
+    let is_map = path.extension() == Some(OsStr::new("dmm"));
+    let mut scanned = ScannedFile::default();
+    let mut current_type: Option<String> = None;
+    // Indentation of the proc, which body is being read
+    let mut proc_indent: Option<usize> = None;
+    let mut in_comment = false;
+    for (line_index, raw_line) in content.lines().enumerate() {
+        let line = strip_comments(raw_line, &mut in_comment);
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if proc_indent.is_some_and(|proc_indent| indent <= proc_indent) {
+            proc_indent = None;
+        }
+        if indent == 0 && !trimmed.starts_with('#') {
+            current_type = match trimmed.split_once('(') {
+                Some((proc_path, _)) => {
+                    proc_indent = Some(0);
+                    proc_owner(proc_path)
+                }
+                None if !trimmed.contains('=') => Some(type_path(trimmed)),
+                None => None,
+            };
+        } else if indent > 0 && proc_indent.is_none() {
+            if is_block_proc(trimmed) {
+                proc_indent = Some(indent);
+            } else if !is_map
+                && let Some(captures) = ICON_VAR.captures(trimmed)
+                && &captures[1] == "icon"
+                && let Some(current_type) = &current_type
+            {
+                scanned
+                    .type_icons
+                    .insert(current_type.clone(), captures[2].to_string());
+            }
+        }
+
+        let reference = |icon: Option<&str>, state: Option<&str>| {
+            let (state, dynamic) = match state {
+                Some(state) => match state.split_once('[') {
+                    Some((prefix, _)) => (Some(prefix.to_string()), true),
+                    None => (Some(state.to_string()), false),
+                },
+                None => (None, false),
+            };
+            DMReference {
+                file: path.to_path_buf(),
+                line: line_index + 1,
+                icon: icon.map(str::to_string),
+                dmi: None,
+                state,
+                dynamic,
+                inherited: false,
+                code: raw_line.trim().to_string(),
+            }
+        };
+
+        let line_icon = ICON_VAR
+            .captures_iter(&line)
+            .find(|captures| &captures[1] == "icon")
+            .map(|captures| captures[2].to_string());
+        let mut calls = Vec::new();
+        for captures in ICON_CALL.captures_iter(&line) {
+            scanned.references.push((
+                reference(Some(&captures[1]), Some(&captures[2])),
+                None,
+            ));
+            calls.push(captures.get(0).unwrap().range());
+        }
+        let mut found_any = !calls.is_empty();
+        for captures in STATE_VAR.captures_iter(&line) {
+            let whole = captures.get(0).unwrap();
+            if calls.iter().any(|call| call.contains(&whole.start())) {
+                continue;
+            }
+            // Only `icon_state` belongs to `icon`, `inhand_icon_state` and
+            // the like use other files
+            let linked = &captures[1] == "icon_state";
+            let icon = if linked { line_icon.as_deref() } else { None };
+            let owner_type = if linked && icon.is_none() {
+                // `/obj/item{icon_state = "name"}` in maps and the code
+                TYPE_BRACE
+                    .captures(&line[..whole.start()])
+                    .map(|captures| captures[1].to_string())
+                    .or_else(|| current_type.clone())
+            } else {
+                None
+            };
+            scanned
+                .references
+                .push((reference(icon, Some(&captures[2])), owner_type));
+            found_any = true;
+        }
+        if !found_any {
+            for captures in DMI_LITERAL.captures_iter(&line) {
+                scanned
+                    .references
+                    .push((reference(Some(&captures[1]), None), None));
+            }
+        }
+    }
+    scanned
+}
+
+/// Removes `//` and `/* */` comments, which may span several lines. Comment
+/// markers inside `"strings"` and `'files'` are kept.
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let bytes = line.as_bytes();
+    let mut stripped = String::with_capacity(line.len());
+    // Start of the code after the last comment
+    let mut kept_from = 0;
+    let mut quote: Option<u8> = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        let next = bytes.get(index + 1).copied();
+        if *in_comment {
+            if bytes[index] == b'*' && next == Some(b'/') {
+                *in_comment = false;
+                index += 2;
+                kept_from = index;
+            } else {
+                index += 1;
+            }
+            continue;
+        }
+        match (quote, bytes[index], next) {
+            // Escaped character, like `\"`
+            (Some(_), b'\\', _) => index += 2,
+            (Some(open), byte, _) if byte == open => {
+                quote = None;
+                index += 1;
+            }
+            (Some(_), _, _) => index += 1,
+            (None, byte @ (b'"' | b'\''), _) => {
+                quote = Some(byte);
+                index += 1;
+            }
+            (None, b'/', Some(b'/')) => {
+                stripped.push_str(&line[kept_from..index]);
+                return stripped;
+            }
+            (None, b'/', Some(b'*')) => {
+                stripped.push_str(&line[kept_from..index]);
+                *in_comment = true;
+                index += 2;
+            }
+            (None, _, _) => index += 1,
+        }
+    }
+    if !*in_comment {
+        stripped.push_str(&line[kept_from..]);
+    }
+    stripped
+}
+
+/// `obj/item{` is `/obj/item`.
+fn type_path(line: &str) -> String {
+    let path = line.trim_end_matches(['{', ' ', '\t', ',', ')']);
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+/// Type of `/obj/item/proc/attack` and `/obj/item/attack`.
+fn proc_owner(proc_path: &str) -> Option<String> {
+    let path = type_path(proc_path.trim());
+    let (owner, _) = path.rsplit_once('/')?;
+    let owner = owner
+        .strip_suffix("/proc")
+        .or_else(|| owner.strip_suffix("/verb"))
+        .unwrap_or(owner);
+    (!owner.is_empty()).then(|| owner.to_string())
+}
+
+/// `proc/name()` or `name()` inside a type block.
+fn is_block_proc(trimmed: &str) -> bool {
+    trimmed.ends_with(')')
+        && !trimmed.contains('=')
+        && trimmed
+            .split_once('(')
+            .is_some_and(|(name, _)| !name.contains(' ') && !name.is_empty())
+}
+
+/// Icon of the type or its closest parent.
+fn inherited_icon(
+    type_icons: &HashMap<String, String>,
+    owner_type: &str,
+) -> Option<String> {
+    let mut current = owner_type;
+    loop {
+        if let Some(icon) = type_icons.get(current) {
+            return Some(icon.clone());
+        }
+        current = current.rsplit_once('/')?.0;
+        if current.is_empty() {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Icon, state, dynamic flag and owner type of every reference.
+    type Found<'a> = (Option<&'a str>, Option<&'a str>, bool, Option<&'a str>);
+
+    fn found(scanned: &ScannedFile) -> Vec<Found<'_>> {
+        scanned
+            .references
+            .iter()
+            .map(|(reference, owner)| {
+                (
+                    reference.icon.as_deref(),
+                    reference.state.as_deref(),
+                    reference.dynamic,
+                    owner.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_type_icon_and_state() {
+        let scanned = scan_file(
+            Path::new("item.dm"),
+            "/obj/item\n\
+             \ticon = 'icons/obj/item.dmi'\n\
+             \ticon_state = \"item\"\n\
+             \tinhand_icon_state = \"held\"\n",
+        );
+        assert_eq!(
+            scanned.type_icons,
+            HashMap::from([(
+                "/obj/item".to_string(),
+                "icons/obj/item.dmi".to_string()
+            )])
+        );
+        assert_eq!(
+            found(&scanned),
+            [
+                (Some("icons/obj/item.dmi"), None, false, None),
+                (None, Some("item"), false, Some("/obj/item")),
+                (None, Some("held"), false, None),
+            ]
+        );
+        assert_eq!(scanned.references[1].0.line, 3);
+    }
+
+    #[test]
+    fn finds_icon_on_the_same_line() {
+        let scanned = scan_file(
+            Path::new("item.dm"),
+            "/obj/item/proc/update()\n\
+             \tif(icon_state == \"off\") icon = 'icons/obj/on.dmi'\n",
+        );
+        assert_eq!(
+            found(&scanned),
+            [(Some("icons/obj/on.dmi"), Some("off"), false, None)]
+        );
+    }
+
+    #[test]
+    fn finds_icon_calls() {
+        let scanned = scan_file(
+            Path::new("item.dm"),
+            "/obj/item/proc/overlays()\n\
+             \tvar/icon/base = icon('icons/obj/x.dmi', \"y\")\n\
+             \treturn image('icons/obj/z.dmi', src, \"light_[dir]\")\n\
+             \tnew /mutable_appearance('m.dmi', icon_state = \"m\")\n",
+        );
+        assert_eq!(
+            found(&scanned),
+            [
+                (Some("icons/obj/x.dmi"), Some("y"), false, None),
+                (Some("icons/obj/z.dmi"), Some("light_"), true, None),
+                (Some("m.dmi"), Some("m"), false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_map_blocks() {
+        let scanned = scan_file(
+            Path::new("station.dmm"),
+            "\"a\" = (\n\
+             /obj/item{icon_state = \"broken\"; name = \"x\"},\n\
+             /obj/item{icon = 'icons/obj/map.dmi'; icon_state = \"map\"},\n\
+             /turf/floor)\n",
+        );
+        assert!(scanned.type_icons.is_empty());
+        assert_eq!(
+            found(&scanned),
+            [
+                (None, Some("broken"), false, Some("/obj/item")),
+                (Some("icons/obj/map.dmi"), Some("map"), false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_comment_markers_in_strings() {
+        let mut in_comment = false;
+        assert_eq!(
+            strip_comments(r#"desc = "a//b" // comment"#, &mut in_comment),
+            r#"desc = "a//b" "#
+        );
+        assert_eq!(
+            strip_comments(r#"desc = "/* x */" /* gone */ y"#, &mut in_comment),
+            r#"desc = "/* x */"  y"#
+        );
+        assert_eq!(
+            strip_comments(r#"icon = 'a//b.dmi' // c"#, &mut in_comment),
+            "icon = 'a//b.dmi' "
+        );
+        assert_eq!(
+            strip_comments(r#"desc = "\"//" // c"#, &mut in_comment),
+            r#"desc = "\"//" "#
+        );
+        assert!(!in_comment);
+    }
+
+    #[test]
+    fn strips_multi_line_comments() {
+        let mut in_comment = false;
+        assert_eq!(strip_comments("a = 1 /* start", &mut in_comment), "a = 1 ");
+        assert!(in_comment);
+        assert_eq!(strip_comments("icon_state = \"x\"", &mut in_comment), "");
+        assert_eq!(strip_comments("end */ b = 2", &mut in_comment), " b = 2");
+        assert!(!in_comment);
+
+        let scanned = scan_file(
+            Path::new("item.dm"),
+            "/obj/item\n\
+             \t/*\n\
+             \ticon_state = \"hidden\"\n\
+             \t*/\n\
+             \ticon_state = \"shown\" // icon_state = \"commented\"\n",
+        );
+        assert_eq!(
+            found(&scanned),
+            [(None, Some("shown"), false, Some("/obj/item"))]
+        );
+    }
+
+    #[test]
+    fn inherits_icon_from_closest_parent() {
+        let type_icons = HashMap::from([
+            ("/obj/item".to_string(), "item.dmi".to_string()),
+            (
+                "/obj/item/gun/special".to_string(),
+                "special.dmi".to_string(),
+            ),
+        ]);
+        let inherited = |owner| inherited_icon(&type_icons, owner);
+        assert_eq!(inherited("/obj/item").as_deref(), Some("item.dmi"));
+        assert_eq!(
+            inherited("/obj/item/gun/energy").as_deref(),
+            Some("item.dmi")
+        );
+        assert_eq!(
+            inherited("/obj/item/gun/special/big").as_deref(),
+            Some("special.dmi")
+        );
+        assert_eq!(inherited("/obj"), None);
+        assert_eq!(inherited("/mob/living"), None);
+    }
+
+    #[test]
+    fn reads_type_paths() {
+        assert_eq!(type_path("obj/item{"), "/obj/item");
+        assert_eq!(
+            proc_owner("/obj/item/proc/attack"),
+            Some("/obj/item".into())
+        );
+        assert_eq!(proc_owner("/obj/item/attack"), Some("/obj/item".into()));
+        assert_eq!(proc_owner("/proc/global"), None);
+    }
+}
@@ -0,0 +1,504 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{Display, Write},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rayon::iter::{
+    IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use serde::Serialize;
+
+use crate::{
+    dm_source::{DMProject, DMReference, DMReferences},
+    dmi_index::{IndexEntry, index_dmi},
+    search::{MatchMode, Matcher, SearchError},
+    utils::csv_field,
+};
+
+/// State, which is never referenced in the project code or maps.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct UnusedState {
+    pub path: PathBuf,
+    pub state: String,
+}
+
+/// Candidates for removal from the project DMIs.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UnusedReport {
+    pub root: PathBuf,
+    pub states: Vec<UnusedState>,
+    pub checked_dmis: usize,
+    pub checked_states: usize,
+    /// DMIs, which could not be loaded, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl UnusedReport {
+    /// Path of the DMI, relative to the project root.
+    pub fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    /// Unused states, grouped by DMI.
+    pub fn to_text(&self) -> String {
+        let mut report = format!(
+            "Unused states of {}: {} of {} states in {} DMIs\n",
+            self.root.to_string_lossy(),
+            self.states.len(),
+            self.checked_states,
+            self.checked_dmis
+        );
+        let mut last_path: Option<&PathBuf> = None;
+        for unused in &self.states {
+            if last_path != Some(&unused.path) {
+                let _ = writeln!(
+                    report,
+                    "\n{}",
+                    self.relative(&unused.path).to_string_lossy()
+                );
+                last_path = Some(&unused.path);
+            }
+            let _ = writeln!(report, "    {:?}", unused.state);
+        }
+        for (path, err) in &self.failed {
+            let _ = writeln!(
+                report,
+                "\n{} (failed to load): {}",
+                self.relative(path).to_string_lossy(),
+                err
+            );
+        }
+        report
+    }
+
+    /// `dmi,state` lines with a header.
+    pub fn to_csv(&self) -> String {
+        let mut report = String::from("dmi,state\n");
+        for unused in &self.states {
+            let _ = writeln!(
+                report,
+                "{},{}",
+                csv_field(self.relative(&unused.path).to_string_lossy()),
+                csv_field(&unused.state)
+            );
+        }
+        report
+    }
+}
+
+/// Globs of the state names, separated by spaces.
+pub fn parse_allow_list(patterns: &str) -> Result<Vec<Matcher>, SearchError> {
+    patterns
+        .split_whitespace()
+        .map(|pattern| Matcher::new(pattern, MatchMode::Glob))
+        .collect()
+}
+
+/// Checks every state of the project DMIs. A state is used if the code
+/// names it, if a name, built at runtime, may be it, or if it matches the
+/// `allowed` patterns. The unnamed state is used by any reference to the
+/// DMI. Indexed DMIs are not parsed again, unless they changed.
+pub fn find_unused(
+    project: &DMProject,
+    references: &DMReferences,
+    index: &BTreeMap<PathBuf, IndexEntry>,
+    allowed: &[Matcher],
+) -> UnusedReport {
+    let checked: Vec<(PathBuf, Result<Vec<String>, String>)> = project
+        .dmis
+        .par_iter()
+        .map(|path| {
+            let state_names = index_dmi(path, index.get(path))
+                .map(|(entry, _)| entry.state_names())
+                .map_err(|err| err.to_string());
+            (path.clone(), state_names)
+        })
+        .collect();
+
+    let mut report = UnusedReport {
+        root: project.root.clone(),
+        ..Default::default()
+    };
+    let mut unused: BTreeSet<UnusedState> = BTreeSet::new();
+    for (path, state_names) in checked {
+        let state_names = match state_names {
+            Ok(state_names) => state_names,
+            Err(err) => {
+                report.failed.push((path, err));
+                continue;
+            }
+        };
+        report.checked_dmis += 1;
+        report.checked_states += state_names.len();
+        let dmi = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        for state in state_names {
+            let used =
+                allowed.iter().any(|pattern| pattern.find(&state).is_some())
+                    || (state.is_empty() && references.references_dmi(&dmi))
+                    || !references.of_state(&dmi, &state).is_empty();
+            if !used {
+                unused.insert(UnusedState {
+                    path: path.clone(),
+                    state,
+                });
+            }
+        }
+    }
+    report.states = unused.into_iter().collect();
+    report.failed.sort();
+    report
+}
+
+/// What a broken reference points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MissingKind {
+    /// The DMI file does not exist
+    Dmi,
+    /// The DMI has no such state, BYOND shows its unnamed state instead, if
+    /// it has one
+    State { fallback: bool },
+}
+
+impl Display for MissingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MissingKind::Dmi => write!(f, "DMI does not exist"),
+            MissingKind::State { fallback: true } => {
+                write!(f, "No such state, the unnamed state is shown instead")
+            }
+            MissingKind::State { fallback: false } => {
+                write!(f, "No such state, nothing is shown")
+            }
+        }
+    }
+}
+
+/// Reference to a nonexistent DMI or state.
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingReference {
+    pub reference: DMReference,
+    pub kind: MissingKind,
+}
+
+/// Broken references in the project code and maps.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MissingReport {
+    pub root: PathBuf,
+    /// In the scanning order
+    pub missing: Vec<MissingReference>,
+    /// Literal references, which were checked
+    pub checked: usize,
+    /// DMIs, which could not be loaded, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl MissingReport {
+    /// `file:line`, relative to the project root.
+    pub fn location(&self, reference: &DMReference) -> String {
+        format!(
+            "{}:{}",
+            reference
+                .file
+                .strip_prefix(&self.root)
+                .unwrap_or(&reference.file)
+                .to_string_lossy(),
+            reference.line
+        )
+    }
+}
+
+/// Checks every reference with a known DMI and a name, which is not built
+/// at runtime. Indexed DMIs are not parsed again, unless they changed.
+pub fn find_missing(
+    references: &DMReferences,
+    root: &Path,
+    index: &BTreeMap<PathBuf, IndexEntry>,
+) -> MissingReport {
+    let checked: Vec<&DMReference> = references
+        .references
+        .iter()
+        .filter(|reference| reference.icon.is_some() && !reference.dynamic)
+        .collect();
+    let used_dmis: BTreeSet<&PathBuf> = checked
+        .iter()
+        .filter(|reference| reference.state.is_some())
+        .filter_map(|reference| reference.dmi.as_ref())
+        .collect();
+    let states: HashMap<&PathBuf, Result<HashSet<String>, String>> = used_dmis
+        .into_par_iter()
+        .map(|dmi| {
+            let state_names = index_dmi(dmi, index.get(dmi))
+                .map(|(entry, _)| entry.state_names().into_iter().collect())
+                .map_err(|err| err.to_string());
+            (dmi, state_names)
+        })
+        .collect();
+
+    let mut report = MissingReport {
+        root: root.to_path_buf(),
+        checked: checked.len(),
+        ..Default::default()
+    };
+    for reference in checked {
+        let kind = match (&reference.dmi, &reference.state) {
+            (None, _) => MissingKind::Dmi,
+            (Some(dmi), Some(state)) => match states.get(dmi) {
+                Some(Ok(state_names)) if !state_names.contains(state) => {
+                    MissingKind::State {
+                        fallback: state_names.contains(""),
+                    }
+                }
+                _ => continue,
+            },
+            (Some(_), None) => continue,
+        };
+        report.missing.push(MissingReference {
+            reference: reference.clone(),
+            kind,
+        });
+    }
+    report.failed = states
+        .into_iter()
+        .filter_map(|(dmi, state_names)| {
+            Some((dmi.clone(), state_names.err()?))
+        })
+        .collect();
+    report.failed.sort();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmi_index::{FileStamp, IndexedState};
+
+    use super::*;
+
+    /// Project folder with `a.dmi` and `b.dmi`, indexed with the given
+    /// states, so they are never parsed. Returns the folder, the DMIs and
+    /// the index.
+    fn project_files(
+        test: &str,
+        a_states: &[&str],
+        b_states: &[&str],
+    ) -> (PathBuf, [PathBuf; 2], BTreeMap<PathBuf, IndexEntry>) {
+        let dir = std::env::temp_dir().join(format!(
+            "dmi_assistant_usage_{}_{}",
+            test,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+        let mut index = BTreeMap::new();
+        let mut dmis = Vec::new();
+        for (name, states) in [("a.dmi", a_states), ("b.dmi", b_states)] {
+            let path = dir.join(name);
+            fs::write(&path, name).unwrap();
+            let metadata = fs::metadata(&path).unwrap();
+            let states = states
+                .iter()
+                .map(|state| IndexedState {
+                    name: state.to_string(),
+                    dirs: 1,
+                    frames: 1,
+                    delays: Vec::new(),
+                    movement: false,
+                    rewind: false,
+                    hashes: Vec::new(),
+                    warnings: Vec::new(),
+                })
+                .collect();
+            let entry = IndexEntry {
+                stamp: FileStamp {
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                    hash: String::new(),
+                },
+                width: 32,
+                height: 32,
+                states,
+            };
+            index.insert(path.clone(), entry);
+            dmis.push(path);
+        }
+        let [a, b] = <[PathBuf; 2]>::try_from(dmis).unwrap();
+        (dir, [a, b], index)
+    }
+
+    fn reference(
+        icon: Option<&str>,
+        dmi: Option<&Path>,
+        state: Option<&str>,
+        dynamic: bool,
+    ) -> DMReference {
+        DMReference {
+            file: PathBuf::from("code.dm"),
+            line: 1,
+            icon: icon.map(str::to_string),
+            dmi: dmi.map(Path::to_path_buf),
+            state: state.map(str::to_string),
+            dynamic,
+            inherited: false,
+            code: String::new(),
+        }
+    }
+
+    fn unused_states(report: &UnusedReport) -> Vec<(&str, &str)> {
+        report
+            .states
+            .iter()
+            .map(|unused| {
+                let dmi = unused.path.file_name().unwrap().to_str().unwrap();
+                (dmi, unused.state.as_str())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_unused_states() {
+        let (dir, [a, b], index) = project_files(
+            "unused",
+            &["", "used", "light_1", "light_2", "debug_x", "old"],
+            &["", "other", "guessed"],
+        );
+        let project = DMProject {
+            root: dir.clone(),
+            dmis: vec![a.clone(), b.clone()],
+            ..Default::default()
+        };
+        let references: DMReferences = [
+            reference(Some("a.dmi"), Some(&a), Some("used"), false),
+            // `"light_[dir]"`
+            reference(Some("a.dmi"), Some(&a), Some("light_"), true),
+            // State of an unknown DMI may be any of them
+            reference(None, None, Some("guessed"), false),
+        ]
+        .into_iter()
+        .collect();
+        let allowed = parse_allow_list("debug_* nothing").unwrap();
+
+        let report = find_unused(&project, &references, &index, &allowed);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.checked_dmis, 2);
+        assert_eq!(report.checked_states, 9);
+        assert!(report.failed.is_empty());
+        // Unnamed state of b.dmi is unused, as nothing refers to the file
+        assert_eq!(
+            unused_states(&report),
+            [("a.dmi", "old"), ("b.dmi", ""), ("b.dmi", "other")]
+        );
+    }
+
+    #[test]
+    fn runtime_names_of_unknown_dmis_use_nothing() {
+        let (dir, [a, b], index) =
+            project_files("unknown_runtime", &["a"], &["b"]);
+        let project = DMProject {
+            root: dir.clone(),
+            dmis: vec![a, b],
+            ..Default::default()
+        };
+        // `icon_state = "[state]"` with no known icon
+        let references: DMReferences = [reference(None, None, Some(""), true)]
+            .into_iter()
+            .collect();
+
+        let report = find_unused(&project, &references, &index, &[]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unused_states(&report), [("a.dmi", "a"), ("b.dmi", "b")]);
+    }
+
+    #[test]
+    fn reports_unloadable_dmis() {
+        let (dir, [a, _], index) = project_files("failed", &["a"], &[]);
+        let missing = dir.join("missing.dmi");
+        let project = DMProject {
+            root: dir.clone(),
+            dmis: vec![a, missing.clone()],
+            ..Default::default()
+        };
+
+        let report =
+            find_unused(&project, &DMReferences::default(), &index, &[]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.checked_dmis, 1);
+        assert_eq!(unused_states(&report), [("a.dmi", "a")]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, missing);
+    }
+
+    #[test]
+    fn finds_missing_files_and_states() {
+        let (dir, [a, b], index) =
+            project_files("missing", &["", "used"], &["other"]);
+        let references: DMReferences = [
+            reference(Some("gone.dmi"), None, Some("x"), false),
+            reference(Some("gone.dmi"), None, None, false),
+            reference(Some("a.dmi"), Some(&a), Some("nope"), false),
+            reference(Some("b.dmi"), Some(&b), Some("nope"), false),
+            reference(Some("a.dmi"), Some(&a), Some("used"), false),
+            reference(Some("b.dmi"), Some(&b), None, false),
+            // Built at runtime or with an unknown DMI, can not be checked
+            reference(Some("a.dmi"), Some(&a), Some("nope_"), true),
+            reference(None, None, Some("nope"), false),
+        ]
+        .into_iter()
+        .collect();
+
+        let report = find_missing(&references, &dir, &index);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.checked, 6);
+        assert!(report.failed.is_empty());
+        let missing: Vec<(&str, Option<&str>, MissingKind)> = report
+            .missing
+            .iter()
+            .map(|missing| {
+                (
+                    missing.reference.icon.as_deref().unwrap(),
+                    missing.reference.state.as_deref(),
+                    missing.kind,
+                )
+            })
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                ("gone.dmi", Some("x"), MissingKind::Dmi),
+                ("gone.dmi", None, MissingKind::Dmi),
+                ("a.dmi", Some("nope"), MissingKind::State { fallback: true }),
+                (
+                    "b.dmi",
+                    Some("nope"),
+                    MissingKind::State { fallback: false }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unloadable_referenced_dmis() {
+        let (dir, _, index) = project_files("missing_failed", &[], &[]);
+        let broken = dir.join("broken.dmi");
+        fs::write(&broken, "not a dmi").unwrap();
+        let references: DMReferences = [reference(
+            Some("broken.dmi"),
+            Some(&broken),
+            Some("x"),
+            false,
+        )]
+        .into_iter()
+        .collect();
+
+        let report = find_missing(&references, &dir, &index);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.missing.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, broken);
+    }
+}
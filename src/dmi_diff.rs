@@ -0,0 +1,453 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use dmi::icon::{Icon, Looping};
+use image::{DynamicImage, Rgba, RgbaImage, imageops::FilterType};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    dmi_merge::{StateKey, keyed_states},
+    dmi_model::ParsedState,
+    dmi_utils::Directions,
+    screens::viewer::StateboxResizing,
+};
+
+/// Color of the changed pixels in the difference overlay.
+pub const OVERLAY_CHANGED_COLOR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+/// Unchanged pixels are drawn with their alpha divided by this.
+const OVERLAY_FADE: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StateChangeKind {
+    Added,
+    Removed,
+    /// Same pixels under a different name
+    Renamed,
+    Modified,
+}
+
+impl Display for StateChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Added => "Added",
+            Self::Removed => "Removed",
+            Self::Renamed => "Renamed",
+            Self::Modified => "Modified",
+        })
+    }
+}
+
+/// Old and new versions of a single frame.
+#[derive(Debug, Clone)]
+pub struct FrameDiff {
+    pub dir: Directions,
+    pub frame: usize,
+    pub old: Option<DynamicImage>,
+    pub new: Option<DynamicImage>,
+    /// New frame with the unchanged pixels faded and the changed ones painted
+    /// with [`OVERLAY_CHANGED_COLOR`]
+    pub overlay: DynamicImage,
+    pub changed_pixels: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct StateDiff {
+    pub kind: StateChangeKind,
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+    /// Movement flag of the new state, or the old one if the state was
+    /// removed
+    pub movement: bool,
+    /// Human-readable descriptions like `delay: [1.0] -> [2.0]`
+    pub metadata_changes: Vec<String>,
+    /// Changed frames for modified states, every frame for added and removed
+    /// ones, nothing for renamed
+    pub frames: Vec<FrameDiff>,
+}
+
+impl StateDiff {
+    /// New name, or the old one if the state was removed.
+    pub fn name(&self) -> &str {
+        self.new_name
+            .as_deref()
+            .or(self.old_name.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// Differences between two versions of a DMI.
+#[derive(Debug, Clone, Default)]
+pub struct DMIDiff {
+    /// Width and height
+    pub old_size: (u32, u32),
+    pub new_size: (u32, u32),
+    /// Changed states only, sorted by kind and name
+    pub states: Vec<StateDiff>,
+    /// Count of states present in both versions without any changes
+    pub unchanged: usize,
+}
+
+impl DMIDiff {
+    pub fn is_empty(&self) -> bool {
+        self.old_size == self.new_size && self.states.is_empty()
+    }
+
+    pub fn count(&self, kind: StateChangeKind) -> usize {
+        self.states
+            .iter()
+            .filter(|state| state.kind == kind)
+            .count()
+    }
+}
+
+/// Parses the states of both icons in their original size and compares
+/// them. States are matched by name and movement flag, several states with
+/// the same both are matched in order.
+pub fn diff_icons(old: Icon, new: Icon) -> DMIDiff {
+    let old_size = (old.width, old.height);
+    let new_size = (new.width, new.height);
+    let parse = |icon: Icon| -> HashMap<StateKey, ParsedState> {
+        keyed_states(icon.states)
+            .into_par_iter()
+            .map(|(key, state)| {
+                let parsed = ParsedState::parse_from_raw(
+                    state,
+                    StateboxResizing::Original,
+                    FilterType::Nearest,
+                );
+                (key, parsed)
+            })
+            .collect()
+    };
+    let old_states = parse(old);
+    let new_states = parse(new);
+
+    let mut states: Vec<StateDiff> = Vec::new();
+    let mut unchanged = 0;
+
+    let mut removed: Vec<&ParsedState> = Vec::new();
+    for (key, old_state) in &old_states {
+        match new_states.get(key) {
+            Some(new_state) => {
+                let metadata_changes = metadata_changes(old_state, new_state);
+                let frames = frame_diffs(Some(old_state), Some(new_state));
+                if metadata_changes.is_empty() && frames.is_empty() {
+                    unchanged += 1;
+                    continue;
+                }
+                states.push(StateDiff {
+                    kind: StateChangeKind::Modified,
+                    old_name: Some(key.name.clone()),
+                    new_name: Some(key.name.clone()),
+                    movement: key.movement,
+                    metadata_changes,
+                    frames,
+                });
+            }
+            None => removed.push(old_state),
+        }
+    }
+
+    // Blank states have no hash, so they are never taken for renames
+    let mut added: HashMap<Option<u64>, Vec<&ParsedState>> = HashMap::new();
+    for (key, new_state) in &new_states {
+        if !old_states.contains_key(key) {
+            added
+                .entry(pixel_hash(new_state))
+                .or_default()
+                .push(new_state);
+        }
+    }
+    // Several states may share the same pixels, match them in name order
+    let order = |state: &&ParsedState| (state.name.clone(), state.movement);
+    for candidates in added.values_mut() {
+        candidates.sort_by_key(|state| std::cmp::Reverse(order(state)));
+    }
+    removed.sort_by_key(order);
+
+    for old_state in removed {
+        let renamed_to = pixel_hash(old_state)
+            .and_then(|hash| added.get_mut(&Some(hash)))
+            .and_then(|candidates| candidates.pop());
+        match renamed_to {
+            Some(new_state) => states.push(StateDiff {
+                kind: StateChangeKind::Renamed,
+                old_name: Some(old_state.name.clone()),
+                new_name: Some(new_state.name.clone()),
+                movement: new_state.movement,
+                metadata_changes: metadata_changes(old_state, new_state),
+                frames: Vec::new(),
+            }),
+            None => states.push(StateDiff {
+                kind: StateChangeKind::Removed,
+                old_name: Some(old_state.name.clone()),
+                new_name: None,
+                movement: old_state.movement,
+                metadata_changes: Vec::new(),
+                frames: frame_diffs(Some(old_state), None),
+            }),
+        }
+    }
+    for new_state in added.into_values().flatten() {
+        states.push(StateDiff {
+            kind: StateChangeKind::Added,
+            old_name: None,
+            new_name: Some(new_state.name.clone()),
+            movement: new_state.movement,
+            metadata_changes: Vec::new(),
+            frames: frame_diffs(None, Some(new_state)),
+        });
+    }
+
+    states.sort_by(|a, b| {
+        (a.kind, a.name(), a.movement).cmp(&(b.kind, b.name(), b.movement))
+    });
+    DMIDiff {
+        old_size,
+        new_size,
+        states,
+        unchanged,
+    }
+}
+
+/// Hash of the state's dimensions and original pixels, used to detect renames.
+/// None for blank states, which all look the same.
+pub fn pixel_hash(state: &ParsedState) -> Option<u64> {
+    if state
+        .dirs
+        .values()
+        .flat_map(|dir| &dir.original_frames)
+        .all(|frame| frame.to_rgba8().pixels().all(|pixel| pixel[3] == 0))
+    {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    state.frames.hash(&mut hasher);
+    for (dir, dir_image) in &state.dirs {
+        dir.hash(&mut hasher);
+        for frame in &dir_image.original_frames {
+            frame.width().hash(&mut hasher);
+            frame.height().hash(&mut hasher);
+            frame.to_rgba8().as_raw().hash(&mut hasher);
+        }
+    }
+    Some(hasher.finish())
+}
+
+fn loop_count(loop_flag: &Looping) -> u32 {
+    match loop_flag {
+        Looping::Indefinitely => 0,
+        Looping::NTimes(num) => num.get(),
+    }
+}
+
+fn metadata_changes(old: &ParsedState, new: &ParsedState) -> Vec<String> {
+    let mut changes: Vec<String> = Vec::new();
+    if old.dirs.len() != new.dirs.len() {
+        changes.push(format!("dirs: {} -> {}", old.dirs.len(), new.dirs.len()));
+    }
+    if old.frames != new.frames {
+        changes.push(format!("frames: {} -> {}", old.frames, new.frames));
+    }
+    if old.delay != new.delay {
+        changes.push(format!(
+            "delay: {:?} -> {:?}",
+            old.delay.as_deref().unwrap_or_default(),
+            new.delay.as_deref().unwrap_or_default()
+        ));
+    }
+    let (old_loop, new_loop) =
+        (loop_count(&old.loop_flag), loop_count(&new.loop_flag));
+    if old_loop != new_loop {
+        let describe = |count: u32| match count {
+            0 => "indefinitely".to_string(),
+            count => count.to_string(),
+        };
+        changes.push(format!(
+            "loop: {} -> {}",
+            describe(old_loop),
+            describe(new_loop)
+        ));
+    }
+    if old.rewind != new.rewind {
+        changes.push(format!("rewind: {} -> {}", old.rewind, new.rewind));
+    }
+    if old.movement != new.movement {
+        changes.push(format!("movement: {} -> {}", old.movement, new.movement));
+    }
+    changes
+}
+
+/// Compares every frame of every direction. If both states are present, only
+/// changed frames are returned.
+fn frame_diffs(
+    old: Option<&ParsedState>,
+    new: Option<&ParsedState>,
+) -> Vec<FrameDiff> {
+    let dirs: BTreeSet<Directions> = old
+        .into_iter()
+        .chain(new)
+        .flat_map(|state| state.dirs.keys().copied())
+        .collect();
+    let frames = old
+        .into_iter()
+        .chain(new)
+        .map(|state| state.frames)
+        .max()
+        .unwrap_or_default() as usize;
+    let only_changed = old.is_some() && new.is_some();
+
+    let mut diffs: Vec<FrameDiff> = Vec::new();
+    for dir in dirs {
+        for frame in 0..frames {
+            let old_frame =
+                old.and_then(|state| state.get_original_frame(&dir, frame));
+            let new_frame =
+                new.and_then(|state| state.get_original_frame(&dir, frame));
+            if old_frame.is_none() && new_frame.is_none() {
+                continue;
+            }
+            let (overlay, changed_pixels) = overlay(old_frame, new_frame);
+            if only_changed && changed_pixels == 0 {
+                continue;
+            }
+            diffs.push(FrameDiff {
+                dir,
+                frame,
+                old: old_frame.cloned(),
+                new: new_frame.cloned(),
+                overlay,
+                changed_pixels,
+            });
+        }
+    }
+    diffs
+}
+
+/// Builds the difference overlay of two frames, which may be missing or have
+/// different sizes. Missing pixels are treated as transparent, and all fully
+/// transparent pixels are considered equal.
+pub fn overlay(
+    old: Option<&DynamicImage>,
+    new: Option<&DynamicImage>,
+) -> (DynamicImage, u32) {
+    let old = old.map(|image| image.to_rgba8());
+    let new = new.map(|image| image.to_rgba8());
+    let width = old
+        .iter()
+        .chain(&new)
+        .map(|image| image.width())
+        .max()
+        .unwrap_or_default();
+    let height = old
+        .iter()
+        .chain(&new)
+        .map(|image| image.height())
+        .max()
+        .unwrap_or_default();
+
+    let pixel = |image: &Option<RgbaImage>, x: u32, y: u32| -> Rgba<u8> {
+        match image
+            .as_ref()
+            .and_then(|image| image.get_pixel_checked(x, y))
+        {
+            Some(pixel) if pixel[3] != 0 => *pixel,
+            _ => Rgba([0, 0, 0, 0]),
+        }
+    };
+
+    let mut changed_pixels = 0;
+    let overlay = RgbaImage::from_fn(width, height, |x, y| {
+        let old_pixel = pixel(&old, x, y);
+        let new_pixel = pixel(&new, x, y);
+        if old_pixel != new_pixel {
+            changed_pixels += 1;
+            OVERLAY_CHANGED_COLOR
+        } else {
+            let [r, g, b, a] = new_pixel.0;
+            Rgba([r, g, b, a / OVERLAY_FADE])
+        }
+    });
+    (DynamicImage::ImageRgba8(overlay), changed_pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use dmi::icon::IconState;
+
+    use super::*;
+
+    fn state(name: &str, movement: bool, alpha: u8) -> IconState {
+        IconState {
+            name: name.to_string(),
+            dirs: 1,
+            frames: 1,
+            images: vec![DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                32,
+                32,
+                Rgba([255, 0, 0, alpha]),
+            ))],
+            movement,
+            ..Default::default()
+        }
+    }
+
+    fn icon(states: Vec<IconState>) -> Icon {
+        Icon {
+            width: 32,
+            height: 32,
+            states,
+            ..Default::default()
+        }
+    }
+
+    fn changes(diff: &DMIDiff) -> Vec<(StateChangeKind, &str, bool)> {
+        diff.states
+            .iter()
+            .map(|state| (state.kind, state.name(), state.movement))
+            .collect()
+    }
+
+    #[test]
+    fn matches_movement_variants_separately() {
+        let old =
+            icon(vec![state("walk", false, 255), state("walk", true, 255)]);
+        let new =
+            icon(vec![state("walk", false, 255), state("walk", true, 128)]);
+
+        let diff = diff_icons(old, new);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(changes(&diff), [(StateChangeKind::Modified, "walk", true)]);
+        assert_eq!(diff.states[0].frames.len(), 1);
+        assert_eq!(diff.states[0].frames[0].changed_pixels, 32 * 32);
+    }
+
+    #[test]
+    fn detects_renames() {
+        let old = icon(vec![state("old", false, 255)]);
+        let new = icon(vec![state("new", false, 255)]);
+
+        let diff = diff_icons(old, new);
+        assert_eq!(changes(&diff), [(StateChangeKind::Renamed, "new", false)]);
+        assert_eq!(diff.states[0].old_name.as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn blank_states_are_not_renames() {
+        let old = icon(vec![state("old", false, 0)]);
+        let new = icon(vec![state("new", false, 0)]);
+
+        let diff = diff_icons(old, new);
+        assert_eq!(
+            changes(&diff),
+            [
+                (StateChangeKind::Added, "new", false),
+                (StateChangeKind::Removed, "old", false)
+            ]
+        );
+    }
+}
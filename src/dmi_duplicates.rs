@@ -0,0 +1,397 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    path::PathBuf,
+};
+
+use dmi::icon::IconState;
+use image::DynamicImage;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::Serialize;
+
+use crate::dmi_utils::{Directions, load_dmi};
+
+/// Where a copy of the sprite lives.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct SpriteLocation {
+    pub path: PathBuf,
+    pub state: String,
+    /// None for the whole state
+    pub dir: Option<Directions>,
+    /// Starting from 0, None for the whole state
+    pub frame: Option<u32>,
+}
+
+impl Display for SpriteLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} state {:?}", self.path.to_string_lossy(), self.state)?;
+        if let (Some(dir), Some(frame)) = (self.dir, self.frame) {
+            write!(f, " {} frame {}", dir, frame + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sprites with the same pixels.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    /// BLAKE3 of the pixels in hex
+    pub hash: String,
+    pub width: u32,
+    pub height: u32,
+    /// Images in a single copy
+    pub images: usize,
+    pub copies: Vec<SpriteLocation>,
+}
+
+impl DuplicateGroup {
+    /// Raw RGBA bytes of all copies but one.
+    pub fn wasted_bytes(&self) -> usize {
+        self.width as usize
+            * self.height as usize
+            * 4
+            * self.images
+            * (self.copies.len() - 1)
+    }
+}
+
+/// Duplicates among the DMIs, the most wasteful first.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DuplicateReport {
+    /// States with the same images in every dir and frame
+    pub states: Vec<DuplicateGroup>,
+    /// Same frames in different states, which are not copies of each other
+    pub frames: Vec<DuplicateGroup>,
+    /// DMIs, which could not be loaded, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl DuplicateReport {
+    pub fn wasted_bytes(&self) -> usize {
+        self.states
+            .iter()
+            .chain(&self.frames)
+            .map(DuplicateGroup::wasted_bytes)
+            .sum()
+    }
+}
+
+/// Hashes of the visible states of a single DMI and their visible frames.
+type DMIHashes = Vec<(StateHash, Vec<(Directions, u32, ImageHash)>)>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ImageHash {
+    hash: String,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, Clone)]
+struct StateHash {
+    name: String,
+    image: ImageHash,
+    images: usize,
+}
+
+/// Loads every DMI and groups the states and frames with the same pixels.
+/// Fully transparent frames and states are skipped.
+pub fn find_duplicates(paths: &[PathBuf]) -> DuplicateReport {
+    let hashed: Vec<(PathBuf, Result<DMIHashes, String>)> = paths
+        .par_iter()
+        .map(|path| {
+            let hashes = load_dmi(path)
+                .map(|icon| icon.states.iter().filter_map(hash_state).collect())
+                .map_err(|err| err.to_string());
+            (path.clone(), hashes)
+        })
+        .collect();
+
+    let mut report = DuplicateReport::default();
+    let mut states: HashMap<ImageHash, (usize, Vec<SpriteLocation>)> =
+        HashMap::new();
+    let mut frames: HashMap<ImageHash, Vec<SpriteLocation>> = HashMap::new();
+    for (path, hashes) in hashed {
+        let hashes = match hashes {
+            Ok(hashes) => hashes,
+            Err(err) => {
+                report.failed.push((path, err));
+                continue;
+            }
+        };
+        for (state, state_frames) in hashes {
+            states
+                .entry(state.image)
+                .or_insert_with(|| (state.images, Vec::new()))
+                .1
+                .push(SpriteLocation {
+                    path: path.clone(),
+                    state: state.name.clone(),
+                    dir: None,
+                    frame: None,
+                });
+            for (dir, frame, image) in state_frames {
+                frames.entry(image).or_default().push(SpriteLocation {
+                    path: path.clone(),
+                    state: state.name.clone(),
+                    dir: Some(dir),
+                    frame: Some(frame),
+                });
+            }
+        }
+    }
+
+    // Frames of the copied states are already reported with them
+    let mut state_copies: HashMap<(&PathBuf, &String), usize> = HashMap::new();
+    let state_groups: Vec<DuplicateGroup> = states
+        .into_iter()
+        .filter(|(_, (_, copies))| copies.len() > 1)
+        .map(|(image, (images, copies))| group(image, images, copies))
+        .collect();
+    for (group_index, group) in state_groups.iter().enumerate() {
+        for copy in &group.copies {
+            state_copies.insert((&copy.path, &copy.state), group_index);
+        }
+    }
+    let frame_groups: Vec<DuplicateGroup> = frames
+        .into_iter()
+        .filter(|(_, copies)| {
+            let owners: BTreeSet<Result<usize, (&PathBuf, &String)>> = copies
+                .iter()
+                .map(|copy| {
+                    state_copies
+                        .get(&(&copy.path, &copy.state))
+                        .copied()
+                        .ok_or((&copy.path, &copy.state))
+                })
+                .collect();
+            owners.len() > 1
+        })
+        .map(|(image, copies)| group(image, 1, copies))
+        .collect();
+
+    report.states = state_groups;
+    report.frames = frame_groups;
+    for groups in [&mut report.states, &mut report.frames] {
+        groups.sort_by(|first, second| {
+            second
+                .wasted_bytes()
+                .cmp(&first.wasted_bytes())
+                .then_with(|| first.copies.cmp(&second.copies))
+        });
+    }
+    report.failed.sort();
+    report
+}
+
+fn group(
+    image: ImageHash,
+    images: usize,
+    mut copies: Vec<SpriteLocation>,
+) -> DuplicateGroup {
+    copies.sort();
+    DuplicateGroup {
+        hash: image.hash,
+        width: image.width,
+        height: image.height,
+        images,
+        copies,
+    }
+}
+
+/// None for the states without visible frames.
+fn hash_state(
+    state: &IconState,
+) -> Option<(StateHash, Vec<(Directions, u32, ImageHash)>)> {
+    let dirs = state.dirs.max(1) as usize;
+    let used_images = (dirs * state.frames as usize).min(state.images.len());
+    let mut state_hasher = blake3::Hasher::new();
+    state_hasher.update(&[state.dirs]);
+    state_hasher.update(&state.frames.to_le_bytes());
+
+    let mut frames: Vec<(Directions, u32, ImageHash)> = Vec::new();
+    for (index, image) in state.images[..used_images].iter().enumerate() {
+        let (image_hash, visible) = hash_image(image);
+        state_hasher.update(image_hash.hash.as_bytes());
+        if visible {
+            frames.push((
+                Directions::from((index % dirs) as u8),
+                (index / dirs) as u32,
+                image_hash,
+            ));
+        }
+    }
+
+    if frames.is_empty() {
+        return None;
+    }
+    let (width, height) = state
+        .images
+        .first()
+        .map_or((0, 0), |image| (image.width(), image.height()));
+    let state_hash = StateHash {
+        name: state.name.clone(),
+        image: ImageHash {
+            hash: state_hasher.finalize().to_hex().to_string(),
+            width,
+            height,
+        },
+        images: used_images,
+    };
+    Some((state_hash, frames))
+}
+
+/// Hash of the image and whether any of its pixels is not transparent.
+fn hash_image(image: &DynamicImage) -> (ImageHash, bool) {
+    let pixels = image.to_rgba8();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&pixels.width().to_le_bytes());
+    hasher.update(&pixels.height().to_le_bytes());
+    hasher.update(pixels.as_raw());
+    let image_hash = ImageHash {
+        hash: hasher.finalize().to_hex().to_string(),
+        width: pixels.width(),
+        height: pixels.height(),
+    };
+    (image_hash, pixels.pixels().any(|pixel| pixel[3] != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use dmi::icon::Icon;
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::dmi_utils::save_dmi;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "dmi_assistant_duplicates_{}_{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Solid color images, frame by frame.
+    fn state(name: &str, dirs: u8, colors: &[[u8; 4]]) -> IconState {
+        IconState {
+            name: name.to_string(),
+            dirs,
+            frames: (colors.len() / dirs as usize) as u32,
+            images: colors
+                .iter()
+                .map(|color| {
+                    DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                        32,
+                        32,
+                        Rgba(*color),
+                    ))
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn save(path: &PathBuf, states: Vec<IconState>) {
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states,
+            ..Default::default()
+        };
+        save_dmi(&icon, path).unwrap();
+    }
+
+    fn location(
+        path: &PathBuf,
+        state: &str,
+        frame: Option<(Directions, u32)>,
+    ) -> SpriteLocation {
+        SpriteLocation {
+            path: path.clone(),
+            state: state.to_string(),
+            dir: frame.map(|(dir, _)| dir),
+            frame: frame.map(|(_, frame)| frame),
+        }
+    }
+
+    #[test]
+    fn groups_identical_states_and_frames() {
+        let dir = temp_dir("groups");
+        let first = dir.join("a.dmi");
+        let second = dir.join("b.dmi");
+        let missing = dir.join("missing.dmi");
+        save(
+            &first,
+            vec![
+                state("walk", 1, &[RED, BLUE]),
+                state("empty", 1, &[CLEAR]),
+                state("stand", 1, &[GREEN]),
+            ],
+        );
+        save(
+            &second,
+            vec![
+                state("walk_copy", 1, &[RED, BLUE]),
+                state("empty", 1, &[CLEAR]),
+                state("pose", 4, &[CLEAR, CLEAR, GREEN, CLEAR]),
+                state("flash", 1, &[RED]),
+            ],
+        );
+
+        let report =
+            find_duplicates(&[first.clone(), second.clone(), missing.clone()]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Transparent states are not copies
+        assert_eq!(report.states.len(), 1);
+        let walk = &report.states[0];
+        assert_eq!(
+            walk.copies,
+            [
+                location(&first, "walk", None),
+                location(&second, "walk_copy", None),
+            ]
+        );
+        assert_eq!((walk.width, walk.height, walk.images), (32, 32, 2));
+        assert_eq!(walk.wasted_bytes(), 32 * 32 * 4 * 2);
+
+        // Blue is only in the copied states, so it is reported with them
+        let frames: Vec<&[SpriteLocation]> = report
+            .frames
+            .iter()
+            .map(|group| group.copies.as_slice())
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                &[
+                    location(&first, "walk", Some((Directions::South, 0))),
+                    location(&second, "flash", Some((Directions::South, 0))),
+                    location(
+                        &second,
+                        "walk_copy",
+                        Some((Directions::South, 0))
+                    ),
+                ][..],
+                &[
+                    location(&first, "stand", Some((Directions::South, 0))),
+                    location(&second, "pose", Some((Directions::East, 0))),
+                ][..],
+            ]
+        );
+        assert_eq!(report.wasted_bytes(), 32 * 32 * 4 * (2 + 2 + 1));
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, missing);
+    }
+}
@@ -0,0 +1,434 @@
+use std::{fmt::Display, fs, path::Path, str::FromStr};
+
+use dmi::icon::{Icon, IconState};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    dmi_model::StateId,
+    dmi_utils::{DMIParsingError, load_dmi, save_dmi},
+};
+
+/// Errors, returned by DMI editing operations.
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DMIEditError {
+    /// There is no state with this name in the icon.
+    #[error("State {0:?} does not exist")]
+    NoSuchState(String),
+    /// State with this name is already present in the icon.
+    #[error("State {0:?} already exists")]
+    StateExists(String),
+    /// State is already the first or the last one.
+    #[error("State {0:?} can not be moved further")]
+    CanNotMove(String),
+    /// Icons of different sizes can not be combined.
+    #[error("Icon size {found} does not match {expected}")]
+    SizeMismatch { expected: String, found: String },
+    /// Operation needs at least one icon or state.
+    #[error("Nothing to {0}")]
+    Empty(&'static str),
+    /// Result would overwrite one of the inputs.
+    #[error("Output file {0} is one of the input files")]
+    SameFile(String),
+    /// Reading or writing DMI failed.
+    #[error(transparent)]
+    Parsing(#[from] DMIParsingError),
+}
+
+/// What to do, when merged icons have states with the same name.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum MergeConflictPolicy {
+    /// Keep both, renaming the later one to `name copy`
+    #[default]
+    Rename,
+    /// Keep the state from the first icon it was found in
+    KeepFirst,
+    /// Replace the state with the one from the later icon
+    KeepLast,
+    /// Abort the merge
+    Fail,
+}
+
+impl Display for MergeConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Rename => "Rename Duplicates",
+            Self::KeepFirst => "Keep First",
+            Self::KeepLast => "Keep Last",
+            Self::Fail => "Fail",
+        })
+    }
+}
+
+impl FromStr for MergeConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rename" => Ok(Self::Rename),
+            "keep-first" => Ok(Self::KeepFirst),
+            "keep-last" => Ok(Self::KeepLast),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!(
+                "unknown conflict policy {}, expected rename, keep-first, keep-last or fail",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the position of the first state with the given name and
+/// movement flag.
+pub fn state_index(icon: &Icon, id: &StateId) -> Result<usize, DMIEditError> {
+    icon.states
+        .iter()
+        .position(|state| {
+            state.name == id.name && state.movement == id.movement
+        })
+        .ok_or_else(|| DMIEditError::NoSuchState(id.to_string()))
+}
+
+/// Returns `base` or `base copy`, `base copy 2`, ... - whichever is not taken
+/// in the icon yet.
+pub fn unique_state_name(icon: &Icon, base: &str) -> String {
+    let is_taken =
+        |name: &str| icon.states.iter().any(|state| state.name == name);
+    if !is_taken(base) {
+        return base.to_string();
+    }
+    let mut candidate = format!("{} copy", base);
+    let mut counter = 2;
+    while is_taken(&candidate) {
+        candidate = format!("{} copy {}", base, counter);
+        counter += 1;
+    }
+    candidate
+}
+
+/// Renames the state. Only a state with the same movement flag can take the
+/// new name.
+pub fn rename_state(
+    icon: &mut Icon,
+    id: &StateId,
+    new_name: &str,
+) -> Result<(), DMIEditError> {
+    let index = state_index(icon, id)?;
+    if id.name == new_name {
+        return Ok(());
+    }
+    if icon
+        .states
+        .iter()
+        .any(|state| state.name == new_name && state.movement == id.movement)
+    {
+        return Err(DMIEditError::StateExists(new_name.to_string()));
+    }
+    icon.states[index].name = new_name.to_string();
+    Ok(())
+}
+
+/// Removes the state from the icon and returns it.
+pub fn delete_state(
+    icon: &mut Icon,
+    id: &StateId,
+) -> Result<IconState, DMIEditError> {
+    let index = state_index(icon, id)?;
+    Ok(icon.states.remove(index))
+}
+
+/// Inserts a copy of the state right after it and returns the copy's id.
+pub fn duplicate_state(
+    icon: &mut Icon,
+    id: &StateId,
+) -> Result<StateId, DMIEditError> {
+    let index = state_index(icon, id)?;
+    let mut copy = icon.states[index].clone();
+    copy.name = unique_state_name(icon, &id.name);
+    let copy_id = StateId::of(&copy);
+    icon.states.insert(index + 1, copy);
+    Ok(copy_id)
+}
+
+/// Moves the state by `offset` positions (negative - towards the start) and
+/// returns its new position.
+pub fn move_state(
+    icon: &mut Icon,
+    id: &StateId,
+    offset: isize,
+) -> Result<usize, DMIEditError> {
+    let index = state_index(icon, id)?;
+    let new_index = index
+        .checked_add_signed(offset)
+        .filter(|new_index| *new_index < icon.states.len())
+        .ok_or_else(|| DMIEditError::CanNotMove(id.to_string()))?;
+    let state = icon.states.remove(index);
+    icon.states.insert(new_index, state);
+    Ok(new_index)
+}
+
+/// Combines states of all icons into one, in order. Icons must have the same
+/// size. Only states with the same name and movement flag from different
+/// icons conflict. Returns the merged icon and descriptions of resolved
+/// conflicts.
+pub fn merge_icons(
+    icons: &[Icon],
+    policy: MergeConflictPolicy,
+) -> Result<(Icon, Vec<String>), DMIEditError> {
+    let first = icons.first().ok_or(DMIEditError::Empty("merge"))?;
+    let mut merged = Icon {
+        states: Vec::new(),
+        ..first.clone()
+    };
+    // Index of the icon, each merged state was taken from
+    let mut origins: Vec<usize> = Vec::new();
+    let mut conflicts: Vec<String> = Vec::new();
+
+    for (origin, icon) in icons.iter().enumerate() {
+        if icon.width != merged.width || icon.height != merged.height {
+            return Err(DMIEditError::SizeMismatch {
+                expected: format!("{}x{}", merged.width, merged.height),
+                found: format!("{}x{}", icon.width, icon.height),
+            });
+        }
+        for state in &icon.states {
+            let conflicting = merged.states.iter().zip(&origins).position(
+                |(other, other_origin)| {
+                    *other_origin != origin
+                        && other.name == state.name
+                        && other.movement == state.movement
+                },
+            );
+            let Some(index) = conflicting else {
+                merged.states.push(state.clone());
+                origins.push(origin);
+                continue;
+            };
+            match policy {
+                MergeConflictPolicy::Rename => {
+                    let mut renamed = state.clone();
+                    renamed.name = unique_state_name(&merged, &state.name);
+                    conflicts.push(format!(
+                        "{:?} was renamed to {:?}",
+                        state.name, renamed.name
+                    ));
+                    merged.states.push(renamed);
+                    origins.push(origin);
+                }
+                MergeConflictPolicy::KeepFirst => {
+                    conflicts.push(format!("{:?} was skipped", state.name));
+                }
+                MergeConflictPolicy::KeepLast => {
+                    conflicts.push(format!("{:?} was replaced", state.name));
+                    merged.states[index] = state.clone();
+                    origins[index] = origin;
+                }
+                MergeConflictPolicy::Fail => {
+                    return Err(DMIEditError::StateExists(state.name.clone()));
+                }
+            }
+        }
+    }
+    Ok((merged, conflicts))
+}
+
+/// Moves the named states out of the icon into a new icon of the same size.
+pub fn split_icon(
+    icon: &mut Icon,
+    names: &[String],
+) -> Result<Icon, DMIEditError> {
+    if names.is_empty() {
+        return Err(DMIEditError::Empty("split"));
+    }
+    if let Some(missing) = names
+        .iter()
+        .find(|name| !icon.states.iter().any(|state| &state.name == *name))
+    {
+        return Err(DMIEditError::NoSuchState(missing.clone()));
+    }
+    let (moved, kept): (Vec<IconState>, Vec<IconState>) =
+        std::mem::take(&mut icon.states)
+            .into_iter()
+            .partition(|state| names.contains(&state.name));
+    // Cloned while empty to copy only the icon's own metadata
+    let mut split = icon.clone();
+    split.states = moved;
+    icon.states = kept;
+    Ok(split)
+}
+
+/// Merges DMI files with [`merge_icons`] and saves the result into `output`.
+pub fn merge_dmi_files<T: AsRef<Path>>(
+    inputs: &[T],
+    output: &Path,
+    policy: MergeConflictPolicy,
+) -> Result<Vec<String>, DMIEditError> {
+    let icons = inputs
+        .iter()
+        .map(|input| load_dmi(input))
+        .collect::<Result<Vec<Icon>, DMIParsingError>>()?;
+    let (merged, conflicts) = merge_icons(&icons, policy)?;
+    save_dmi(&merged, output)?;
+    Ok(conflicts)
+}
+
+/// Moves the named states from `input` into a new `output` DMI. The new file
+/// is written before the states are removed from the original one.
+pub fn split_dmi_file(
+    input: &Path,
+    names: &[String],
+    output: &Path,
+) -> Result<(), DMIEditError> {
+    // Relative and symlinked paths must not get past the check
+    let same_file = match (fs::canonicalize(input), fs::canonicalize(output)) {
+        (Ok(input), Ok(output)) => input == output,
+        _ => input == output,
+    };
+    if same_file {
+        return Err(DMIEditError::SameFile(
+            output.to_string_lossy().into_owned(),
+        ));
+    }
+    let mut icon = load_dmi(input)?;
+    let split = split_icon(&mut icon, names)?;
+    save_dmi(&split, output)?;
+    save_dmi(&icon, input)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn state(name: &str, shade: u8) -> IconState {
+        IconState {
+            name: name.to_string(),
+            dirs: 1,
+            frames: 1,
+            images: vec![DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                32,
+                32,
+                Rgba([shade, 0, 0, 255]),
+            ))],
+            ..Default::default()
+        }
+    }
+
+    fn icon(states: Vec<IconState>) -> Icon {
+        Icon {
+            width: 32,
+            height: 32,
+            states,
+            ..Default::default()
+        }
+    }
+
+    /// Name and shade of every state.
+    fn summary(icon: &Icon) -> Vec<(&str, u8)> {
+        icon.states
+            .iter()
+            .map(|state| {
+                let shade = state.images[0].to_rgba8().get_pixel(0, 0).0[0];
+                (state.name.as_str(), shade)
+            })
+            .collect()
+    }
+
+    fn inputs() -> [Icon; 2] {
+        [
+            icon(vec![state("a", 1), state("b", 1)]),
+            icon(vec![state("b", 2), state("c", 2)]),
+        ]
+    }
+
+    #[test]
+    fn merge_renames_duplicates() {
+        let (merged, conflicts) =
+            merge_icons(&inputs(), MergeConflictPolicy::Rename).unwrap();
+        assert_eq!(
+            summary(&merged),
+            [("a", 1), ("b", 1), ("b copy", 2), ("c", 2)]
+        );
+        assert_eq!(conflicts, [r#""b" was renamed to "b copy""#]);
+    }
+
+    #[test]
+    fn merge_keeps_first_duplicate() {
+        let (merged, conflicts) =
+            merge_icons(&inputs(), MergeConflictPolicy::KeepFirst).unwrap();
+        assert_eq!(summary(&merged), [("a", 1), ("b", 1), ("c", 2)]);
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn merge_keeps_last_duplicate_in_place() {
+        let (merged, conflicts) =
+            merge_icons(&inputs(), MergeConflictPolicy::KeepLast).unwrap();
+        assert_eq!(summary(&merged), [("a", 1), ("b", 2), ("c", 2)]);
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn merge_fails_on_duplicate() {
+        let result = merge_icons(&inputs(), MergeConflictPolicy::Fail);
+        assert!(matches!(
+            result,
+            Err(DMIEditError::StateExists(name)) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn merge_keeps_duplicates_within_one_icon() {
+        let icons = [icon(vec![state("a", 1), state("a", 2)])];
+        let (merged, conflicts) =
+            merge_icons(&icons, MergeConflictPolicy::Fail).unwrap();
+        assert_eq!(summary(&merged), [("a", 1), ("a", 2)]);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn merge_rejects_different_sizes() {
+        let icons = [
+            icon(vec![state("a", 1)]),
+            Icon {
+                width: 64,
+                ..icon(Vec::new())
+            },
+        ];
+        let result = merge_icons(&icons, MergeConflictPolicy::Rename);
+        assert!(matches!(
+            result,
+            Err(DMIEditError::SizeMismatch { expected, found })
+                if expected == "32x32" && found == "64x32"
+        ));
+    }
+
+    #[test]
+    fn split_keeps_state_order() {
+        let mut original = icon(vec![
+            state("a", 1),
+            state("b", 2),
+            state("c", 3),
+            state("d", 4),
+        ]);
+        let names = ["c".to_string(), "a".to_string()];
+
+        let split = split_icon(&mut original, &names).unwrap();
+        assert_eq!(summary(&split), [("a", 1), ("c", 3)]);
+        assert_eq!(summary(&original), [("b", 2), ("d", 4)]);
+    }
+
+    #[test]
+    fn split_rejects_missing_state() {
+        let mut original = icon(vec![state("a", 1)]);
+        let result = split_icon(&mut original, &["x".to_string()]);
+        assert!(
+            matches!(result, Err(DMIEditError::NoSuchState(name)) if name == "x")
+        );
+        assert_eq!(summary(&original), [("a", 1)]);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+};
+
+use dmi::icon::Icon;
+use git2::Repository;
+use thiserror::Error;
+
+use crate::dmi_utils::DMIParsingError;
+
+/// Revision used, when none is given.
+pub const DEFAULT_REVISION: &str = "HEAD";
+
+/// Errors, returned by reading DMIs from git history.
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DMIGitError {
+    /// File is not found or inaccesible.
+    #[error(transparent)]
+    NoSuchFile(#[from] io::Error),
+    /// File is not inside of a work tree of any git repository.
+    #[error("{0} is not inside a git repository")]
+    NotInRepository(String),
+    /// Revision or file in it can not be found, or the repository is broken.
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+    /// Blob is not a valid DMI.
+    #[error(transparent)]
+    Parsing(#[from] DMIParsingError),
+}
+
+/// DMI as it was in some commit.
+#[derive(Debug, Clone)]
+pub struct RevisionDMI {
+    pub icon: Icon,
+    /// Short hash and summary of the commit the revision points to
+    pub commit: String,
+}
+
+/// Loads the DMI at `path` as it was in `revision` (`HEAD`, branch, tag,
+/// commit hash or anything else `git rev-parse` understands) of the repository
+/// containing it. Only the local `.git` directory is read.
+pub fn load_dmi_from_revision<T: AsRef<Path>>(
+    path: T,
+    revision: &str,
+) -> Result<RevisionDMI, DMIGitError> {
+    // The file itself may be deleted in the work tree, but not in `revision`
+    let path = path.as_ref();
+    let file_name = path.file_name().ok_or_else(|| {
+        DMIGitError::NotInRepository(path.to_string_lossy().into_owned())
+    })?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let path = fs::canonicalize(parent)?.join(file_name);
+    let not_in_repository =
+        || DMIGitError::NotInRepository(path.to_string_lossy().into_owned());
+
+    let repo = Repository::discover(path.parent().unwrap_or(&path))
+        .map_err(|_| not_in_repository())?;
+    let workdir: PathBuf =
+        fs::canonicalize(repo.workdir().ok_or_else(not_in_repository)?)?;
+    let relative_path = path
+        .strip_prefix(&workdir)
+        .map_err(|_| not_in_repository())?;
+
+    let revision = match revision.trim() {
+        "" => DEFAULT_REVISION,
+        revision => revision,
+    };
+    let commit = repo.revparse_single(revision)?.peel_to_commit()?;
+    let blob = commit
+        .tree()?
+        .get_path(relative_path)?
+        .to_object(&repo)?
+        .peel_to_blob()?;
+    let icon = Icon::load(Cursor::new(blob.content()))
+        .map_err(DMIParsingError::from)?;
+
+    let short_id = commit.as_object().short_id()?;
+    Ok(RevisionDMI {
+        icon,
+        commit: format!(
+            "{} {}",
+            short_id.as_str().unwrap_or_default(),
+            commit.summary().unwrap_or_default()
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use dmi::icon::IconState;
+    use git2::Signature;
+    use image::DynamicImage;
+
+    use super::*;
+    use crate::dmi_utils::save_dmi;
+
+    #[test]
+    fn loads_files_deleted_in_the_work_tree() {
+        let dir = std::env::temp_dir()
+            .join(format!("dmi_assistant_git_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("icon.dmi");
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![IconState {
+                name: "a".to_string(),
+                dirs: 1,
+                frames: 1,
+                images: vec![DynamicImage::new_rgba8(32, 32)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        save_dmi(&icon, &path).unwrap();
+
+        let repo = Repository::init(&dir).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("icon.dmi")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add icon",
+            &tree,
+            &[],
+        )
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let loaded = load_dmi_from_revision(&path, "");
+        let missing = load_dmi_from_revision(dir.join("missing.dmi"), "HEAD");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.icon.states.len(), 1);
+        assert_eq!(loaded.icon.states[0].name, "a");
+        assert!(loaded.commit.ends_with(" Add icon"));
+        assert!(matches!(missing, Err(DMIGitError::Git(_))));
+    }
+}
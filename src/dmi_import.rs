@@ -0,0 +1,543 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    fs, io,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use dmi::icon::{Icon, IconState, Looping};
+use image::DynamicImage;
+use thiserror::Error;
+
+use crate::dmi_utils::Directions;
+
+/// Errors, returned by building DMIs from loose images.
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DMIImportError {
+    /// Folder is not found or inaccesible.
+    #[error(transparent)]
+    NoSuchFolder(#[from] io::Error),
+    /// Folder does not contain any PNG.
+    #[error("No PNG files found in {0}")]
+    NoImages(String),
+    /// Icon can not be built with the given parameters.
+    #[error("{0}")]
+    InvalidState(String),
+}
+
+/// Single PNG from the imported folder, with the state, direction and frame
+/// parsed from its name.
+///
+/// Names follow the `state[_dir][_frame].png` convention: `wall.png`,
+/// `wall_3.png`, `door_N.png` and `door_N_2.png` are all recognized. `dir`
+/// accepts the same names as [`Directions`]'s `FromStr`.
+///
+/// Any name ending in such a suffix is split, even if the suffix is a part of
+/// the state name: `light_s.png` is the south dir of `light` and `floor_2.png`
+/// is its third frame. Such files can be kept whole, see [`group_states`].
+#[derive(Debug, Clone)]
+pub struct SourceImage {
+    pub path: PathBuf,
+    /// File name without the extension
+    pub stem: String,
+    pub state: String,
+    pub dir: Directions,
+    pub frame: u32,
+    pub image: DynamicImage,
+}
+
+impl SourceImage {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let stem = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let (state, dir, frame) = parse_source_name(&stem);
+        let image = image::open(path)
+            .map_err(|err| format!("{}: {}", path.to_string_lossy(), err))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            stem,
+            state,
+            dir,
+            frame,
+            image: DynamicImage::ImageRgba8(image.into_rgba8()),
+        })
+    }
+}
+
+/// Splits `state[_dir][_frame]` into its parts.
+fn parse_source_name(stem: &str) -> (String, Directions, u32) {
+    let mut name = stem;
+    let mut frame = 0;
+    let mut dir = Directions::South;
+
+    if let Some((rest, suffix)) = name.rsplit_once('_')
+        && !rest.is_empty()
+        && let Ok(parsed_frame) = suffix.parse::<u32>()
+    {
+        frame = parsed_frame;
+        name = rest;
+    }
+    if let Some((rest, suffix)) = name.rsplit_once('_')
+        && !rest.is_empty()
+        && let Ok(parsed_dir) = suffix.parse::<Directions>()
+    {
+        dir = parsed_dir;
+        name = rest;
+    }
+    (name.to_string(), dir, frame)
+}
+
+/// Reads every PNG directly in the folder. Files, which are not valid images,
+/// are returned as errors alongside.
+pub fn scan_folder(
+    folder: &Path,
+) -> Result<(Vec<SourceImage>, Vec<String>), DMIImportError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case(OsStr::new("png"))
+                })
+        })
+        .collect();
+    if paths.is_empty() {
+        return Err(DMIImportError::NoImages(
+            folder.to_string_lossy().into_owned(),
+        ));
+    }
+    paths.sort();
+
+    let mut sources = Vec::with_capacity(paths.len());
+    let mut errors = Vec::new();
+    for path in paths {
+        match SourceImage::load(&path) {
+            Ok(source) => sources.push(source),
+            Err(err) => errors.push(err),
+        }
+    }
+    Ok((sources, errors))
+}
+
+/// Parameters of the state, which can not be derived from the file names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportStateSettings {
+    /// 1, 4 or 8
+    pub dirs: u8,
+    /// Delay of every frame in ticks
+    pub delay: f32,
+    /// How many times the animation is played, 0 means indefinitely
+    pub loop_count: u32,
+    pub rewind: bool,
+    pub movement: bool,
+}
+
+impl Default for ImportStateSettings {
+    fn default() -> Self {
+        Self {
+            dirs: 1,
+            delay: 1.0,
+            loop_count: 0,
+            rewind: false,
+            movement: false,
+        }
+    }
+}
+
+/// State assembled from the source images.
+#[derive(Debug, Clone)]
+pub struct ImportedState {
+    pub name: String,
+    /// State name parsed from the file names, the same for all the states
+    /// of the files kept whole
+    pub parsed_name: String,
+    /// Whether any of the file names has a dir or frame suffix
+    pub has_suffixes: bool,
+    pub frames: u32,
+    /// Images by direction and frame
+    pub images: BTreeMap<(Directions, u32), DynamicImage>,
+    pub settings: ImportStateSettings,
+}
+
+impl ImportedState {
+    /// Directions, which have at least one image
+    pub fn detected_dirs(&self) -> u8 {
+        let max_dir = self
+            .images
+            .keys()
+            .map(|(dir, _)| u8::from(*dir))
+            .max()
+            .unwrap_or_default();
+        match max_dir {
+            0 => 1,
+            1..=3 => 4,
+            _ => 8,
+        }
+    }
+}
+
+/// Groups source images into states, splitting horizontal strips into frames
+/// by `width`. `previous` settings are kept for the states with the same
+/// name. Files of the states named in `whole_names` are not split by their
+/// suffixes, each of them becomes a state named after the whole file name.
+/// Returns the states in order of their first appearance and the problems
+/// found.
+pub fn group_states(
+    sources: &[SourceImage],
+    width: u32,
+    height: u32,
+    previous: &[ImportedState],
+    whole_names: &BTreeSet<String>,
+) -> (Vec<ImportedState>, Vec<String>) {
+    let mut states: Vec<ImportedState> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
+    if width == 0 || height == 0 {
+        warnings.push(format!("icon size {}x{} is invalid", width, height));
+        return (states, warnings);
+    }
+
+    for source in sources {
+        let source_width = source.image.width();
+        if source.image.height() != height
+            || source_width == 0
+            || source_width % width != 0
+        {
+            warnings.push(format!(
+                "{} is {}x{}, which is not a strip of {}x{} icons, skipped",
+                source.path.to_string_lossy(),
+                source_width,
+                source.image.height(),
+                width,
+                height
+            ));
+            continue;
+        }
+
+        let (name, dir, first_frame) = if whole_names.contains(&source.state) {
+            (&source.stem, Directions::South, 0)
+        } else {
+            (&source.state, source.dir, source.frame)
+        };
+        let existing_index =
+            states.iter().position(|state| &state.name == name);
+        let state = match existing_index {
+            Some(index) => &mut states[index],
+            None => {
+                states.push(ImportedState {
+                    name: name.clone(),
+                    parsed_name: source.state.clone(),
+                    has_suffixes: false,
+                    frames: 0,
+                    images: BTreeMap::new(),
+                    settings: previous
+                        .iter()
+                        .find(|state| &state.name == name)
+                        .map(|state| state.settings.clone())
+                        .unwrap_or_default(),
+                });
+                states.last_mut().unwrap()
+            }
+        };
+        state.has_suffixes |= source.stem != source.state;
+
+        for strip_index in 0..source_width / width {
+            let frame = first_frame + strip_index;
+            let image =
+                source.image.crop_imm(strip_index * width, 0, width, height);
+            if state.images.insert((dir, frame), image).is_some() {
+                warnings.push(format!(
+                    "{}: frame {} of direction {} of state {:?} is duplicated",
+                    source.path.to_string_lossy(),
+                    frame,
+                    dir,
+                    name
+                ));
+            }
+            state.frames = state.frames.max(frame + 1);
+        }
+    }
+
+    for state in &mut states {
+        if !previous.iter().any(|previous| previous.name == state.name) {
+            state.settings.dirs = state.detected_dirs();
+        }
+    }
+    (states, warnings)
+}
+
+/// Builds an icon from the grouped states. Missing images are replaced with
+/// transparent ones and reported.
+pub fn build_icon(
+    width: u32,
+    height: u32,
+    states: &[ImportedState],
+) -> Result<(Icon, Vec<String>), DMIImportError> {
+    let mut warnings: Vec<String> = Vec::new();
+    let mut icon_states: Vec<IconState> = Vec::with_capacity(states.len());
+
+    for state in states {
+        let settings = &state.settings;
+        if ![1, 4, 8].contains(&settings.dirs) {
+            return Err(DMIImportError::InvalidState(format!(
+                "state {:?} has {} dirs, only 1, 4 or 8 are allowed",
+                state.name, settings.dirs
+            )));
+        }
+        if state.detected_dirs() > settings.dirs {
+            warnings.push(format!(
+                "state {:?} has images for {} dirs, but only {} are used",
+                state.name,
+                state.detected_dirs(),
+                settings.dirs
+            ));
+        }
+
+        let mut images: Vec<DynamicImage> =
+            Vec::with_capacity(settings.dirs as usize * state.frames as usize);
+        for frame in 0..state.frames {
+            for dir_index in 0..settings.dirs {
+                let direction: Directions = dir_index.into();
+                match state.images.get(&(direction, frame)) {
+                    Some(image) => images.push(image.clone()),
+                    None => {
+                        warnings.push(format!(
+                            "state {:?} has no frame {} for direction {}, left transparent",
+                            state.name, frame, direction
+                        ));
+                        images.push(DynamicImage::new_rgba8(width, height));
+                    }
+                }
+            }
+        }
+
+        icon_states.push(IconState {
+            name: state.name.clone(),
+            dirs: settings.dirs,
+            frames: state.frames,
+            images,
+            delay: (state.frames > 1)
+                .then(|| vec![settings.delay; state.frames as usize]),
+            loop_flag: match NonZeroU32::new(settings.loop_count) {
+                Some(count) => Looping::NTimes(count),
+                None => Looping::Indefinitely,
+            },
+            rewind: settings.rewind,
+            movement: settings.movement,
+            ..Default::default()
+        });
+    }
+
+    Ok((
+        Icon {
+            width,
+            height,
+            states: icon_states,
+            ..Default::default()
+        },
+        warnings,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    /// Horizontal strip of `frames` solid 4x4 icons.
+    fn source(stem: &str, frames: u32, color: [u8; 4]) -> SourceImage {
+        let (state, dir, frame) = parse_source_name(stem);
+        SourceImage {
+            path: PathBuf::from(format!("{}.png", stem)),
+            stem: stem.to_string(),
+            state,
+            dir,
+            frame,
+            image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                4 * frames,
+                4,
+                Rgba(color),
+            )),
+        }
+    }
+
+    #[test]
+    fn parses_source_names() {
+        let cases = [
+            ("wall", "wall", Directions::South, 0),
+            ("wall_3", "wall", Directions::South, 3),
+            ("door_N", "door", Directions::North, 0),
+            ("door_north_2", "door", Directions::North, 2),
+            ("big_door_se", "big_door", Directions::SouthEast, 0),
+            ("a_b", "a_b", Directions::South, 0),
+            // Suffixes are split off even when they belong to the name
+            ("light_s", "light", Directions::South, 0),
+            ("floor_2", "floor", Directions::South, 2),
+            // Nothing would be left for the name
+            ("_s", "_s", Directions::South, 0),
+            ("12", "12", Directions::South, 0),
+        ];
+        for (stem, name, dir, frame) in cases {
+            assert_eq!(
+                parse_source_name(stem),
+                (name.to_string(), dir, frame),
+                "{}",
+                stem
+            );
+        }
+    }
+
+    #[test]
+    fn groups_strips_into_frames() {
+        let mut sources = vec![
+            source("walk", 2, RED),
+            source("walk_n", 1, BLUE),
+            source("walk_n_1", 1, BLUE),
+            source("walk_0", 1, BLUE),
+            source("stand", 1, RED),
+            source("odd", 1, RED),
+        ];
+        sources[5].image = DynamicImage::new_rgba8(6, 4);
+
+        let (states, warnings) =
+            group_states(&sources, 4, 4, &[], &BTreeSet::new());
+        let names: Vec<&str> =
+            states.iter().map(|state| state.name.as_str()).collect();
+        assert_eq!(names, ["walk", "stand"]);
+        // The odd width and the second south frame 0
+        assert_eq!(warnings.len(), 2);
+
+        let walk = &states[0];
+        assert_eq!(walk.frames, 2);
+        let keys: Vec<(Directions, u32)> =
+            walk.images.keys().copied().collect();
+        assert_eq!(
+            keys,
+            [
+                (Directions::South, 0),
+                (Directions::South, 1),
+                (Directions::North, 0),
+                (Directions::North, 1),
+            ]
+        );
+        assert_eq!(walk.settings.dirs, 4);
+        assert!(walk.has_suffixes);
+        let stand = &states[1];
+        assert_eq!((stand.frames, stand.settings.dirs), (1, 1));
+        assert!(!stand.has_suffixes);
+    }
+
+    #[test]
+    fn keeps_previous_settings() {
+        let sources = [source("walk", 1, RED), source("walk_n", 1, RED)];
+        let (mut previous, _) =
+            group_states(&sources, 4, 4, &[], &BTreeSet::new());
+        previous[0].settings.dirs = 1;
+        previous[0].settings.delay = 2.5;
+
+        let (states, _) =
+            group_states(&sources, 4, 4, &previous, &BTreeSet::new());
+        assert_eq!(states[0].settings, previous[0].settings);
+    }
+
+    #[test]
+    fn keeps_whole_names() {
+        let sources = [
+            source("light_s", 1, RED),
+            source("light_n", 1, BLUE),
+            source("wall", 1, RED),
+        ];
+        let (states, warnings) = group_states(
+            &sources,
+            4,
+            4,
+            &[],
+            &BTreeSet::from(["light".to_string()]),
+        );
+        assert!(warnings.is_empty());
+        let names: Vec<(&str, &str, u8)> = states
+            .iter()
+            .map(|state| {
+                (
+                    state.name.as_str(),
+                    state.parsed_name.as_str(),
+                    state.settings.dirs,
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("light_s", "light", 1),
+                ("light_n", "light", 1),
+                ("wall", "wall", 1)
+            ]
+        );
+        assert!(states[0].has_suffixes && states[1].has_suffixes);
+    }
+
+    #[test]
+    fn builds_missing_images_transparent() {
+        let sources = [
+            source("walk", 2, RED),
+            source("walk_n", 1, BLUE),
+            source("stand", 1, RED),
+        ];
+        let (mut states, _) =
+            group_states(&sources, 4, 4, &[], &BTreeSet::new());
+        states[0].settings.delay = 2.0;
+        states[0].settings.loop_count = 3;
+        states[1].settings.rewind = true;
+
+        let (icon, warnings) = build_icon(4, 4, &states).unwrap();
+        // North frame 1, east and west in both frames
+        assert_eq!(warnings.len(), 5);
+        assert_eq!((icon.width, icon.height), (4, 4));
+
+        let walk = &icon.states[0];
+        assert_eq!((walk.dirs, walk.frames), (4, 2));
+        assert_eq!(walk.images.len(), 8);
+        let colors: Vec<[u8; 4]> = walk
+            .images
+            .iter()
+            .map(|image| image.to_rgba8().get_pixel(0, 0).0)
+            .collect();
+        let clear = [0, 0, 0, 0];
+        assert_eq!(colors, [RED, BLUE, clear, clear, RED, clear, clear, clear]);
+        assert_eq!(walk.delay, Some(vec![2.0, 2.0]));
+        assert!(matches!(
+            walk.loop_flag,
+            Looping::NTimes(count) if count.get() == 3
+        ));
+
+        let stand = &icon.states[1];
+        assert_eq!(stand.delay, None);
+        assert!(matches!(stand.loop_flag, Looping::Indefinitely));
+        assert!(stand.rewind);
+    }
+
+    #[test]
+    fn warns_about_unused_dirs() {
+        let sources = [source("walk", 1, RED), source("walk_n", 1, BLUE)];
+        let (mut states, _) =
+            group_states(&sources, 4, 4, &[], &BTreeSet::new());
+        states[0].settings.dirs = 1;
+        let (icon, warnings) = build_icon(4, 4, &states).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(icon.states[0].images.len(), 1);
+
+        states[0].settings.dirs = 3;
+        assert!(matches!(
+            build_icon(4, 4, &states),
+            Err(DMIImportError::InvalidState(_))
+        ));
+    }
+}
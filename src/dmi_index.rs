@@ -0,0 +1,557 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+use dmi::icon::{Icon, IconState};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    dmi_model::{StateWarning, check_state_frames},
+    dmi_similar::perceptual_hash,
+    dmi_utils::DMIParsingError,
+    utils::csv_field,
+};
+
+/// Name of the index file in `data_dir`.
+pub const INDEX_FILE_NAME: &str = "explorer_index.json";
+/// Indexes of other versions are dropped on loading.
+const INDEX_VERSION: u32 = 5;
+/// Frames of every dir, which get a perceptual hash, later frames are not
+/// hashed to keep the index small.
+const HASHED_FRAMES_LIMIT: usize = 32;
+
+/// Errors, returned by saving the index.
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DMIIndexError {
+    /// Index file can not be written.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Index can not be serialized.
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Size, modification time and content hash of the indexed file. Size and
+/// time are checked first, the hash is only computed if they changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// BLAKE3 of the file content in hex
+    pub hash: String,
+}
+
+/// What the Explorer needs to know about a single state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedState {
+    pub name: String,
+    pub dirs: u8,
+    pub frames: u32,
+    /// Delays of the frames in ticks, empty without animation
+    pub delays: Vec<f32>,
+    pub movement: bool,
+    pub rewind: bool,
+    /// Distinct perceptual hashes of the frames of every dir, up to
+    /// [`HASHED_FRAMES_LIMIT`] frames, see [`perceptual_hash`]
+    pub hashes: Vec<u64>,
+    /// Missing frames, see [`check_state_frames`]
+    pub warnings: Vec<StateWarning>,
+}
+
+/// Indexed DMI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub stamp: FileStamp,
+    pub width: u32,
+    pub height: u32,
+    /// In the file order
+    pub states: Vec<IndexedState>,
+}
+
+impl IndexEntry {
+    pub fn state_names(&self) -> Vec<String> {
+        self.states.iter().map(|state| state.name.clone()).collect()
+    }
+
+    /// Frames of every dir of every state.
+    pub fn total_frames(&self) -> u64 {
+        self.states
+            .iter()
+            .map(|state| state.dirs.max(1) as u64 * state.frames as u64)
+            .sum()
+    }
+}
+
+/// DMIs opened in the Explorer, kept between the sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplorerIndex {
+    pub version: u32,
+    pub entries: BTreeMap<PathBuf, IndexEntry>,
+    /// Folders added to the Explorer, new DMIs in them are indexed on sight
+    #[serde(default)]
+    pub roots: BTreeSet<PathBuf>,
+    /// DMIs removed by the user, they are not added again by the watcher or
+    /// the project scan, only when loaded explicitly
+    #[serde(default)]
+    pub removed: BTreeSet<PathBuf>,
+    /// `.dme` or the root folder of the BYOND project, which code is
+    /// scanned for the icon references
+    #[serde(default)]
+    pub project: Option<PathBuf>,
+}
+
+impl Default for ExplorerIndex {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            entries: BTreeMap::new(),
+            roots: BTreeSet::new(),
+            removed: BTreeSet::new(),
+            project: None,
+        }
+    }
+}
+
+impl ExplorerIndex {
+    /// Loads the index from `data_dir`. Missing, broken or outdated index
+    /// gives an empty one.
+    pub fn load<T: AsRef<Path>>(data_dir: T) -> Self {
+        let path = data_dir.as_ref().join(INDEX_FILE_NAME);
+        let Ok(content) = fs::read(&path) else {
+            return Self::default();
+        };
+        match serde_json::from_slice::<Self>(&content) {
+            Ok(index) if index.version == INDEX_VERSION => index,
+            Ok(index) => {
+                warn!(
+                    "Dropping Explorer index of version {}, expected {}",
+                    index.version, INDEX_VERSION
+                );
+                Self::default()
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to read Explorer index {}: {}",
+                    path.to_string_lossy(),
+                    err
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Saves the index into `data_dir` through a temporary file.
+    pub fn save<T: AsRef<Path>>(
+        &self,
+        data_dir: T,
+    ) -> Result<(), DMIIndexError> {
+        let path = data_dir.as_ref().join(INDEX_FILE_NAME);
+        let mut temporary_file = path.as_os_str().to_owned();
+        temporary_file.push(".tmp");
+        fs::write(&temporary_file, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary_file, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary_file);
+        })?;
+        Ok(())
+    }
+
+    /// Depth of the path inside the closest root, if it is in any.
+    pub fn depth_in_roots<T: AsRef<Path>>(&self, path: T) -> Option<usize> {
+        let path = path.as_ref();
+        self.roots
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .map(|relative| relative.components().count())
+            .min()
+    }
+}
+
+/// Size and modification time of the file still match the stamp.
+pub fn is_unchanged<T: AsRef<Path>>(path: T, stamp: &FileStamp) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| {
+        metadata.len() == stamp.size
+            && metadata.modified().ok() == stamp.modified
+    })
+}
+
+/// Indexes the DMI, reusing the `previous` entry if the file did not change.
+/// Returns the entry and whether the file was parsed again.
+pub fn index_dmi<T: AsRef<Path>>(
+    path: T,
+    previous: Option<&IndexEntry>,
+) -> Result<(IndexEntry, bool), DMIParsingError> {
+    let path = path.as_ref();
+    if let Some(previous) = previous
+        && is_unchanged(path, &previous.stamp)
+    {
+        return Ok((previous.clone(), false));
+    }
+
+    let metadata = fs::metadata(path)?;
+    let content = fs::read(path)?;
+    let stamp = FileStamp {
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        hash: blake3::hash(&content).to_hex().to_string(),
+    };
+    // Touched, but not changed
+    if let Some(previous) = previous
+        && previous.stamp.hash == stamp.hash
+    {
+        return Ok((
+            IndexEntry {
+                stamp,
+                ..previous.clone()
+            },
+            false,
+        ));
+    }
+
+    let icon = Icon::load(Cursor::new(content))?;
+    let entry = IndexEntry {
+        stamp,
+        width: icon.width,
+        height: icon.height,
+        states: icon
+            .states
+            .iter()
+            .map(|state| IndexedState {
+                name: state.name.clone(),
+                dirs: state.dirs,
+                frames: state.frames,
+                delays: state.delay.clone().unwrap_or_default(),
+                movement: state.movement,
+                rewind: state.rewind,
+                hashes: frame_hashes(state),
+                warnings: check_state_frames(state),
+            })
+            .collect(),
+    };
+    Ok((entry, true))
+}
+
+/// Images are stored frame by frame, every frame holds all the dirs.
+fn frame_hashes(state: &IconState) -> Vec<u64> {
+    let mut hashes: Vec<u64> = state
+        .images
+        .iter()
+        .take(state.dirs as usize * HASHED_FRAMES_LIMIT)
+        .map(perceptual_hash)
+        .collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+/// Indexed DMI as it is exported, see [`export_dmi_entry`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedDMI {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// In bytes
+    pub file_size: u64,
+    /// RFC 3339 in the local time zone
+    pub modified: Option<String>,
+    pub states: Vec<ExportedState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedState {
+    pub name: String,
+    pub dirs: u8,
+    pub frames: u32,
+    /// In ticks
+    pub delays: Vec<f32>,
+    pub movement: bool,
+    pub rewind: bool,
+}
+
+/// Exports the entry with the states, which pass `keep_state`.
+pub fn export_dmi_entry(
+    path: &Path,
+    entry: &IndexEntry,
+    keep_state: impl Fn(&IndexedState) -> bool,
+) -> ExportedDMI {
+    ExportedDMI {
+        path: path.to_path_buf(),
+        width: entry.width,
+        height: entry.height,
+        file_size: entry.stamp.size,
+        modified: entry
+            .stamp
+            .modified
+            .map(|modified| DateTime::<Local>::from(modified).to_rfc3339()),
+        states: entry
+            .states
+            .iter()
+            .filter(|state| keep_state(state))
+            .map(|state| ExportedState {
+                name: state.name.clone(),
+                dirs: state.dirs,
+                frames: state.frames,
+                delays: state.delays.clone(),
+                movement: state.movement,
+                rewind: state.rewind,
+            })
+            .collect(),
+    }
+}
+
+/// A line for every state, delays are separated by `;`. DMIs without states
+/// get a single line with empty state columns.
+pub fn exported_dmis_to_csv(dmis: &[ExportedDMI]) -> String {
+    let mut csv = String::from(
+        "dmi,width,height,file_size,modified,state,dirs,frames,delays,\
+         movement,rewind\n",
+    );
+    for dmi in dmis {
+        let dmi_columns = format!(
+            "{},{},{},{},{}",
+            csv_field(dmi.path.to_string_lossy()),
+            dmi.width,
+            dmi.height,
+            dmi.file_size,
+            dmi.modified.as_deref().unwrap_or_default()
+        );
+        if dmi.states.is_empty() {
+            let _ = writeln!(csv, "{},,,,,,", dmi_columns);
+        }
+        for state in &dmi.states {
+            let delays: Vec<String> =
+                state.delays.iter().map(f32::to_string).collect();
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                dmi_columns,
+                csv_field(&state.name),
+                state.dirs,
+                state.frames,
+                delays.join(";"),
+                state.movement,
+                state.rewind
+            );
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::dmi_utils::save_dmi;
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "dmi_assistant_index_{}_{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn icon(names: &[&str]) -> Icon {
+        Icon {
+            width: 32,
+            height: 32,
+            states: names
+                .iter()
+                .map(|name| IconState {
+                    name: name.to_string(),
+                    dirs: 1,
+                    frames: 1,
+                    images: vec![DynamicImage::new_rgba8(32, 32)],
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn indexed_state(name: &str, delays: Vec<f32>) -> IndexedState {
+        IndexedState {
+            name: name.to_string(),
+            dirs: 4,
+            frames: delays.len().max(1) as u32,
+            delays,
+            movement: false,
+            rewind: false,
+            hashes: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reuses_unchanged_entries() {
+        let dir = temp_dir("freshness");
+        let path = dir.join("a.dmi");
+        save_dmi(&icon(&["a", "b"]), &path).unwrap();
+
+        let (entry, reparsed) = index_dmi(&path, None).unwrap();
+        assert!(reparsed);
+        assert_eq!(entry.state_names(), ["a", "b"]);
+        assert!(is_unchanged(&path, &entry.stamp));
+
+        let (reused, reparsed) = index_dmi(&path, Some(&entry)).unwrap();
+        assert!(!reparsed);
+        assert_eq!(reused, entry);
+
+        // Touched, but the content is the same
+        let touched = IndexEntry {
+            stamp: FileStamp {
+                modified: None,
+                ..entry.stamp.clone()
+            },
+            ..entry.clone()
+        };
+        assert!(!is_unchanged(&path, &touched.stamp));
+        let (refreshed, reparsed) = index_dmi(&path, Some(&touched)).unwrap();
+        assert!(!reparsed);
+        assert_eq!(refreshed, entry);
+
+        save_dmi(&icon(&["a", "b", "c"]), &path).unwrap();
+        let changed = !is_unchanged(&path, &entry.stamp);
+        let (updated, reparsed) = index_dmi(&path, Some(&entry)).unwrap();
+        let removed = index_dmi(dir.join("missing.dmi"), Some(&entry));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(changed);
+        assert!(reparsed);
+        assert_eq!(updated.state_names(), ["a", "b", "c"]);
+        assert_ne!(updated.stamp.hash, entry.stamp.hash);
+        assert!(removed.is_err());
+    }
+
+    #[test]
+    fn index_survives_saving() {
+        let dir = temp_dir("round_trip");
+        let mut index = ExplorerIndex::default();
+        index.entries.insert(
+            dir.join("a.dmi"),
+            IndexEntry {
+                stamp: FileStamp {
+                    size: 42,
+                    modified: Some(SystemTime::now()),
+                    hash: "hash".to_string(),
+                },
+                width: 32,
+                height: 64,
+                states: vec![indexed_state("walk", vec![1.0, 2.5])],
+            },
+        );
+        index.roots.insert(dir.clone());
+        index.removed.insert(dir.join("b.dmi"));
+        index.project = Some(dir.join("project.dme"));
+
+        index.save(&dir).unwrap();
+        let loaded = ExplorerIndex::load(&dir);
+        let outdated = ExplorerIndex {
+            version: INDEX_VERSION - 1,
+            ..index.clone()
+        };
+        outdated.save(&dir).unwrap();
+        let dropped = ExplorerIndex::load(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let missing = ExplorerIndex::load(&dir);
+
+        assert_eq!(loaded.version, INDEX_VERSION);
+        assert_eq!(loaded.entries, index.entries);
+        assert_eq!(loaded.roots, index.roots);
+        assert_eq!(loaded.removed, index.removed);
+        assert_eq!(loaded.project, index.project);
+        assert!(dropped.entries.is_empty() && dropped.roots.is_empty());
+        assert!(missing.entries.is_empty());
+    }
+
+    /// Image, which perceptual hash has only the given bit set.
+    fn dot(bit: u32) -> DynamicImage {
+        let mut image = RgbaImage::new(9, 8);
+        image.put_pixel(bit % 8, bit / 8, Rgba([255, 255, 255, 255]));
+        DynamicImage::ImageRgba8(image)
+    }
+
+    #[test]
+    fn hashes_every_dir_and_frame() {
+        let state = IconState {
+            name: "walk".to_string(),
+            dirs: 4,
+            frames: 2,
+            images: (0..8).map(dot).collect(),
+            ..Default::default()
+        };
+        let mut expected: Vec<u64> =
+            state.images.iter().map(perceptual_hash).collect();
+        expected.sort_unstable();
+        assert_eq!(frame_hashes(&state), expected);
+        assert_eq!(expected.len(), 8);
+    }
+
+    #[test]
+    fn hashes_limited_frames_once() {
+        let limit = HASHED_FRAMES_LIMIT as u32;
+        let mut images: Vec<DynamicImage> = (0..limit - 1).map(dot).collect();
+        images.push(dot(0));
+        images.push(dot(limit));
+        let state = IconState {
+            name: "long".to_string(),
+            dirs: 1,
+            frames: images.len() as u32,
+            images,
+            ..Default::default()
+        };
+        let hashes = frame_hashes(&state);
+        assert_eq!(hashes.len(), HASHED_FRAMES_LIMIT - 1);
+        assert!(!hashes.contains(&perceptual_hash(&dot(limit))));
+    }
+
+    #[test]
+    fn exports_csv_with_a_row_for_every_state() {
+        let entry = |states: Vec<IndexedState>| IndexEntry {
+            stamp: FileStamp {
+                size: 100,
+                modified: None,
+                hash: String::new(),
+            },
+            width: 32,
+            height: 32,
+            states,
+        };
+        let animated = entry(vec![
+            indexed_state("walk, fast", vec![1.0, 2.5]),
+            indexed_state("skipped", Vec::new()),
+            indexed_state("idle", Vec::new()),
+        ]);
+        let dmis = [
+            export_dmi_entry(Path::new("mob.dmi"), &animated, |state| {
+                state.name != "skipped"
+            }),
+            export_dmi_entry(
+                Path::new("empty.dmi"),
+                &entry(Vec::new()),
+                |_| true,
+            ),
+        ];
+
+        assert_eq!(
+            exported_dmis_to_csv(&dmis),
+            "dmi,width,height,file_size,modified,state,dirs,frames,delays,\
+             movement,rewind\n\
+             mob.dmi,32,32,100,,\"walk, fast\",4,2,1;2.5,false,false\n\
+             mob.dmi,32,32,100,,idle,4,1,,false,false\n\
+             empty.dmi,32,32,100,,,,,,,\n"
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use dmi::icon::Icon;
+use serde::Serialize;
+
+/// Icons bigger than this in any dimension are reported by default.
+pub const DEFAULT_MAX_ICON_SIZE: u32 = 128;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize,
+)]
+pub enum LintSeverity {
+    /// BYOND will show the state wrong or not at all
+    Error,
+    /// Probably a mistake, but works
+    Warning,
+}
+
+impl Display for LintSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum LintKind {
+    DuplicateStateName,
+    /// No frames, dirs or images at all
+    EmptyState,
+    /// Every pixel is transparent
+    BlankState,
+    /// Fewer images than `dirs * frames`
+    MissingImages,
+    /// More images than `dirs * frames`
+    ExtraImages,
+    DelayLengthMismatch,
+    NonPositiveDelay,
+    /// Dirs other than 1, 4 or 8
+    UnusualDirs,
+    OversizedIcon,
+    /// File could not be loaded as a DMI
+    UnreadableFile,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    pub severity: LintSeverity,
+    /// None for the issues of the whole icon
+    pub state: Option<String>,
+    pub message: String,
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.state {
+            Some(state) => write!(
+                f,
+                "[{}] state {:?}: {}",
+                self.severity, state, self.message
+            ),
+            None => write!(f, "[{}] {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Inspects the icon and returns the found issues, errors first.
+pub fn lint_icon(icon: &Icon, max_icon_size: u32) -> Vec<LintIssue> {
+    let mut issues: Vec<LintIssue> = Vec::new();
+    let mut issue = |kind: LintKind,
+                     severity: LintSeverity,
+                     state: Option<&str>,
+                     message| {
+        issues.push(LintIssue {
+            kind,
+            severity,
+            state: state.map(str::to_string),
+            message,
+        })
+    };
+
+    if icon.width > max_icon_size || icon.height > max_icon_size {
+        issue(
+            LintKind::OversizedIcon,
+            LintSeverity::Warning,
+            None,
+            format!(
+                "icon size {}x{} is bigger than {}x{}",
+                icon.width, icon.height, max_icon_size, max_icon_size
+            ),
+        );
+    }
+
+    // Movement and non-movement states may share the name
+    let mut name_counts: BTreeMap<(&str, bool), usize> = BTreeMap::new();
+    for state in &icon.states {
+        *name_counts
+            .entry((&state.name, state.movement))
+            .or_default() += 1;
+    }
+    for ((name, movement), count) in name_counts {
+        if count > 1 {
+            issue(
+                LintKind::DuplicateStateName,
+                LintSeverity::Error,
+                Some(name),
+                format!(
+                    "name is used by {} {} states",
+                    count,
+                    if movement { "movement" } else { "non-movement" }
+                ),
+            );
+        }
+    }
+
+    for state in &icon.states {
+        let name = Some(state.name.as_str());
+        if state.dirs == 0 || state.frames == 0 || state.images.is_empty() {
+            issue(
+                LintKind::EmptyState,
+                LintSeverity::Error,
+                name,
+                format!(
+                    "has {} dirs, {} frames and {} images",
+                    state.dirs,
+                    state.frames,
+                    state.images.len()
+                ),
+            );
+            continue;
+        }
+
+        if ![1, 4, 8].contains(&state.dirs) {
+            issue(
+                LintKind::UnusualDirs,
+                LintSeverity::Warning,
+                name,
+                format!("has {} dirs, expected 1, 4 or 8", state.dirs),
+            );
+        }
+
+        let expected_images = state.dirs as usize * state.frames as usize;
+        if state.images.len() < expected_images {
+            issue(
+                LintKind::MissingImages,
+                LintSeverity::Error,
+                name,
+                format!(
+                    "has {} images, but {} dirs and {} frames need {}",
+                    state.images.len(),
+                    state.dirs,
+                    state.frames,
+                    expected_images
+                ),
+            );
+        } else if state.images.len() > expected_images {
+            issue(
+                LintKind::ExtraImages,
+                LintSeverity::Warning,
+                name,
+                format!(
+                    "has {} images, only {} of them are used",
+                    state.images.len(),
+                    expected_images
+                ),
+            );
+        }
+
+        if state
+            .images
+            .iter()
+            .all(|image| image.to_rgba8().pixels().all(|pixel| pixel[3] == 0))
+        {
+            issue(
+                LintKind::BlankState,
+                LintSeverity::Warning,
+                name,
+                "every pixel is transparent".to_string(),
+            );
+        }
+
+        match &state.delay {
+            Some(delay)
+                if state.frames > 1 && delay.len() != state.frames as usize =>
+            {
+                issue(
+                    LintKind::DelayLengthMismatch,
+                    LintSeverity::Warning,
+                    name,
+                    format!(
+                        "has {} delays for {} frames",
+                        delay.len(),
+                        state.frames
+                    ),
+                )
+            }
+            None if state.frames > 1 => issue(
+                LintKind::DelayLengthMismatch,
+                LintSeverity::Warning,
+                name,
+                format!("has {} frames, but no delays", state.frames),
+            ),
+            _ => {}
+        }
+        if let Some(delay) = &state.delay
+            && delay.iter().any(|delay| *delay <= 0.0)
+        {
+            issue(
+                LintKind::NonPositiveDelay,
+                LintSeverity::Warning,
+                name,
+                format!("has zero or negative delays {:?}", delay),
+            );
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.severity);
+    issues
+}
+
+/// Counts errors and warnings.
+pub fn count_issues(issues: &[LintIssue]) -> (usize, usize) {
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == LintSeverity::Error)
+        .count();
+    (errors, issues.len() - errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use dmi::icon::IconState;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn images(count: usize) -> Vec<DynamicImage> {
+        let opaque = RgbaImage::from_pixel(32, 32, Rgba([255, 0, 0, 255]));
+        vec![DynamicImage::ImageRgba8(opaque); count]
+    }
+
+    fn state(name: &str) -> IconState {
+        IconState {
+            name: name.to_string(),
+            dirs: 1,
+            frames: 1,
+            images: images(1),
+            ..Default::default()
+        }
+    }
+
+    fn icon(states: Vec<IconState>) -> Icon {
+        Icon {
+            width: 32,
+            height: 32,
+            states,
+            ..Default::default()
+        }
+    }
+
+    fn kinds(icon: &Icon) -> Vec<LintKind> {
+        lint_icon(icon, DEFAULT_MAX_ICON_SIZE)
+            .into_iter()
+            .map(|issue| issue.kind)
+            .collect()
+    }
+
+    #[test]
+    fn clean_icon_has_no_issues() {
+        let mut animated = state("animated");
+        animated.dirs = 4;
+        animated.frames = 2;
+        animated.images = images(8);
+        animated.delay = Some(vec![1.0, 2.0]);
+        assert!(kinds(&icon(vec![state("a"), animated])).is_empty());
+    }
+
+    #[test]
+    fn reports_duplicate_names() {
+        let mut movement = state("walk");
+        movement.movement = true;
+        let issues = lint_icon(
+            &icon(vec![state("walk"), movement, state("a"), state("a")]),
+            DEFAULT_MAX_ICON_SIZE,
+        );
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, LintKind::DuplicateStateName);
+        assert_eq!(issues[0].severity, LintSeverity::Error);
+        assert_eq!(issues[0].state.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn reports_blank_and_empty_states() {
+        let mut blank = state("blank");
+        blank.images = vec![DynamicImage::new_rgba8(32, 32)];
+        let mut empty = state("empty");
+        empty.images.clear();
+        assert_eq!(
+            kinds(&icon(vec![blank, empty])),
+            [LintKind::EmptyState, LintKind::BlankState]
+        );
+    }
+
+    #[test]
+    fn reports_missing_and_extra_images() {
+        let mut missing = state("missing");
+        missing.dirs = 4;
+        let mut extra = state("extra");
+        extra.images = images(2);
+        assert_eq!(
+            kinds(&icon(vec![missing, extra])),
+            [LintKind::MissingImages, LintKind::ExtraImages]
+        );
+    }
+
+    #[test]
+    fn reports_delay_length_mismatch() {
+        let mut short = state("short");
+        short.frames = 3;
+        short.images = images(3);
+        short.delay = Some(vec![1.0, 1.0]);
+        let mut missing = short.clone();
+        missing.name = "missing".to_string();
+        missing.delay = None;
+        assert_eq!(
+            kinds(&icon(vec![short, missing])),
+            [LintKind::DelayLengthMismatch, LintKind::DelayLengthMismatch]
+        );
+    }
+
+    #[test]
+    fn reports_non_positive_delays() {
+        let mut zero = state("zero");
+        zero.frames = 2;
+        zero.images = images(2);
+        zero.delay = Some(vec![0.0, 1.0]);
+        let mut negative = zero.clone();
+        negative.name = "negative".to_string();
+        negative.delay = Some(vec![1.0, -1.0]);
+        assert_eq!(
+            kinds(&icon(vec![zero, negative])),
+            [LintKind::NonPositiveDelay, LintKind::NonPositiveDelay]
+        );
+    }
+
+    #[test]
+    fn reports_unusual_dirs() {
+        let mut unusual = state("unusual");
+        unusual.dirs = 2;
+        unusual.images = images(2);
+        assert_eq!(kinds(&icon(vec![unusual])), [LintKind::UnusualDirs]);
+    }
+
+    #[test]
+    fn reports_oversized_icon() {
+        let oversized = Icon {
+            height: DEFAULT_MAX_ICON_SIZE + 1,
+            ..icon(Vec::new())
+        };
+        assert_eq!(kinds(&oversized), [LintKind::OversizedIcon]);
+        assert!(lint_icon(&oversized, DEFAULT_MAX_ICON_SIZE * 2).is_empty());
+    }
+
+    #[test]
+    fn errors_go_first() {
+        let mut unusual = state("unusual");
+        unusual.dirs = 2;
+        unusual.images = images(2);
+        let mut empty = state("empty");
+        empty.images.clear();
+        let issues = lint_icon(&icon(vec![unusual, empty]), 32);
+        assert_eq!(count_issues(&issues), (1, 1));
+        assert_eq!(issues[0].severity, LintSeverity::Error);
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+};
+
+use dmi::icon::{Icon, IconState};
+use thiserror::Error;
+
+use crate::dmi_model::StateSummary;
+
+/// Errors, returned by three-way merging of DMIs.
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DMIMergeError {
+    /// Icon size was changed on any side.
+    #[error(
+        "Icon size is {base} in base, {ours} in ours and {theirs} in theirs"
+    )]
+    SizeConflict {
+        base: String,
+        ours: String,
+        theirs: String,
+    },
+    /// Some conflicts have no chosen side.
+    #[error("{0} conflicting states are not resolved")]
+    Unresolved(usize),
+}
+
+/// One of the three versions of the merged DMI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeSide {
+    /// Common ancestor
+    Base,
+    /// Current branch
+    Ours,
+    /// Branch being merged
+    Theirs,
+}
+
+impl Display for MergeSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Base => "Base",
+            Self::Ours => "Ours",
+            Self::Theirs => "Theirs",
+        })
+    }
+}
+
+impl FromStr for MergeSide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "base" => Ok(Self::Base),
+            "ours" => Ok(Self::Ours),
+            "theirs" => Ok(Self::Theirs),
+            _ => Err(format!(
+                "unknown merge side {}, expected base, ours or theirs",
+                s
+            )),
+        }
+    }
+}
+
+/// Identifies a state in all three versions. A DMI may hold several states
+/// with the same name, like the movement and non-movement ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StateKey {
+    pub name: String,
+    pub movement: bool,
+    /// Number of the earlier states with the same name and movement flag
+    pub occurrence: usize,
+}
+
+impl Display for StateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if self.movement {
+            f.write_str(" (movement)")?;
+        }
+        if self.occurrence > 0 {
+            write!(f, " #{}", self.occurrence + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keys of the states in order.
+pub fn keyed_states(states: Vec<IconState>) -> Vec<(StateKey, IconState)> {
+    let mut occurrences: HashMap<(String, bool), usize> = HashMap::new();
+    states
+        .into_iter()
+        .map(|state| {
+            let occurrence = occurrences
+                .entry((state.name.clone(), state.movement))
+                .or_default();
+            let key = StateKey {
+                name: state.name.clone(),
+                movement: state.movement,
+                occurrence: *occurrence,
+            };
+            *occurrence += 1;
+            (key, state)
+        })
+        .collect()
+}
+
+/// State changed differently on both sides. Missing version means, that the
+/// state does not exist (was deleted or not yet added) in it.
+#[derive(Debug, Clone)]
+pub struct StateConflict {
+    pub key: StateKey,
+    pub base: Option<IconState>,
+    pub ours: Option<IconState>,
+    pub theirs: Option<IconState>,
+}
+
+impl StateConflict {
+    pub fn get(&self, side: MergeSide) -> Option<&IconState> {
+        match side {
+            MergeSide::Base => self.base.as_ref(),
+            MergeSide::Ours => self.ours.as_ref(),
+            MergeSide::Theirs => self.theirs.as_ref(),
+        }
+    }
+
+    /// Human-readable description, like `deleted in ours, modified in theirs`.
+    pub fn describe(&self) -> String {
+        let change = |state: &Option<IconState>| match (&self.base, state) {
+            (None, _) => "added",
+            (Some(_), None) => "deleted",
+            (Some(_), Some(_)) => "modified",
+        };
+        format!(
+            "{} in ours, {} in theirs",
+            change(&self.ours),
+            change(&self.theirs)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MergeEntry {
+    /// Changes did not overlap
+    Merged(IconState),
+    Conflict(StateConflict),
+}
+
+/// Result of the three-way merge, before the conflicts are resolved.
+#[derive(Debug, Clone)]
+pub struct ThreeWayMerge {
+    /// Our icon without states, gives metadata to the result
+    pub template: Icon,
+    /// States of the result in order. States deleted without conflicts are
+    /// not included.
+    pub entries: Vec<MergeEntry>,
+}
+
+impl ThreeWayMerge {
+    pub fn conflicts(&self) -> impl Iterator<Item = &StateConflict> {
+        self.entries.iter().filter_map(|entry| match entry {
+            MergeEntry::Conflict(conflict) => Some(conflict),
+            MergeEntry::Merged(_) => None,
+        })
+    }
+
+    pub fn conflict_count(&self) -> usize {
+        self.conflicts().count()
+    }
+
+    /// Builds the merged icon. Conflicts take the side chosen in `choices` by
+    /// state key, or `default`, if there is no choice.
+    pub fn resolve(
+        &self,
+        choices: &HashMap<StateKey, MergeSide>,
+        default: Option<MergeSide>,
+    ) -> Result<Icon, DMIMergeError> {
+        let unresolved = self
+            .conflicts()
+            .filter(|conflict| {
+                default.is_none() && !choices.contains_key(&conflict.key)
+            })
+            .count();
+        if unresolved > 0 {
+            return Err(DMIMergeError::Unresolved(unresolved));
+        }
+
+        let mut icon = self.template.clone();
+        for entry in &self.entries {
+            match entry {
+                MergeEntry::Merged(state) => icon.states.push(state.clone()),
+                MergeEntry::Conflict(conflict) => {
+                    let side = choices
+                        .get(&conflict.key)
+                        .copied()
+                        .or(default)
+                        .unwrap_or(MergeSide::Ours);
+                    if let Some(state) = conflict.get(side) {
+                        icon.states.push(state.clone());
+                    }
+                }
+            }
+        }
+        Ok(icon)
+    }
+}
+
+/// Compares metadata and pixels of both states.
+pub fn states_equal(a: Option<&IconState>, b: Option<&IconState>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            StateSummary::from(a) == StateSummary::from(b)
+                && a.images.len() == b.images.len()
+                && a.images.iter().zip(&b.images).all(|(a, b)| {
+                    a.width() == b.width()
+                        && a.height() == b.height()
+                        && a.to_rgba8().as_raw() == b.to_rgba8().as_raw()
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Merges state-level changes of `ours` and `theirs` made since `base`.
+/// States are matched by name and movement flag, several states with the
+/// same both are matched in order. A state changed on only one side takes
+/// that side's version, a state changed on both sides the same way is taken
+/// as is, anything else is a conflict. Icon size must be the same in all
+/// three versions.
+///
+/// Result keeps our order of states, states added only in theirs are placed
+/// after the state preceding them in theirs.
+pub fn merge_three_way(
+    base: Icon,
+    mut ours: Icon,
+    theirs: Icon,
+) -> Result<ThreeWayMerge, DMIMergeError> {
+    let size = |icon: &Icon| (icon.width, icon.height);
+    if size(&base) != size(&ours) || size(&ours) != size(&theirs) {
+        let format_size =
+            |icon: &Icon| format!("{}x{}", icon.width, icon.height);
+        return Err(DMIMergeError::SizeConflict {
+            base: format_size(&base),
+            ours: format_size(&ours),
+            theirs: format_size(&theirs),
+        });
+    }
+
+    // Our icon without states gives metadata to the result
+    let ours_keyed = keyed_states(std::mem::take(&mut ours.states));
+    let theirs_keyed = keyed_states(theirs.states);
+
+    let mut keys: Vec<StateKey> =
+        ours_keyed.iter().map(|(key, _)| key.clone()).collect();
+    let ours_keys: HashSet<&StateKey> =
+        ours_keyed.iter().map(|(key, _)| key).collect();
+    let mut previous: Option<&StateKey> = None;
+    for (key, _) in &theirs_keyed {
+        if !ours_keys.contains(key) {
+            let position = previous
+                .and_then(|previous| {
+                    keys.iter().position(|other| other == previous)
+                })
+                .map_or(keys.len(), |position| position + 1);
+            keys.insert(position, key.clone());
+        }
+        previous = Some(key);
+    }
+
+    let mut base_states: HashMap<StateKey, IconState> =
+        keyed_states(base.states).into_iter().collect();
+    let mut ours_states: HashMap<StateKey, IconState> =
+        ours_keyed.into_iter().collect();
+    let mut theirs_states: HashMap<StateKey, IconState> =
+        theirs_keyed.into_iter().collect();
+    let mut entries: Vec<MergeEntry> = Vec::with_capacity(keys.len());
+    for key in keys {
+        let base_state = base_states.remove(&key);
+        let ours_state = ours_states.remove(&key);
+        let theirs_state = theirs_states.remove(&key);
+
+        let merged = if states_equal(ours_state.as_ref(), theirs_state.as_ref())
+            || states_equal(theirs_state.as_ref(), base_state.as_ref())
+        {
+            ours_state
+        } else if states_equal(ours_state.as_ref(), base_state.as_ref()) {
+            theirs_state
+        } else {
+            entries.push(MergeEntry::Conflict(StateConflict {
+                key,
+                base: base_state,
+                ours: ours_state,
+                theirs: theirs_state,
+            }));
+            continue;
+        };
+        if let Some(state) = merged {
+            entries.push(MergeEntry::Merged(state));
+        }
+    }
+
+    Ok(ThreeWayMerge {
+        template: ours,
+        entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn state(name: &str, movement: bool, shade: u8) -> IconState {
+        IconState {
+            name: name.to_string(),
+            dirs: 1,
+            frames: 1,
+            images: vec![DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                32,
+                32,
+                Rgba([shade, 0, 0, 255]),
+            ))],
+            movement,
+            ..Default::default()
+        }
+    }
+
+    fn icon(states: Vec<IconState>) -> Icon {
+        Icon {
+            width: 32,
+            height: 32,
+            states,
+            ..Default::default()
+        }
+    }
+
+    /// Name, movement flag and shade of every state.
+    fn summary(icon: &Icon) -> Vec<(&str, bool, u8)> {
+        icon.states
+            .iter()
+            .map(|state| {
+                let shade = state.images[0].to_rgba8().get_pixel(0, 0).0[0];
+                (state.name.as_str(), state.movement, shade)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_different_states_edited_on_each_side() {
+        let base = icon(vec![state("a", false, 1), state("b", false, 1)]);
+        let ours = icon(vec![state("a", false, 2), state("b", false, 1)]);
+        let theirs = icon(vec![
+            state("a", false, 1),
+            state("b", false, 3),
+            state("c", false, 3),
+        ]);
+
+        let merge = merge_three_way(base, ours, theirs).unwrap();
+        assert_eq!(merge.conflict_count(), 0);
+        let merged = merge.resolve(&HashMap::new(), None).unwrap();
+        assert_eq!(
+            summary(&merged),
+            [("a", false, 2), ("b", false, 3), ("c", false, 3)]
+        );
+    }
+
+    #[test]
+    fn same_state_edited_on_both_sides_conflicts() {
+        let base = icon(vec![state("a", false, 1)]);
+        let ours = icon(vec![state("a", false, 2)]);
+        let theirs = icon(vec![state("a", false, 3)]);
+
+        let merge = merge_three_way(base, ours, theirs).unwrap();
+        let conflicts: Vec<&StateConflict> = merge.conflicts().collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].key.name, "a");
+        assert_eq!(
+            conflicts[0].describe(),
+            "modified in ours, modified in theirs"
+        );
+        assert!(matches!(
+            merge.resolve(&HashMap::new(), None),
+            Err(DMIMergeError::Unresolved(1))
+        ));
+
+        let choices =
+            HashMap::from([(conflicts[0].key.clone(), MergeSide::Theirs)]);
+        let merged = merge.resolve(&choices, None).unwrap();
+        assert_eq!(summary(&merged), [("a", false, 3)]);
+        let merged = merge.resolve(&HashMap::new(), Some(MergeSide::Base));
+        assert_eq!(summary(&merged.unwrap()), [("a", false, 1)]);
+    }
+
+    #[test]
+    fn delete_against_modify_conflicts() {
+        let base = icon(vec![state("a", false, 1), state("b", false, 1)]);
+        let ours = icon(vec![state("b", false, 1)]);
+        let theirs = icon(vec![state("a", false, 2), state("b", false, 1)]);
+
+        let merge = merge_three_way(base, ours, theirs).unwrap();
+        let conflicts: Vec<&StateConflict> = merge.conflicts().collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].describe(),
+            "deleted in ours, modified in theirs"
+        );
+        let merged = merge
+            .resolve(&HashMap::new(), Some(MergeSide::Ours))
+            .unwrap();
+        assert_eq!(summary(&merged), [("b", false, 1)]);
+    }
+
+    #[test]
+    fn delete_of_unchanged_state_is_kept() {
+        let base = icon(vec![state("a", false, 1), state("b", false, 1)]);
+        let ours = icon(vec![state("b", false, 1)]);
+        let theirs = icon(vec![state("a", false, 1), state("b", false, 1)]);
+
+        let merge = merge_three_way(base, ours, theirs).unwrap();
+        assert_eq!(merge.conflict_count(), 0);
+        let merged = merge.resolve(&HashMap::new(), None).unwrap();
+        assert_eq!(summary(&merged), [("b", false, 1)]);
+    }
+
+    #[test]
+    fn movement_variants_sharing_a_name_merge_separately() {
+        let base = icon(vec![state("walk", false, 1), state("walk", true, 1)]);
+        let ours = icon(vec![state("walk", false, 2), state("walk", true, 1)]);
+        let theirs =
+            icon(vec![state("walk", false, 1), state("walk", true, 3)]);
+
+        let merge = merge_three_way(base, ours, theirs).unwrap();
+        assert_eq!(merge.conflict_count(), 0);
+        let merged = merge.resolve(&HashMap::new(), None).unwrap();
+        assert_eq!(summary(&merged), [("walk", false, 2), ("walk", true, 3)]);
+    }
+
+    #[test]
+    fn rejects_different_sizes() {
+        let base = Icon {
+            width: 64,
+            ..icon(Vec::new())
+        };
+        let result = merge_three_way(base, icon(Vec::new()), icon(Vec::new()));
+        assert!(matches!(
+            result,
+            Err(DMIMergeError::SizeConflict { base, .. }) if base == "64x32"
+        ));
+    }
+}
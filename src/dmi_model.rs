@@ -1,14 +1,53 @@
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    sync::{Arc, OnceLock},
+};
 
 use dmi::icon::{Icon, IconState, Looping};
 use iced_gif::Frames;
 use image::{DynamicImage, imageops::FilterType};
-use log::error;
+use rayon::iter::{
+    IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    dmi_utils::Directions, screens::viewer::StateboxResizing, utils::animate,
+    dmi_utils::{Directions, get_state_frame},
+    screens::viewer::StateboxResizing,
+    utils::animate,
 };
 
+/// Identifies a state in the icon. A DMI may hold a movement and a
+/// non-movement state with the same name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StateId {
+    pub name: String,
+    pub movement: bool,
+}
+
+impl StateId {
+    pub fn of(state: &IconState) -> Self {
+        Self {
+            name: state.name.clone(),
+            movement: state.movement,
+        }
+    }
+}
+
+impl Display for StateId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if self.movement {
+            f.write_str(" (movement)")?;
+        }
+        Ok(())
+    }
+}
+
+/// DMI prepared for displaying. Parsing only splits the images by states and
+/// directions on all CPU cores, resized frames and animations are made on
+/// demand by the getters of [`ParsedState`] and cached.
 #[derive(Debug, Clone, Default)]
 pub struct ParsedDMI {
     pub original_height: u32,
@@ -17,132 +56,109 @@ pub struct ParsedDMI {
     pub displayed_height: u32,
     pub displayed_width: u32,
 
-    pub states: HashMap<String, ParsedState>,
+    /// States are shared with the background tasks preparing them, see
+    /// [`ParsedState::prepare`]
+    pub states: HashMap<StateId, Arc<ParsedState>>,
 }
 
 impl ParsedDMI {
+    /// Number of states with at least one warning.
+    pub fn states_with_warnings(&self) -> usize {
+        self.states
+            .values()
+            .filter(|state| !state.all_warnings().is_empty())
+            .count()
+    }
+
     pub fn parse_from_raw(
         raw: Icon,
         resizing: StateboxResizing,
         filter_type: FilterType,
     ) -> Self {
-        let original_height = raw.height;
-        let original_width = raw.width;
-
-        let displayed_height;
-        let displayed_width;
-        let new_resizing;
-
-        match resizing {
-            StateboxResizing::Original => {
-                displayed_height = original_height;
-                displayed_width = original_width;
-                new_resizing = resizing;
-            }
-            StateboxResizing::Resized { height, width } => {
-                if height > original_height && width > original_width {
-                    displayed_height = height;
-                    displayed_width = width;
-                    new_resizing = resizing;
-                } else if height > original_height {
-                    displayed_height = height;
-                    displayed_width = original_width;
-                    new_resizing = StateboxResizing::Resized {
-                        height,
-                        width: original_width,
-                    }
-                } else if width > original_width {
-                    displayed_height = original_height;
-                    displayed_width = width;
-                    new_resizing = StateboxResizing::Resized {
-                        height: original_height,
-                        width,
-                    }
-                } else {
-                    displayed_height = original_height;
-                    displayed_width = original_width;
-                    new_resizing = StateboxResizing::Original;
-                }
-            }
-        }
+        let (resizing, displayed_height, displayed_width) =
+            fit_resizing(raw.height, raw.width, resizing);
 
-        let states: HashMap<String, ParsedState> = raw
+        let states: HashMap<StateId, Arc<ParsedState>> = raw
             .states
-            .into_iter()
+            .into_par_iter()
             .map(|state| {
                 (
-                    state.name.clone(),
-                    ParsedState::parse_from_raw(
+                    StateId::of(&state),
+                    Arc::new(ParsedState::parse_from_raw(
                         state,
-                        new_resizing,
+                        resizing,
                         filter_type,
-                    ),
+                    )),
                 )
             })
             .collect();
 
         Self {
-            original_height,
-            original_width,
+            original_height: raw.height,
+            original_width: raw.width,
             displayed_height,
             displayed_width,
             states,
         }
     }
 
+    /// Changes the size of the displayed frames. Resized frames and
+    /// animations made so far are dropped, original ones are kept.
     pub fn resize(
         &mut self,
         resizing: StateboxResizing,
         filter_type: FilterType,
     ) {
-        let new_resizing;
+        let (resizing, displayed_height, displayed_width) =
+            fit_resizing(self.original_height, self.original_width, resizing);
+        self.displayed_height = displayed_height;
+        self.displayed_width = displayed_width;
+        self.states.par_iter_mut().for_each(|(_, state)| {
+            *state = Arc::new(state.with_resizing(resizing, filter_type));
+        });
+    }
+}
 
-        match resizing {
-            StateboxResizing::Original => {
-                self.displayed_height = self.original_height;
-                self.displayed_width = self.original_width;
-                new_resizing = resizing;
-            }
-            StateboxResizing::Resized { height, width } => {
-                if height > self.original_height && width > self.original_width
-                {
-                    self.displayed_height = height;
-                    self.displayed_width = width;
-                    new_resizing = resizing;
-                } else if height > self.original_height {
-                    self.displayed_height = height;
-                    self.displayed_width = self.original_width;
-                    new_resizing = StateboxResizing::Resized {
+/// Icons are only scaled up, so the requested size is clamped to the original
+/// one. Returns the resizing to use with the displayed height and width.
+fn fit_resizing(
+    original_height: u32,
+    original_width: u32,
+    resizing: StateboxResizing,
+) -> (StateboxResizing, u32, u32) {
+    match resizing {
+        StateboxResizing::Original => {
+            (resizing, original_height, original_width)
+        }
+        StateboxResizing::Resized { height, width } => {
+            if height > original_height && width > original_width {
+                (resizing, height, width)
+            } else if height > original_height {
+                (
+                    StateboxResizing::Resized {
                         height,
-                        width: self.original_width,
-                    }
-                } else if width > self.original_width {
-                    self.displayed_height = self.original_height;
-                    self.displayed_width = width;
-                    new_resizing = StateboxResizing::Resized {
-                        height: self.original_height,
+                        width: original_width,
+                    },
+                    height,
+                    original_width,
+                )
+            } else if width > original_width {
+                (
+                    StateboxResizing::Resized {
+                        height: original_height,
                         width,
-                    }
-                } else {
-                    self.displayed_height = self.original_height;
-                    self.displayed_width = self.original_width;
-                    new_resizing = StateboxResizing::Original;
-                }
-            }
-        }
-
-        match new_resizing {
-            StateboxResizing::Original => {}
-            _ => {
-                for state in &mut self.states {
-                    state.1.resize(new_resizing, filter_type);
-                }
+                    },
+                    original_height,
+                    width,
+                )
+            } else {
+                (StateboxResizing::Original, original_height, original_width)
             }
         }
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct ParsedState {
     pub name: String,
 
@@ -152,28 +168,41 @@ pub struct ParsedState {
     pub frames: u32,
     pub movement: bool,
 
+    /// Size of the resized frames, `Original` means there are none
+    pub resizing: StateboxResizing,
+    pub filter_type: FilterType,
+
     pub dirs: BTreeMap<Directions, DirImage>,
+    /// Problems of the whole state, see [`ParsedState::all_warnings`]
+    pub warnings: Vec<StateWarning>,
 }
 
 impl ParsedState {
+    pub fn id(&self) -> StateId {
+        StateId {
+            name: self.name.clone(),
+            movement: self.movement,
+        }
+    }
+
     pub fn parse_from_raw(
         state: IconState,
         resizing: StateboxResizing,
         filter_type: FilterType,
     ) -> Self {
-        let mut dirs: BTreeMap<Directions, DirImage> = BTreeMap::new();
-        for dir_index in 0..state.dirs {
-            let direction: Directions = dir_index.into();
-            let dir_image = DirImage::parse_from_raw(
-                &state,
-                &resizing,
-                direction,
-                state.frames,
-                state.loop_flag,
-                filter_type,
-            );
-            dirs.insert(direction, dir_image);
+        let mut warnings: Vec<StateWarning> = Vec::new();
+        if state.dirs == 0 || state.frames == 0 {
+            warnings.push(StateWarning::Empty);
         }
+        let dirs: BTreeMap<Directions, DirImage> = (0..state.dirs)
+            .map(Directions::from)
+            .map(|direction| {
+                (
+                    direction,
+                    DirImage::parse_from_raw(&state, direction, state.frames),
+                )
+            })
+            .collect();
 
         Self {
             name: state.name,
@@ -182,19 +211,97 @@ impl ParsedState {
             rewind: state.rewind,
             frames: state.frames,
             movement: state.movement,
+            resizing,
+            filter_type,
             dirs,
+            warnings,
         }
     }
 
-    pub fn resize(
-        &mut self,
+    /// Copy of the state with another size of the resized frames.
+    pub fn with_resizing(
+        &self,
         resizing: StateboxResizing,
         filter_type: FilterType,
-    ) {
-        for dir in &mut self.dirs {
-            dir.1
-                .resize(self.loop_flag, &self.delay, resizing, filter_type);
+    ) -> Self {
+        Self {
+            resizing,
+            filter_type,
+            dirs: self
+                .dirs
+                .iter()
+                .map(|(direction, dir)| (*direction, dir.without_resized()))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Makes everything the view will need, so getting it later is cheap.
+    /// Thread-safe, meant to be called from background tasks.
+    pub fn prepare(&self, resized: bool, animated: bool) {
+        for direction in self.dirs.keys() {
+            match (animated, resized) {
+                (true, true) => {
+                    self.get_animated(direction);
+                }
+                (true, false) => {
+                    self.get_original_animated(direction);
+                }
+                (false, true) => {
+                    self.get_frame(direction, 0);
+                }
+                (false, false) => {}
+            }
+        }
+    }
+
+    /// Everything [`ParsedState::prepare`] makes is already cached.
+    pub fn is_prepared(&self, resized: bool, animated: bool) -> bool {
+        let resized = resized && self.resizing != StateboxResizing::Original;
+        self.dirs.values().all(|dir| {
+            dir.original_frames.is_empty()
+                || match (animated, resized) {
+                    (true, true) => dir.resized_animated.get().is_some(),
+                    (true, false) => dir.original_animated.get().is_some(),
+                    (false, true) => dir.resized_frames.get().is_some(),
+                    (false, false) => true,
+                }
+        })
+    }
+
+    /// Problems of the state and all of its directions, including failed
+    /// animations made so far.
+    pub fn all_warnings(&self) -> Vec<StateWarning> {
+        let mut warnings = self.warnings.clone();
+        for (direction, dir) in &self.dirs {
+            warnings.extend(dir.warnings.iter().cloned());
+            let animations = [
+                (false, dir.original_animated.get()),
+                (true, dir.resized_animated.get()),
+            ];
+            for (resized, animated) in animations {
+                if let Some(Err(reason)) = animated {
+                    warnings.push(StateWarning::AnimationFailed {
+                        dir: *direction,
+                        resized,
+                        reason: reason.clone(),
+                    });
+                }
+            }
         }
+        warnings
+    }
+
+    fn resized_frames(&self, dir: &DirImage) -> Option<&Vec<DynamicImage>> {
+        let StateboxResizing::Resized { height, width } = self.resizing else {
+            return None;
+        };
+        Some(dir.resized_frames.get_or_init(|| {
+            dir.original_frames
+                .iter()
+                .map(|frame| frame.resize(width, height, self.filter_type))
+                .collect()
+        }))
     }
 
     pub fn get_frame(
@@ -202,177 +309,196 @@ impl ParsedState {
         dir: &Directions,
         frame: usize,
     ) -> Option<&DynamicImage> {
-        self.dirs.get(dir)?.get_frame(frame)
+        let dir = self.dirs.get(dir)?;
+        match self.resized_frames(dir) {
+            Some(frames) => frames.get(frame),
+            None => dir.original_frames.get(frame),
+        }
     }
+
     pub fn get_original_frame(
         &self,
         dir: &Directions,
         frame: usize,
     ) -> Option<&DynamicImage> {
-        self.dirs.get(dir)?.get_original_frame(frame)
+        self.dirs.get(dir)?.original_frames.get(frame)
     }
 
     pub fn get_animated(&self, dir: &Directions) -> Option<&Animated> {
-        self.dirs.get(dir)?.get_animated()
+        let dir_image = self.dirs.get(dir)?;
+        let Some(resized_frames) = self.resized_frames(dir_image) else {
+            return self.get_original_animated(dir);
+        };
+        if resized_frames.is_empty() {
+            return None;
+        }
+        dir_image
+            .resized_animated
+            .get_or_init(|| {
+                make_animated(
+                    resized_frames.clone(),
+                    &self.loop_flag,
+                    &self.delay,
+                )
+            })
+            .as_ref()
+            .ok()
     }
 
     pub fn get_original_animated(&self, dir: &Directions) -> Option<&Animated> {
-        self.dirs.get(dir)?.get_original_animated()
+        let dir = self.dirs.get(dir)?;
+        if dir.original_frames.is_empty() {
+            return None;
+        }
+        dir.original_animated
+            .get_or_init(|| {
+                make_animated(
+                    dir.original_frames.clone(),
+                    &self.loop_flag,
+                    &self.delay,
+                )
+            })
+            .as_ref()
+            .ok()
     }
 }
 
+/// Frames of one direction of the state. Everything but the original frames
+/// is made on the first request, see [`ParsedState`] getters.
 #[derive(Debug, Clone, Default)]
 pub struct DirImage {
-    pub resized_frames: Option<Vec<DynamicImage>>,
     pub original_frames: Vec<DynamicImage>,
 
-    pub resized_animated: Option<Animated>,
-    pub original_animated: Option<Animated>,
+    resized_frames: OnceLock<Vec<DynamicImage>>,
+    original_animated: OnceLock<Result<Animated, String>>,
+    resized_animated: OnceLock<Result<Animated, String>>,
+
+    /// Missing frames of this direction
+    pub warnings: Vec<StateWarning>,
 }
 
 impl DirImage {
     pub fn parse_from_raw(
         state: &IconState,
-        resizing: &StateboxResizing,
         direction: Directions,
         frame_num: u32,
-        loop_flag: Looping,
-        filter_type: FilterType,
     ) -> Self {
+        let mut warnings: Vec<StateWarning> = Vec::new();
         let mut original_frames: Vec<DynamicImage> =
             Vec::with_capacity(frame_num as usize);
 
         for frame_index in 0..frame_num {
-            // I was forced to write it, because dmi crate's get_image is broken
-            // All hail stupidity
-            let frame = state.images.get(
-                direction as usize + frame_index as usize * state.dirs as usize,
-            );
-            if frame.is_none() {
-                error!(
-                    "[DMI LOADING] Failed to get frame with index {} from state {}",
-                    frame_index, state.name
-                );
+            let Some(frame) = get_state_frame(state, direction, frame_index)
+            else {
+                warnings.push(StateWarning::MissingFrames {
+                    dir: direction,
+                    found: frame_index,
+                    expected: frame_num,
+                });
                 break;
-            }
-            let frame: &DynamicImage = frame.unwrap();
+            };
             original_frames.push(frame.clone());
         }
 
-        if original_frames.is_empty() {
-            return Self::default();
-        }
-        let animated =
-            animate(original_frames.clone(), &loop_flag, &state.delay)
-                .map_err(|err| {
-                    error!(
-                        "[DMI LOADING] Failed to animate state {}: {}",
-                        state.name, err
-                    );
-                    err
-                })
-                .ok();
-        let animated = match animated {
-            Some(vec) => Animated::new(vec).ok(),
-            None => None,
-        };
-        match resizing {
-            StateboxResizing::Original => Self {
-                resized_frames: None,
-                original_frames,
-                resized_animated: None,
-                original_animated: animated,
-            },
-            StateboxResizing::Resized { height, width } => {
-                let resized_frames: Vec<DynamicImage> = original_frames
-                    .iter()
-                    .map(|frame| frame.resize(*width, *height, filter_type))
-                    .collect();
-                let resized_animated = animate(
-                    resized_frames.clone(),
-                    &loop_flag,
-                    &state.delay,
-                )
-                .map_err(|err| {
-                    error!(
-                        "[DMI LOADING] Failed to animate resized (to {}:{}) state {}: {}",
-                        height, width, state.name, err
-                    );
-                    err
-                })
-                .ok();
-                let resized_animated = match resized_animated {
-                    Some(vec) => Animated::new(vec).ok(),
-                    None => None,
-                };
-                Self {
-                    resized_frames: Some(resized_frames),
-                    original_frames,
-                    resized_animated,
-                    original_animated: animated,
-                }
-            }
-        }
-    }
-
-    pub fn resize(
-        &mut self,
-        loop_flag: Looping,
-        delay: &Option<Vec<f32>>,
-        resizing: StateboxResizing,
-        filter_type: FilterType,
-    ) {
-        match resizing {
-            StateboxResizing::Original => unreachable!(),
-            StateboxResizing::Resized { height, width } => {
-                let resized_frames: Vec<DynamicImage> = self
-                    .original_frames
-                    .iter()
-                    .map(|frame| frame.resize(width, height, filter_type))
-                    .collect();
-                let resized_animated =
-                    animate(resized_frames.clone(), &loop_flag, delay)
-                        .map_err(|err| {
-                            error!(
-                        "[DMI LOADING] Failed to animate resized (to {}:{}) DirImage: {}",
-                        height, width, err
-                    );
-                            err
-                        })
-                        .ok();
-                let resized_animated = match resized_animated {
-                    Some(vec) => Animated::new(vec).ok(),
-                    None => None,
-                };
-                self.resized_animated = resized_animated;
-                self.resized_frames = Some(resized_frames);
-            }
+        Self {
+            original_frames,
+            warnings,
+            ..Default::default()
         }
     }
 
-    pub fn get_frame(&self, frame: usize) -> Option<&DynamicImage> {
-        if let Some(frames) = &self.resized_frames {
-            frames.get(frame)
-        } else {
-            self.original_frames.get(frame)
+    /// Copy without the resized frames and animation.
+    fn without_resized(&self) -> Self {
+        Self {
+            original_frames: self.original_frames.clone(),
+            original_animated: self.original_animated.clone(),
+            warnings: self.warnings.clone(),
+            ..Default::default()
         }
     }
+}
 
-    pub fn get_original_frame(&self, frame: usize) -> Option<&DynamicImage> {
-        self.original_frames.get(frame)
-    }
+/// Problem of a single state, found while parsing it for display. The state
+/// is still shown, but some of it is missing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StateWarning {
+    /// State has no dirs or no frames
+    Empty,
+    /// Images of the direction end after `found` of `expected` frames, so the
+    /// rest are not shown
+    MissingFrames {
+        dir: Directions,
+        found: u32,
+        expected: u32,
+    },
+    /// GIF of the direction could not be made, only static frames are shown
+    AnimationFailed {
+        dir: Directions,
+        resized: bool,
+        reason: String,
+    },
+}
 
-    pub fn get_animated(&self) -> Option<&Animated> {
-        if let Some(animated) = &self.resized_animated {
-            Some(animated)
-        } else {
-            self.original_animated.as_ref()
+impl Display for StateWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "state has nothing to show"),
+            Self::MissingFrames {
+                dir,
+                found,
+                expected,
+            } => write!(
+                f,
+                "{}: only {} of {} frames have images",
+                dir, found, expected
+            ),
+            Self::AnimationFailed {
+                dir,
+                resized,
+                reason,
+            } => write!(
+                f,
+                "{}: failed to animate{}: {}",
+                dir,
+                if *resized { " resized frames" } else { "" },
+                reason
+            ),
         }
     }
+}
 
-    pub fn get_original_animated(&self) -> Option<&Animated> {
-        self.original_animated.as_ref()
+/// Finds the warnings [`ParsedState::parse_from_raw`] would report about
+/// missing frames, without decoding or animating anything.
+pub fn check_state_frames(state: &IconState) -> Vec<StateWarning> {
+    if state.dirs == 0 || state.frames == 0 {
+        return vec![StateWarning::Empty];
     }
+    (0..state.dirs)
+        .map(Directions::from)
+        .filter_map(|dir| {
+            let found = (0..state.frames)
+                .take_while(|frame| {
+                    get_state_frame(state, dir, *frame).is_some()
+                })
+                .count() as u32;
+            (found < state.frames).then_some(StateWarning::MissingFrames {
+                dir,
+                found,
+                expected: state.frames,
+            })
+        })
+        .collect()
+}
+
+/// Encodes the frames into GIF and decodes it back for displaying.
+fn make_animated(
+    frames: Vec<DynamicImage>,
+    loop_flag: &Looping,
+    delay: &Option<Vec<f32>>,
+) -> Result<Animated, String> {
+    let bytes =
+        animate(frames, loop_flag, delay).map_err(|err| err.to_string())?;
+    Animated::new(bytes).map_err(|err| err.to_string())
 }
 
 #[derive(Debug, Clone)]
@@ -387,3 +513,34 @@ impl Animated {
         Ok(Self { bytes, frames })
     }
 }
+
+/// Metadata of a single icon state without any images. Cheap to clone and
+/// to serialize.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateSummary {
+    pub name: String,
+    pub dirs: u8,
+    pub frames: u32,
+    pub delay: Option<Vec<f32>>,
+    /// How many times the animation is played, 0 means indefinitely.
+    pub loop_count: u32,
+    pub rewind: bool,
+    pub movement: bool,
+}
+
+impl From<&IconState> for StateSummary {
+    fn from(state: &IconState) -> Self {
+        Self {
+            name: state.name.clone(),
+            dirs: state.dirs,
+            frames: state.frames,
+            delay: state.delay.clone(),
+            loop_count: match state.loop_flag {
+                Looping::Indefinitely => 0,
+                Looping::NTimes(num) => num.get(),
+            },
+            rewind: state.rewind,
+            movement: state.movement,
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use image::{DynamicImage, imageops::FilterType};
+
+use crate::dmi_index::IndexEntry;
+
+/// How many similar states are listed.
+pub const SIMILAR_STATES_LIMIT: usize = 50;
+
+/// Images are shrunk to this size before hashing. There is one more column
+/// than bits in a row, because neighbours are compared.
+const HASH_WIDTH: u32 = 9;
+const HASH_HEIGHT: u32 = 8;
+
+/// State, found by [`find_similar`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarState {
+    pub path: PathBuf,
+    pub state: String,
+    /// From 0 to 1, 1 is the same picture
+    pub similarity: f32,
+}
+
+/// Difference hash of the image. Each bit tells whether a pixel of the
+/// shrunk image is brighter than its right neighbour. Transparent pixels
+/// count as black, so the silhouette matters as much as the colors.
+pub fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let shrunk = image
+        .resize_exact(HASH_WIDTH, HASH_HEIGHT, FilterType::Triangle)
+        .to_rgba8();
+    let brightness = |x: u32, y: u32| {
+        let [red, green, blue, alpha] = shrunk.get_pixel(x, y).0;
+        (red as u32 * 299 + green as u32 * 587 + blue as u32 * 114)
+            * alpha as u32
+            / 255
+    };
+
+    let mut hash: u64 = 0;
+    for y in 0..HASH_HEIGHT {
+        for x in 0..HASH_WIDTH - 1 {
+            hash <<= 1;
+            if brightness(x, y) > brightness(x + 1, y) {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Share of the equal bits of two hashes.
+pub fn similarity(first: u64, second: u64) -> f32 {
+    1.0 - (first ^ second).count_ones() as f32 / u64::BITS as f32
+}
+
+/// The most similar states of the indexed DMIs, best first. Every hashed
+/// frame of every direction of a state is compared, the closest one counts.
+pub fn find_similar<'a>(
+    hash: u64,
+    entries: impl Iterator<Item = (&'a PathBuf, &'a IndexEntry)>,
+    limit: usize,
+) -> Vec<SimilarState> {
+    let mut similar: Vec<SimilarState> = entries
+        .flat_map(|(path, entry)| {
+            entry.states.iter().filter_map(move |state| {
+                let best = state
+                    .hashes
+                    .iter()
+                    .map(|state_hash| similarity(hash, *state_hash))
+                    .max_by(f32::total_cmp)?;
+                Some(SimilarState {
+                    path: path.clone(),
+                    state: state.name.clone(),
+                    similarity: best,
+                })
+            })
+        })
+        .collect();
+    similar.sort_by(|first, second| {
+        second.similarity.total_cmp(&first.similarity)
+    });
+    similar.truncate(limit);
+    similar
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::dmi_index::{FileStamp, IndexedState};
+
+    /// Opaque gray image of the hashed size, so it is hashed as is.
+    fn image(brightness: impl Fn(u32, u32) -> u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(
+            HASH_WIDTH,
+            HASH_HEIGHT,
+            |x, y| {
+                let value = brightness(x, y);
+                Rgba([value, value, value, 255])
+            },
+        ))
+    }
+
+    /// Every row has distinct values, so no neighbours are equal.
+    fn pattern(x: u32, y: u32) -> u8 {
+        ((x * 7 + y * 13) % 9 * 28) as u8
+    }
+
+    fn hashes() -> (u64, u64, u64) {
+        let base = perceptual_hash(&image(pattern));
+        let near = perceptual_hash(&image(|x, y| {
+            if (x, y) == (4, 3) { 255 } else { pattern(x, y) }
+        }));
+        // Every neighbour comparison is reversed
+        let unrelated = perceptual_hash(&image(|x, y| 255 - pattern(x, y)));
+        (base, near, unrelated)
+    }
+
+    #[test]
+    fn near_identical_images_are_similar() {
+        let (base, near, unrelated) = hashes();
+        assert_eq!(similarity(base, base), 1.0);
+        assert!(similarity(base, near) >= 1.0 - 2.0 / 64.0);
+        assert_eq!(similarity(base, unrelated), 0.0);
+    }
+
+    #[test]
+    fn transparent_pixels_count_as_black() {
+        let transparent = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            HASH_WIDTH,
+            HASH_HEIGHT,
+            Rgba([255, 255, 255, 0]),
+        ));
+        let dot = |alpha: u8| {
+            let mut dot = transparent.to_rgba8();
+            dot.put_pixel(2, 2, Rgba([255, 255, 255, alpha]));
+            perceptual_hash(&DynamicImage::ImageRgba8(dot))
+        };
+        assert_eq!(perceptual_hash(&transparent), 0);
+        assert_eq!(dot(255), 1 << (63 - (2 * 8 + 2)));
+        assert_eq!(dot(0), 0);
+    }
+
+    #[test]
+    fn finds_closest_states_first() {
+        let (base, near, unrelated) = hashes();
+        let state = |name: &str, hashes: Vec<u64>| IndexedState {
+            name: name.to_string(),
+            dirs: 1,
+            frames: hashes.len() as u32,
+            delays: Vec::new(),
+            movement: false,
+            rewind: false,
+            hashes,
+            warnings: Vec::new(),
+        };
+        let path = PathBuf::from("a.dmi");
+        let entry = IndexEntry {
+            stamp: FileStamp {
+                size: 0,
+                modified: None,
+                hash: String::new(),
+            },
+            width: HASH_WIDTH,
+            height: HASH_HEIGHT,
+            states: vec![
+                state("far", vec![unrelated]),
+                state("unhashed", Vec::new()),
+                state("near", vec![unrelated, near]),
+            ],
+        };
+
+        let similar = find_similar(base, [(&path, &entry)].into_iter(), 10);
+        let found: Vec<&str> =
+            similar.iter().map(|state| state.state.as_str()).collect();
+        assert_eq!(found, ["near", "far"]);
+        assert_eq!(similar[0].similarity, similarity(base, near));
+
+        let similar = find_similar(base, [(&path, &entry)].into_iter(), 1);
+        assert_eq!(similar.len(), 1);
+    }
+}
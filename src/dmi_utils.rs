@@ -1,14 +1,23 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fmt::Display;
-use std::fs::File;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, Cursor};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use dmi::dirs::Dirs;
-use dmi::icon::Icon;
+use dmi::icon::{Icon, IconState};
 use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::utils::animate;
+
+/// Default template of exported file names, see [`export_dmi`].
+pub const DEFAULT_EXPORT_NAME_TEMPLATE: &str = "{state}_{dir}_{frame}.png";
+
 /// Errors, returned by DMIs parsing.
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -28,6 +37,12 @@ pub enum DMIParsingError {
     /// Other image parsing errors
     #[error(transparent)]
     ImageError(#[from] image::ImageError),
+    /// File name template misses required placeholders
+    #[error("Invalid file name template: {0}")]
+    InvalidTemplate(String),
+    /// Icon metadata does not match its images, so it can not be saved
+    #[error("Invalid DMI metadata: {0}")]
+    InvalidMetadata(String),
 }
 
 pub fn load_and_save_dmi(
@@ -49,13 +64,275 @@ pub fn load_and_save_dmi(
     Ok(())
 }
 
+/// Amount of files written by [`export_dmi`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportSummary {
+    pub images: usize,
+    pub gifs: usize,
+}
+
+/// Writes every frame of every direction of every state into `output_dir`
+/// as PNG, plus an animated GIF per direction of animated states.
+///
+/// File paths are produced from `name_template` relative to `output_dir`.
+/// The template must contain `{state}`, `{dir}` and `{frame}` and may contain
+/// `{dmi}` (name of the DMI file without extension), `{index}` (position of
+/// the state in the DMI) and `/` to put files into subfolders.
+/// For GIFs `{frame}` is replaced with `anim` and the extension with `gif`.
+/// States, whose names are the same after removing the forbidden characters
+/// (ignoring case), like movement variants, get `_2`, `_3`... suffixes.
+pub fn export_dmi<T: AsRef<Path>>(
+    icon: &Icon,
+    dmi_name: &str,
+    output_dir: T,
+    name_template: &str,
+) -> Result<ExportSummary, DMIParsingError> {
+    for placeholder in ["{state}", "{dir}", "{frame}"] {
+        if !name_template.contains(placeholder) {
+            return Err(DMIParsingError::InvalidTemplate(format!(
+                "{} is missing {}",
+                name_template, placeholder
+            )));
+        }
+    }
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+
+    let render_path =
+        |index: usize, state: &str, dir: Directions, frame: &str| {
+            output_dir.join(
+                name_template
+                    .replace("{dmi}", &sanitize_file_name(dmi_name))
+                    .replace("{index}", &index.to_string())
+                    .replace("{dir}", &dir.to_string())
+                    .replace("{frame}", frame)
+                    .replace("{state}", state),
+            )
+        };
+
+    let mut used_names: HashSet<String> = HashSet::new();
+    let mut summary = ExportSummary::default();
+    for (index, state) in icon.states.iter().enumerate() {
+        let sanitized = sanitize_file_name(&state.name);
+        let mut file_name = sanitized.clone();
+        let mut suffix = 1;
+        while !used_names.insert(file_name.to_lowercase()) {
+            suffix += 1;
+            file_name = format!("{}_{}", sanitized, suffix);
+        }
+        for dir_index in 0..state.dirs {
+            let direction: Directions = dir_index.into();
+            let mut frames: Vec<DynamicImage> =
+                Vec::with_capacity(state.frames as usize);
+            for frame in 0..state.frames {
+                let Some(image) = get_state_frame(state, direction, frame)
+                else {
+                    break;
+                };
+                let path = render_path(
+                    index,
+                    &file_name,
+                    direction,
+                    &frame.to_string(),
+                );
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                image.to_rgba8().save_with_format(path, ImageFormat::Png)?;
+                summary.images += 1;
+                frames.push(image.clone());
+            }
+            if frames.len() > 1 {
+                let animated = animate(frames, &state.loop_flag, &state.delay)?;
+                let mut gif_path =
+                    render_path(index, &file_name, direction, "anim");
+                gif_path.set_extension("gif");
+                fs::write(gif_path, animated)?;
+                summary.gifs += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Replaces characters, which are forbidden in file names on some platforms.
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|char| match char {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            char if char.is_control() => '_',
+            char => char,
+        })
+        .collect()
+}
+
 pub fn load_dmi<T: AsRef<Path>>(
     input_file: T,
 ) -> Result<Icon, DMIParsingError> {
     Ok(Icon::load(File::open(input_file)?)?)
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, Eq, PartialEq)]
+/// Checks, that the icon's metadata describes its images, so BYOND will be
+/// able to read the icon after saving.
+pub fn check_icon_metadata(icon: &Icon) -> Result<(), DMIParsingError> {
+    if icon.width == 0 || icon.height == 0 {
+        return Err(DMIParsingError::InvalidMetadata(format!(
+            "icon size is {}x{}",
+            icon.width, icon.height
+        )));
+    }
+    for state in &icon.states {
+        let invalid = |reason: String| {
+            Err(DMIParsingError::InvalidMetadata(format!(
+                "state {:?}: {}",
+                state.name, reason
+            )))
+        };
+        if ![1, 4, 8].contains(&state.dirs) {
+            return invalid(format!("{} dirs", state.dirs));
+        }
+        if state.frames == 0 {
+            return invalid("no frames".to_string());
+        }
+        let expected_images = state.dirs as usize * state.frames as usize;
+        if state.images.len() != expected_images {
+            return invalid(format!(
+                "{} images instead of {} ({} dirs, {} frames)",
+                state.images.len(),
+                expected_images,
+                state.dirs,
+                state.frames
+            ));
+        }
+        if let Some(image) = state.images.iter().find(|image| {
+            image.width() != icon.width || image.height() != icon.height
+        }) {
+            return invalid(format!(
+                "image is {}x{} instead of {}x{}",
+                image.width(),
+                image.height(),
+                icon.width,
+                icon.height
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Animated state has a delay list, which does not match its frame count.
+fn delays_mismatched(state: &IconState) -> bool {
+    state.frames > 1
+        && state
+            .delay
+            .as_ref()
+            .is_some_and(|delay| delay.len() != state.frames as usize)
+}
+
+/// Names of the states, whose delays [`fit_delays`] changes on saving.
+pub fn mismatched_delays(icon: &Icon) -> Vec<&str> {
+    icon.states
+        .iter()
+        .filter(|state| delays_mismatched(state))
+        .map(|state| state.name.as_str())
+        .collect()
+}
+
+/// Pads delay lists of animated states with their last delay (or 1 tick) and
+/// truncates them to the frame count, the icon is cloned only if it needs it.
+/// Such lists are loaded without complaint and reported by the lint, callers
+/// saving user's icons warn about them with [`mismatched_delays`].
+pub fn fit_delays(icon: &Icon) -> Cow<'_, Icon> {
+    if !icon.states.iter().any(delays_mismatched) {
+        return Cow::Borrowed(icon);
+    }
+    let mut fitted = icon.clone();
+    for state in &mut fitted.states {
+        if delays_mismatched(state)
+            && let Some(delay) = &mut state.delay
+        {
+            let last = delay.last().copied().unwrap_or(1.0);
+            delay.resize(state.frames as usize, last);
+        }
+    }
+    Cow::Owned(fitted)
+}
+
+/// Encodes the icon into PNG with the BYOND `# BEGIN DMI` description in the
+/// zTXt chunk. Delays are fitted to the frames, see [`fit_delays`]. The
+/// result is read back to make sure it is a valid DMI.
+pub fn encode_dmi(icon: &Icon) -> Result<Vec<u8>, DMIParsingError> {
+    check_icon_metadata(icon)?;
+    let icon = fit_delays(icon);
+    let mut encoded: Vec<u8> = Vec::new();
+    icon.save(&mut encoded)?;
+
+    let decoded = Icon::load(Cursor::new(&encoded))?;
+    if decoded.states.len() != icon.states.len()
+        || decoded
+            .states
+            .iter()
+            .zip(&icon.states)
+            .any(|(decoded, original)| {
+                decoded.name != original.name
+                    || decoded.dirs != original.dirs
+                    || decoded.frames != original.frames
+            })
+    {
+        return Err(DMIParsingError::InvalidMetadata(
+            "encoded DMI does not match the original icon".to_string(),
+        ));
+    }
+    Ok(encoded)
+}
+
+/// Saves the icon into the file. The icon is written into a temporary file
+/// first, so the existing file is never left half-written.
+pub fn save_dmi<T: AsRef<Path>>(
+    icon: &Icon,
+    output_file: T,
+) -> Result<(), DMIParsingError> {
+    let output_file = output_file.as_ref();
+    let encoded = encode_dmi(icon)?;
+
+    let mut temporary_file = output_file.as_os_str().to_owned();
+    temporary_file.push(".tmp");
+    fs::write(&temporary_file, encoded)?;
+    fs::rename(&temporary_file, output_file).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary_file);
+    })?;
+    Ok(())
+}
+
+/// Returns the frame of the state for the given direction. Images in
+/// the state are stored frame by frame, each frame containing all of
+/// the state's directions.
+pub fn get_state_frame(
+    state: &IconState,
+    direction: Directions,
+    frame: u32,
+) -> Option<&DynamicImage> {
+    if u8::from(direction) >= state.dirs || frame >= state.frames {
+        return None;
+    }
+    // I was forced to write it, because dmi crate's get_image is broken
+    // All hail stupidity
+    state
+        .images
+        .get(direction as usize + frame as usize * state.dirs as usize)
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    PartialOrd,
+    Ord,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+)]
 pub enum Directions {
     South = 0,
     North = 1,
@@ -83,6 +360,26 @@ impl Display for Directions {
     }
 }
 
+impl FromStr for Directions {
+    type Err = String;
+
+    /// Accepts full names (`south`, `NorthEast`) and short ones (`S`, `ne`),
+    /// case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "s" | "south" => Ok(Directions::South),
+            "n" | "north" => Ok(Directions::North),
+            "e" | "east" => Ok(Directions::East),
+            "w" | "west" => Ok(Directions::West),
+            "se" | "southeast" => Ok(Directions::SouthEast),
+            "sw" | "southwest" => Ok(Directions::SouthWest),
+            "ne" | "northeast" => Ok(Directions::NorthEast),
+            "nw" | "northwest" => Ok(Directions::NorthWest),
+            _ => Err(format!("unknown direction: {}", s)),
+        }
+    }
+}
+
 impl From<u8> for Directions {
     fn from(value: u8) -> Self {
         match value {
@@ -189,3 +486,154 @@ impl Display for CustomFilterType {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use dmi::icon::Looping;
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::dmi_model::StateSummary;
+
+    fn state(name: &str, movement: bool) -> IconState {
+        IconState {
+            name: name.to_string(),
+            dirs: 1,
+            frames: 1,
+            images: vec![DynamicImage::new_rgba8(32, 32)],
+            movement,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn export_keeps_states_with_colliding_names() {
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![
+                state("walk", false),
+                state("walk", true),
+                state("a/b", false),
+                state("a_b", false),
+                state("A_B", false),
+            ],
+            ..Default::default()
+        };
+        let output = std::env::temp_dir()
+            .join(format!("dmi_assistant_export_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&output);
+
+        let summary =
+            export_dmi(&icon, "test", &output, DEFAULT_EXPORT_NAME_TEMPLATE)
+                .unwrap();
+        let mut files: Vec<String> = fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into())
+            .collect();
+        files.sort();
+        fs::remove_dir_all(&output).unwrap();
+
+        assert_eq!(summary.images, 5);
+        assert_eq!(
+            files,
+            [
+                "A_B_3_South_0.png",
+                "a_b_2_South_0.png",
+                "a_b_South_0.png",
+                "walk_2_South_0.png",
+                "walk_South_0.png"
+            ]
+        );
+    }
+
+    /// Frames with a different pixel each, so their order is checked too.
+    fn numbered_images(count: u8) -> Vec<DynamicImage> {
+        (0..count)
+            .map(|index| {
+                let mut image = RgbaImage::new(32, 32);
+                image.put_pixel(index as u32, 0, Rgba([255, index, 0, 255]));
+                DynamicImage::ImageRgba8(image)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saved_icon_loads_unchanged() {
+        let mut animated = state("animated", false);
+        animated.dirs = 4;
+        animated.frames = 2;
+        animated.images = numbered_images(8);
+        animated.delay = Some(vec![1.0, 2.5]);
+        animated.loop_flag = Looping::NTimes(NonZeroU32::new(3).unwrap());
+        animated.rewind = true;
+        let mut walk = state("walk", true);
+        walk.images = numbered_images(1);
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![state("plain", false), animated, walk],
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join(format!(
+            "dmi_assistant_roundtrip_{}.dmi",
+            std::process::id()
+        ));
+
+        save_dmi(&icon, &path).unwrap();
+        let loaded = load_dmi(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!((loaded.width, loaded.height), (32, 32));
+        let summaries = |icon: &Icon| -> Vec<StateSummary> {
+            icon.states.iter().map(StateSummary::from).collect()
+        };
+        assert_eq!(summaries(&loaded), summaries(&icon));
+        for (loaded, original) in loaded.states.iter().zip(&icon.states) {
+            let pixels = |state: &IconState| -> Vec<Vec<u8>> {
+                state
+                    .images
+                    .iter()
+                    .map(|image| image.to_rgba8().into_raw())
+                    .collect()
+            };
+            assert_eq!(pixels(loaded), pixels(original), "{}", original.name);
+        }
+    }
+
+    #[test]
+    fn fits_mismatched_delays() {
+        let mut short = state("short", false);
+        short.frames = 3;
+        short.images = numbered_images(3);
+        short.delay = Some(vec![1.0, 2.0]);
+        let mut long = short.clone();
+        long.name = "long".to_string();
+        long.delay = Some(vec![1.0, 2.0, 3.0, 4.0]);
+        let mut fine = short.clone();
+        fine.name = "fine".to_string();
+        fine.delay = Some(vec![1.0, 2.0, 3.0]);
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![short, long, fine],
+            ..Default::default()
+        };
+
+        assert_eq!(mismatched_delays(&icon), ["short", "long"]);
+        let fitted = fit_delays(&icon);
+        let delays: Vec<&[f32]> = fitted
+            .states
+            .iter()
+            .map(|state| state.delay.as_deref().unwrap())
+            .collect();
+        assert_eq!(delays, [[1.0, 2.0, 2.0], [1.0, 2.0, 3.0], [1.0, 2.0, 3.0]]);
+
+        let fitted = fitted.into_owned();
+        assert!(mismatched_delays(&fitted).is_empty());
+        assert!(matches!(fit_delays(&fitted), Cow::Borrowed(_)));
+    }
+}
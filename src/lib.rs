@@ -9,15 +9,34 @@ use iced_aw::{Tabs, tab_bar};
 use iced_toasts::{Toast, ToastContainer, ToastId, toast_container};
 use log::error;
 
+pub mod cli;
 pub mod config;
+pub mod dm_source;
+pub mod dm_usage;
+pub mod dmi_diff;
+pub mod dmi_duplicates;
+pub mod dmi_edit;
+pub mod dmi_git;
+pub mod dmi_import;
+pub mod dmi_index;
+pub mod dmi_lint;
+pub mod dmi_merge;
 pub mod dmi_model;
+pub mod dmi_similar;
 pub mod dmi_utils;
+pub mod query;
 pub mod screens;
+pub mod search;
 pub mod utils;
+pub mod watcher;
 
 use crate::config::Config;
+use crate::dmi_index::ExplorerIndex;
 use crate::screens::Screen;
+use crate::screens::diff::{DiffMessage, DiffScreen};
 use crate::screens::explorer::{ExplorerMessage, ExplorerScreen};
+use crate::screens::importer::{ImporterMessage, ImporterScreen};
+use crate::screens::merge::{MergeMessage, MergeScreen};
 use crate::screens::viewer::DisplaySettings;
 use screens::Screens;
 use screens::viewer::{ViewerMessage, ViewerScreen};
@@ -40,6 +59,9 @@ pub enum Message {
 
     ViewerMessage(ViewerMessage),
     ExplorerMessage(ExplorerMessage),
+    ImporterMessage(ImporterMessage),
+    DiffMessage(DiffMessage),
+    MergeMessage(MergeMessage),
 }
 
 pub struct DMIAssistant<'a> {
@@ -49,6 +71,9 @@ pub struct DMIAssistant<'a> {
 
     pub viewer_screen: ViewerScreen,
     pub explorer_screen: ExplorerScreen,
+    pub importer_screen: ImporterScreen,
+    pub diff_screen: DiffScreen,
+    pub merge_screen: MergeScreen,
 
     pub theme: Theme,
     pub toasts: ToastContainer<'a, Message>,
@@ -68,16 +93,19 @@ impl DMIAssistant<'_> {
         viewer_screen.display_settings.statebox_default =
             config.statebox_defaults.clone().into();
 
-        let explorer_screen = ExplorerScreen {
-            settings: config.explorer_settings.clone(),
-            ..Default::default()
-        };
+        let explorer_screen = ExplorerScreen::new(
+            config.explorer_settings.clone(),
+            ExplorerIndex::load(&config.data_dir),
+        );
 
         Self {
             config,
             current_screen: Default::default(),
             viewer_screen,
             explorer_screen,
+            importer_screen: Default::default(),
+            diff_screen: Default::default(),
+            merge_screen: Default::default(),
             theme: Default::default(),
             toasts: toast_container(Message::DismissToast),
             clipboard: Clipboard::new()
@@ -97,12 +125,18 @@ impl DMIAssistant<'_> {
                 _ => match self.current_screen {
                     Screens::Explorer => ExplorerScreen::update(self, message),
                     Screens::Viewer => ViewerScreen::update(self, message),
+                    Screens::Importer => ImporterScreen::update(self, message),
+                    Screens::Diff => DiffScreen::update(self, message),
+                    Screens::Merge => MergeScreen::update(self, message),
                 },
             },
 
             Message::Keyboard(_, _) => match self.current_screen {
                 Screens::Explorer => ExplorerScreen::update(self, message),
                 Screens::Viewer => ViewerScreen::update(self, message),
+                Screens::Importer => ImporterScreen::update(self, message),
+                Screens::Diff => DiffScreen::update(self, message),
+                Screens::Merge => MergeScreen::update(self, message),
             },
             Message::PushToast(boxed_toast) => {
                 self.toasts.push(boxed_toast.as_ref().clone());
@@ -123,6 +157,16 @@ impl DMIAssistant<'_> {
                 self,
                 Message::ExplorerMessage(msg.clone()),
             ),
+            Message::ImporterMessage(msg) => ImporterScreen::update(
+                self,
+                Message::ImporterMessage(msg.clone()),
+            ),
+            Message::DiffMessage(msg) => {
+                DiffScreen::update(self, Message::DiffMessage(msg.clone()))
+            }
+            Message::MergeMessage(msg) => {
+                MergeScreen::update(self, Message::MergeMessage(msg.clone()))
+            }
         }
     }
 
@@ -141,6 +185,21 @@ impl DMIAssistant<'_> {
                         self.viewer_screen.label(),
                         ViewerScreen::view(self),
                     )
+                    .push(
+                        Screens::Importer,
+                        self.importer_screen.label(),
+                        ImporterScreen::view(self),
+                    )
+                    .push(
+                        Screens::Diff,
+                        self.diff_screen.label(),
+                        DiffScreen::view(self),
+                    )
+                    .push(
+                        Screens::Merge,
+                        self.merge_screen.label(),
+                        MergeScreen::view(self),
+                    )
                     .set_active_tab(&self.current_screen)
                     .tab_label_spacing(20)
                     .tab_bar_height(Length::Shrink)
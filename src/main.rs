@@ -4,8 +4,16 @@ use std::{env, fs, panic, path::Path};
 
 use chrono::Local;
 use dmi_assistant::{
-    DEFAULT_THEME, DMIAssistant, Message, config::Config, icon::FONT,
+    DEFAULT_THEME, DMIAssistant, Message,
+    config::Config,
+    icon::FONT,
+    screens::{
+        Screens,
+        explorer::ExplorerMessage,
+        merge::{MergeFile, MergeMessage},
+    },
     utils::prepare_dirs,
+    wrap,
 };
 use dotenv::dotenv;
 use iced::{
@@ -41,6 +49,14 @@ pub fn main() -> iced::Result {
 
     info!("Config is: {:?}", &config.log_dir);
 
+    // The index is shown right away, changed files are loaded again and the
+    // project code is scanned for the icon references
+    let startup_task = Task::batch([
+        Task::done(wrap![ExplorerMessage::RefreshIndex]),
+        Task::done(wrap![ExplorerMessage::RescanProject]),
+        merge_tool_args().unwrap_or_else(Task::none),
+    ]);
+
     iced::application("DMI assistant", DMIAssistant::update, DMIAssistant::view)
         .theme(|_| DEFAULT_THEME)
         .subscription(subscription)
@@ -64,11 +80,44 @@ pub fn main() -> iced::Result {
         })
         .font(FONT)
         .font(iced_fonts::NERD_FONT_BYTES)
-        .run_with(|| (DMIAssistant::new(config), Task::none()))
+        .run_with(|| (DMIAssistant::new(config), startup_task))
+}
+
+/// `--merge BASE OURS THEIRS [MERGED]` opens the Merge screen, so the app can
+/// be used as a git mergetool.
+fn merge_tool_args() -> Option<Task<Message>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("--merge") {
+        return None;
+    }
+    let paths = &args[1..];
+    if !(3..=4).contains(&paths.len()) {
+        error!(
+            "--merge expects BASE OURS THEIRS [MERGED] paths, got {:?}",
+            paths
+        );
+        return None;
+    }
+
+    let files = [
+        MergeFile::Base,
+        MergeFile::Ours,
+        MergeFile::Theirs,
+        MergeFile::Output,
+    ];
+    let set_paths = files.into_iter().zip(paths).map(|(file, path)| {
+        Task::done(wrap![MergeMessage::ChangePath(file, path.clone())])
+    });
+    Some(Task::batch([
+        Task::done(Message::ChangeScreen(Screens::Merge)),
+        Task::batch(set_paths).chain(Task::done(wrap![MergeMessage::LoadDMIs])),
+    ]))
 }
 
-fn subscription(_state: &DMIAssistant) -> Subscription<Message> {
+fn subscription(state: &DMIAssistant) -> Subscription<Message> {
     Subscription::batch(vec![
+        state.viewer_screen.subscription(),
+        state.explorer_screen.subscription(),
         keyboard::on_key_press(|key, modifiers| {
             Some(Message::Keyboard(key, modifiers))
         }),
@@ -0,0 +1,590 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::{
+    dmi_index::{IndexEntry, IndexedState},
+    search::{Match, MatchMode, Matcher, SearchError},
+};
+
+/// Fields, which can be used in the queries, for error messages.
+const KNOWN_FIELDS: &str = "path, state, dirs, frames, movement, rewind, \
+                            warnings, size, width, height, states";
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error(
+        "Unknown field \"{0}\", known fields are: {known}",
+        known = KNOWN_FIELDS
+    )]
+    UnknownField(String),
+    #[error("\"{field}\" expects a number, got \"{value}\"")]
+    InvalidNumber { field: String, value: String },
+    #[error("\"{field}\" expects true or false, got \"{value}\"")]
+    InvalidFlag { field: String, value: String },
+    #[error("\"size\" expects WIDTHxHEIGHT or a single number, got \"{0}\"")]
+    InvalidSize(String),
+    #[error("\"{0}\" can only be compared with \":\"")]
+    InvalidComparison(String),
+    #[error("\"{0}\" has no value")]
+    MissingValue(String),
+    #[error("Quote is not closed")]
+    UnclosedQuote,
+    #[error(transparent)]
+    Pattern(#[from] SearchError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn test(self, left: u32, right: u32) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DMINumber {
+    Width,
+    Height,
+    /// Count of the states
+    States,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateNumber {
+    Dirs,
+    Frames,
+    Warnings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateFlag {
+    Movement,
+    Rewind,
+}
+
+/// Condition on the whole DMI.
+#[derive(Debug, Clone)]
+enum DMIPredicate {
+    Path(Matcher),
+    Number(DMINumber, Comparison, u32),
+    Size(u32, u32),
+}
+
+/// Condition on a single state.
+#[derive(Debug, Clone)]
+enum StatePredicate {
+    State(Matcher),
+    /// Bare text, matches either the state name or the DMI path
+    Text(Matcher),
+    Number(StateNumber, Comparison, u32),
+    Flag(StateFlag, bool),
+}
+
+/// Parsed Explorer query, like
+/// `path:clothing state:worn* dirs:4 frames>1 -state:broken`.
+///
+/// Terms are separated by spaces and must all hold, `-` negates a term.
+/// Values can be quoted. Text values with `*` or `?` are matched as globs,
+/// the rest by the selected [`MatchMode`]. Bare text matches either the
+/// state name or the DMI path.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    dmi_terms: Vec<(bool, DMIPredicate)>,
+    /// Negation flag and the predicate
+    state_terms: Vec<(bool, StatePredicate)>,
+}
+
+/// DMI, which satisfies the query.
+#[derive(Debug, Clone, Default)]
+pub struct QueryMatch {
+    pub score: i64,
+    /// Characters of the path, found by `path:` terms
+    pub path: Match,
+    /// Indices of the found states in [`IndexEntry::states`] with the
+    /// characters, found by `state:` and bare terms
+    pub states: Vec<(usize, Match)>,
+}
+
+impl Query {
+    pub fn parse(query: &str, mode: MatchMode) -> Result<Self, QueryError> {
+        let mut parsed = Self::default();
+        for token in tokenize(query)? {
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+                _ => (false, token),
+            };
+            let Some((field, comparison, value)) = split_term(&token) else {
+                parsed.state_terms.push((
+                    negated,
+                    StatePredicate::Text(text_matcher(&token, mode)?),
+                ));
+                continue;
+            };
+            if value.is_empty() {
+                return Err(QueryError::MissingValue(field));
+            }
+
+            let number = || {
+                value.parse::<u32>().map_err(|_| QueryError::InvalidNumber {
+                    field: field.clone(),
+                    value: value.clone(),
+                })
+            };
+            let only_equal = || {
+                if comparison == Comparison::Equal {
+                    Ok(())
+                } else {
+                    Err(QueryError::InvalidComparison(field.clone()))
+                }
+            };
+            match field.as_str() {
+                "path" => {
+                    only_equal()?;
+                    parsed.dmi_terms.push((
+                        negated,
+                        DMIPredicate::Path(text_matcher(&value, mode)?),
+                    ));
+                }
+                "state" => {
+                    only_equal()?;
+                    parsed.state_terms.push((
+                        negated,
+                        StatePredicate::State(text_matcher(&value, mode)?),
+                    ));
+                }
+                "size" => {
+                    only_equal()?;
+                    let size = match value.split_once(['x', 'X']) {
+                        Some((width, height)) => {
+                            width.parse::<u32>().and_then(|width| {
+                                Ok((width, height.parse::<u32>()?))
+                            })
+                        }
+                        None => value.parse::<u32>().map(|size| (size, size)),
+                    }
+                    .map_err(|_| QueryError::InvalidSize(value.clone()))?;
+                    parsed
+                        .dmi_terms
+                        .push((negated, DMIPredicate::Size(size.0, size.1)));
+                }
+                "width" | "height" | "states" => {
+                    let field = match field.as_str() {
+                        "width" => DMINumber::Width,
+                        "height" => DMINumber::Height,
+                        _ => DMINumber::States,
+                    };
+                    parsed.dmi_terms.push((
+                        negated,
+                        DMIPredicate::Number(field, comparison, number()?),
+                    ));
+                }
+                "dirs" | "frames" | "warnings" => {
+                    let field = match field.as_str() {
+                        "dirs" => StateNumber::Dirs,
+                        "frames" => StateNumber::Frames,
+                        _ => StateNumber::Warnings,
+                    };
+                    parsed.state_terms.push((
+                        negated,
+                        StatePredicate::Number(field, comparison, number()?),
+                    ));
+                }
+                "movement" | "rewind" => {
+                    only_equal()?;
+                    let flag = match value.to_lowercase().as_str() {
+                        "true" | "yes" | "1" => true,
+                        "false" | "no" | "0" => false,
+                        _ => {
+                            return Err(QueryError::InvalidFlag {
+                                field,
+                                value,
+                            });
+                        }
+                    };
+                    let field = if field == "movement" {
+                        StateFlag::Movement
+                    } else {
+                        StateFlag::Rewind
+                    };
+                    parsed
+                        .state_terms
+                        .push((negated, StatePredicate::Flag(field, flag)));
+                }
+                _ => return Err(QueryError::UnknownField(field)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dmi_terms.is_empty() && self.state_terms.is_empty()
+    }
+
+    /// Checks the indexed DMI, returns None if it does not satisfy the query.
+    /// If the query has no state terms, every state is found.
+    pub fn find(&self, path: &Path, entry: &IndexEntry) -> Option<QueryMatch> {
+        let path = path.to_string_lossy();
+        let mut path_found = Match::default();
+        for (negated, predicate) in &self.dmi_terms {
+            let holds = match predicate {
+                DMIPredicate::Path(matcher) => match matcher.find(&path) {
+                    Some(found) if !negated => {
+                        merge_match(&mut path_found, found);
+                        true
+                    }
+                    found => found.is_some(),
+                },
+                DMIPredicate::Number(field, comparison, number) => {
+                    let value = match field {
+                        DMINumber::Width => entry.width,
+                        DMINumber::Height => entry.height,
+                        DMINumber::States => entry.states.len() as u32,
+                    };
+                    comparison.test(value, *number)
+                }
+                DMIPredicate::Size(width, height) => {
+                    entry.width == *width && entry.height == *height
+                }
+            };
+            if holds == *negated {
+                return None;
+            }
+        }
+
+        let states: Vec<(usize, Match)> = entry
+            .states
+            .iter()
+            .enumerate()
+            .filter_map(|(index, state)| {
+                self.find_state(&path, state).map(|found| (index, found))
+            })
+            .collect();
+        if states.is_empty() && !self.state_terms.is_empty() {
+            return None;
+        }
+        let best_state_score =
+            states.iter().map(|(_, found)| found.score).max();
+        Some(QueryMatch {
+            score: path_found.score + best_state_score.unwrap_or_default(),
+            path: path_found,
+            states,
+        })
+    }
+
+    fn find_state(&self, path: &str, state: &IndexedState) -> Option<Match> {
+        let mut state_found = Match::default();
+        for (negated, predicate) in &self.state_terms {
+            let holds = match predicate {
+                StatePredicate::State(matcher) => {
+                    match matcher.find(&state.name) {
+                        Some(found) if !negated => {
+                            merge_match(&mut state_found, found);
+                            true
+                        }
+                        found => found.is_some(),
+                    }
+                }
+                StatePredicate::Text(matcher) => {
+                    match matcher.find(&state.name) {
+                        Some(found) if !negated => {
+                            merge_match(&mut state_found, found);
+                            true
+                        }
+                        Some(_) => true,
+                        None => matcher.find(path).is_some(),
+                    }
+                }
+                StatePredicate::Number(field, comparison, number) => {
+                    let value = match field {
+                        StateNumber::Dirs => state.dirs as u32,
+                        StateNumber::Frames => state.frames,
+                        StateNumber::Warnings => state.warnings.len() as u32,
+                    };
+                    comparison.test(value, *number)
+                }
+                StatePredicate::Flag(field, flag) => {
+                    let value = match field {
+                        StateFlag::Movement => state.movement,
+                        StateFlag::Rewind => state.rewind,
+                    };
+                    value == *flag
+                }
+            };
+            if holds == *negated {
+                return None;
+            }
+        }
+        Some(state_found)
+    }
+}
+
+fn merge_match(into: &mut Match, found: Match) {
+    into.score += found.score;
+    into.positions.extend(found.positions);
+}
+
+fn text_matcher(value: &str, mode: MatchMode) -> Result<Matcher, SearchError> {
+    if value.contains(['*', '?']) {
+        Matcher::new(value, MatchMode::Glob)
+    } else {
+        Matcher::new(value, mode)
+    }
+}
+
+/// Splits the query by spaces, keeping the quoted parts together.
+fn tokenize(query: &str) -> Result<Vec<String>, QueryError> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for character in query.chars() {
+        match character {
+            '"' => quoted = !quoted,
+            _ if character.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(character),
+        }
+    }
+    if quoted {
+        return Err(QueryError::UnclosedQuote);
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Splits `field:value`, `field>value` and the like. Returns None for the
+/// bare text.
+fn split_term(token: &str) -> Option<(String, Comparison, String)> {
+    let operator_start = token.find([':', '<', '>', '='])?;
+    let field = &token[..operator_start];
+    if field.is_empty()
+        || !field
+            .chars()
+            .all(|character| character.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    let rest = &token[operator_start..];
+    let (comparison, length) = if rest.starts_with("<=") {
+        (Comparison::LessOrEqual, 2)
+    } else if rest.starts_with(">=") {
+        (Comparison::GreaterOrEqual, 2)
+    } else if rest.starts_with('<') {
+        (Comparison::Less, 1)
+    } else if rest.starts_with('>') {
+        (Comparison::Greater, 1)
+    } else {
+        (Comparison::Equal, 1)
+    };
+    Some((field.to_lowercase(), comparison, rest[length..].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmi_index::FileStamp;
+
+    use super::*;
+
+    fn indexed_state(
+        name: &str,
+        dirs: u8,
+        frames: u32,
+        movement: bool,
+    ) -> IndexedState {
+        IndexedState {
+            name: name.to_string(),
+            dirs,
+            frames,
+            delays: vec![1.0; frames as usize],
+            movement,
+            rewind: false,
+            hashes: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn entry(width: u32, height: u32, states: Vec<IndexedState>) -> IndexEntry {
+        IndexEntry {
+            stamp: FileStamp {
+                size: 0,
+                modified: None,
+                hash: String::new(),
+            },
+            width,
+            height,
+            states,
+        }
+    }
+
+    /// Indices of the states found by the query, None if the DMI is not.
+    fn found(
+        query: &str,
+        path: &str,
+        entry: &IndexEntry,
+    ) -> Option<Vec<usize>> {
+        let query = Query::parse(query, MatchMode::Substring).unwrap();
+        query.find(Path::new(path), entry).map(|found| {
+            found.states.into_iter().map(|(index, _)| index).collect()
+        })
+    }
+
+    #[test]
+    fn tokenize_keeps_quoted_parts() {
+        assert_eq!(
+            tokenize(r#"  state:"worn jacket"  "a b"c  path:x "#).unwrap(),
+            ["state:worn jacket", "a bc", "path:x"]
+        );
+        assert!(tokenize("").unwrap().is_empty());
+        assert!(matches!(
+            tokenize(r#"state:"worn jacket"#),
+            Err(QueryError::UnclosedQuote)
+        ));
+    }
+
+    #[test]
+    fn split_term_reads_comparisons() {
+        let term = |field: &str, comparison, value: &str| {
+            Some((field.to_string(), comparison, value.to_string()))
+        };
+        assert_eq!(
+            split_term("frames<=2"),
+            term("frames", Comparison::LessOrEqual, "2")
+        );
+        assert_eq!(
+            split_term("frames>=2"),
+            term("frames", Comparison::GreaterOrEqual, "2")
+        );
+        assert_eq!(
+            split_term("frames<2"),
+            term("frames", Comparison::Less, "2")
+        );
+        assert_eq!(
+            split_term("frames>2"),
+            term("frames", Comparison::Greater, "2")
+        );
+        assert_eq!(split_term("Dirs:4"), term("dirs", Comparison::Equal, "4"));
+        assert_eq!(split_term("dirs="), term("dirs", Comparison::Equal, ""));
+        assert_eq!(split_term("worn"), None);
+        assert_eq!(split_term(":worn"), None);
+        assert_eq!(split_term("obj/x:y"), None);
+    }
+
+    #[test]
+    fn compares_numbers() {
+        let entry = entry(
+            32,
+            32,
+            vec![
+                indexed_state("one", 1, 1, false),
+                indexed_state("two", 1, 2, false),
+                indexed_state("three", 1, 3, false),
+            ],
+        );
+        assert_eq!(found("frames<=2", "a.dmi", &entry), Some(vec![0, 1]));
+        assert_eq!(found("frames>=2", "a.dmi", &entry), Some(vec![1, 2]));
+        assert_eq!(found("frames<2", "a.dmi", &entry), Some(vec![0]));
+        assert_eq!(found("frames>2", "a.dmi", &entry), Some(vec![2]));
+        assert_eq!(found("frames>3", "a.dmi", &entry), None);
+        assert!(matches!(
+            Query::parse("frames>many", MatchMode::Substring),
+            Err(QueryError::InvalidNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn matches_sizes() {
+        let square = entry(32, 32, Vec::new());
+        let wide = entry(64, 32, Vec::new());
+        assert!(found("size:32x32", "a.dmi", &square).is_some());
+        assert!(found("size:32", "a.dmi", &square).is_some());
+        assert!(found("size:64X32", "a.dmi", &wide).is_some());
+        assert!(found("size:32", "a.dmi", &wide).is_none());
+        assert!(found("-size:32", "a.dmi", &wide).is_some());
+        assert!(matches!(
+            Query::parse("size:32x", MatchMode::Substring),
+            Err(QueryError::InvalidSize(_))
+        ));
+        assert!(matches!(
+            Query::parse("size>32", MatchMode::Substring),
+            Err(QueryError::InvalidComparison(_))
+        ));
+    }
+
+    #[test]
+    fn negates_terms() {
+        let entry = entry(
+            32,
+            32,
+            vec![
+                indexed_state("walk", 4, 1, true),
+                indexed_state("stand", 4, 1, false),
+            ],
+        );
+        assert_eq!(found("-walk", "mob.dmi", &entry), Some(vec![1]));
+        assert_eq!(found("-movement:true", "mob.dmi", &entry), Some(vec![1]));
+        assert_eq!(found("-path:mob", "mob.dmi", &entry), None);
+        assert_eq!(found("-path:obj", "mob.dmi", &entry), Some(vec![0, 1]));
+        // A lone dash is text, not an empty negated term
+        assert_eq!(found("-", "mob.dmi", &entry), None);
+    }
+
+    #[test]
+    fn finds_documented_example() {
+        let suit = entry(
+            32,
+            32,
+            vec![
+                indexed_state("worn_jacket", 4, 2, true),
+                indexed_state("worn_broken", 4, 2, true),
+                indexed_state("worn_still", 4, 1, true),
+                indexed_state("worn_static", 4, 2, false),
+                indexed_state("worn_single", 1, 2, true),
+                indexed_state("jacket", 4, 2, true),
+            ],
+        );
+        let query = "path:clothing state:worn* dirs:4 frames>1 movement:true \
+                     size:32x32 -state:broken";
+        assert_eq!(
+            found(query, "icons/obj/clothing/suit.dmi", &suit),
+            Some(vec![0])
+        );
+        assert_eq!(found(query, "icons/obj/suit.dmi", &suit), None);
+        let big = entry(64, 64, suit.states.clone());
+        assert_eq!(found(query, "icons/obj/clothing/suit.dmi", &big), None);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_missing_values() {
+        assert!(matches!(
+            Query::parse("colour:red", MatchMode::Substring),
+            Err(QueryError::UnknownField(field)) if field == "colour"
+        ));
+        assert!(matches!(
+            Query::parse("state:", MatchMode::Substring),
+            Err(QueryError::MissingValue(field)) if field == "state"
+        ));
+        assert!(matches!(
+            Query::parse("movement:maybe", MatchMode::Substring),
+            Err(QueryError::InvalidFlag { .. })
+        ));
+    }
+}
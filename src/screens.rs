@@ -3,7 +3,10 @@ use iced_aw::TabLabel;
 
 use crate::{DMIAssistant, Message};
 
+pub mod diff;
 pub mod explorer;
+pub mod importer;
+pub mod merge;
 pub mod viewer;
 
 /// Wrapping a screen's Message into the app's Message. Screen's message enum and variant in app's message enum must have the
@@ -31,6 +34,9 @@ pub enum Screens {
     #[default]
     Explorer,
     Viewer,
+    Importer,
+    Diff,
+    Merge,
 }
 
 pub trait Screen {
@@ -0,0 +1,492 @@
+use std::time::Instant;
+
+use iced::{
+    Element, Length, Task,
+    alignment::Vertical,
+    color,
+    widget::{
+        Column, Image, Space, button, column, container,
+        image::{FilterMethod, Handle},
+        row, scrollable, text, text_input,
+    },
+};
+use iced_aw::{NumberInput, TabLabel};
+use iced_toasts::ToastLevel;
+use image::DynamicImage;
+use log::{debug, error};
+use rfd::FileDialog;
+
+use crate::{
+    DMIAssistant, Message,
+    dmi_diff::{DMIDiff, FrameDiff, StateChangeKind, StateDiff, diff_icons},
+    dmi_git::{DEFAULT_REVISION, load_dmi_from_revision},
+    dmi_utils::load_dmi,
+    icon,
+    screens::Screen,
+    utils::{bold_text, popup},
+    wrap,
+};
+
+const DEFAULT_ZOOM: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffSide {
+    Old,
+    New,
+}
+
+#[derive(Debug, Clone)]
+pub enum DiffMessage {
+    ChangePath(DiffSide, String),
+    ChangeOldRevision(String),
+    OpenedFileExplorer(DiffSide),
+    SwapSides,
+
+    Compare,
+    Compared(Result<Comparison, String>),
+    Clear,
+
+    ChangeZoom(u32),
+}
+
+/// Finished comparison of two DMIs.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub diff: DMIDiff,
+    pub frame_images: Vec<Vec<FrameImages>>,
+    /// Commit the old DMI was read from
+    pub commit: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffScreen {
+    pub old_path: String,
+    pub new_path: String,
+    /// If set, the old DMI is read from this git revision of `old_path`
+    pub old_revision: String,
+    pub comparing: bool,
+    pub diff: Option<DMIDiff>,
+    /// Images of the `diff` frames by state and frame, made once, so redraws
+    /// do not convert them again
+    pub frame_images: Vec<Vec<FrameImages>>,
+    /// Commit the old DMI of the current diff was read from
+    pub compared_commit: Option<String>,
+    /// Scale of the displayed frames
+    pub zoom: u32,
+}
+
+impl Default for DiffScreen {
+    fn default() -> Self {
+        Self {
+            old_path: String::new(),
+            new_path: String::new(),
+            old_revision: String::new(),
+            comparing: false,
+            diff: None,
+            frame_images: Vec::new(),
+            compared_commit: None,
+            zoom: DEFAULT_ZOOM,
+        }
+    }
+}
+
+/// Frame converted for displaying.
+#[derive(Debug, Clone)]
+pub struct FrameImage {
+    pub handle: Handle,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FrameImage {
+    pub fn new(image: &DynamicImage) -> Self {
+        Self {
+            handle: Handle::from_rgba(
+                image.width(),
+                image.height(),
+                image.to_rgba8().into_raw(),
+            ),
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+}
+
+/// Old, new and overlay images of a [`FrameDiff`].
+#[derive(Debug, Clone)]
+pub struct FrameImages {
+    pub old: Option<FrameImage>,
+    pub new: Option<FrameImage>,
+    pub overlay: FrameImage,
+}
+
+impl From<&FrameDiff> for FrameImages {
+    fn from(frame: &FrameDiff) -> Self {
+        Self {
+            old: frame.old.as_ref().map(FrameImage::new),
+            new: frame.new.as_ref().map(FrameImage::new),
+            overlay: FrameImage::new(&frame.overlay),
+        }
+    }
+}
+
+/// Converts the frames of every state in the diff.
+fn frame_images(diff: &DMIDiff) -> Vec<Vec<FrameImages>> {
+    diff.states
+        .iter()
+        .map(|state| state.frames.iter().map(FrameImages::from).collect())
+        .collect()
+}
+
+impl DiffScreen {
+    fn frame_image<'a>(
+        &self,
+        image: Option<&FrameImage>,
+    ) -> Element<'a, Message> {
+        match image {
+            Some(image) => Image::new(image.handle.clone())
+                .filter_method(FilterMethod::Nearest)
+                .width((image.width * self.zoom) as f32)
+                .height((image.height * self.zoom) as f32)
+                .into(),
+            None => text("-").into(),
+        }
+    }
+
+    fn display_frame<'a>(
+        &self,
+        frame: &FrameDiff,
+        images: &FrameImages,
+    ) -> Element<'a, Message> {
+        row![
+            column![
+                text!("{} #{}", frame.dir, frame.frame),
+                text!("{} px changed", frame.changed_pixels).size(12)
+            ]
+            .width(120),
+            self.frame_image(images.old.as_ref()),
+            self.frame_image(images.new.as_ref()),
+            self.frame_image(Some(&images.overlay)),
+        ]
+        .spacing(20)
+        .align_y(Vertical::Center)
+        .into()
+    }
+
+    fn display_state<'a>(
+        &self,
+        state: &'a StateDiff,
+        images: &[FrameImages],
+    ) -> Element<'a, Message> {
+        let kind_color = match state.kind {
+            StateChangeKind::Added => color!(0x89fc41),
+            StateChangeKind::Removed => color!(0xfc4144),
+            StateChangeKind::Renamed => color!(0x41b9fc),
+            StateChangeKind::Modified => color!(0xcde002),
+        };
+        let title = match state.kind {
+            StateChangeKind::Renamed => format!(
+                "{} -> {}",
+                state.old_name.as_deref().unwrap_or_default(),
+                state.new_name.as_deref().unwrap_or_default()
+            ),
+            _ => state.name().to_string(),
+        };
+        let title = if state.movement {
+            format!("{} (movement)", title)
+        } else {
+            title
+        };
+
+        let metadata: Column<Message> = state
+            .metadata_changes
+            .iter()
+            .map(|change| text(change).into())
+            .collect();
+        let frames: Column<Message> = state
+            .frames
+            .iter()
+            .zip(images)
+            .map(|(frame, images)| self.display_frame(frame, images))
+            .collect();
+
+        container(
+            column![
+                row![
+                    text(state.kind.to_string()).color(kind_color),
+                    bold_text(title)
+                ]
+                .spacing(10),
+                metadata.spacing(5),
+                frames.spacing(10),
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+impl Screen for DiffScreen {
+    fn label(&self) -> TabLabel {
+        TabLabel::IconText('\u{1F504}', " Diff".to_string())
+    }
+
+    fn update(app: &mut DMIAssistant, message: Message) -> Task<Message> {
+        let screen = &mut app.diff_screen;
+        let Message::DiffMessage(diff_message) = message else {
+            return Task::none();
+        };
+        match diff_message {
+            DiffMessage::ChangePath(side, path) => {
+                match side {
+                    DiffSide::Old => screen.old_path = path,
+                    DiffSide::New => screen.new_path = path,
+                }
+                Task::none()
+            }
+            DiffMessage::ChangeOldRevision(revision) => {
+                screen.old_revision = revision;
+                Task::none()
+            }
+            DiffMessage::OpenedFileExplorer(side) => {
+                let title = match side {
+                    DiffSide::Old => "Open the old DMI",
+                    DiffSide::New => "Open the new DMI",
+                };
+                match FileDialog::new()
+                    .set_title(title)
+                    .set_directory("/")
+                    .add_filter("dmi", &["dmi"])
+                    .pick_file()
+                {
+                    Some(path) => Task::done(wrap![DiffMessage::ChangePath(
+                        side,
+                        path.to_string_lossy().into()
+                    )]),
+                    None => Task::none(),
+                }
+            }
+            DiffMessage::SwapSides => {
+                std::mem::swap(&mut screen.old_path, &mut screen.new_path);
+                Task::none()
+            }
+            DiffMessage::Compare => {
+                screen.comparing = true;
+                let old_path = screen.old_path.clone();
+                let old_revision = screen.old_revision.trim().to_string();
+                let new_path = screen.new_path.clone();
+                Task::future(async move {
+                    let compare_start = Instant::now();
+                    let load = |path: &str| {
+                        load_dmi(path)
+                            .map_err(|err| format!("{}: {}", path, err))
+                    };
+                    let old = if old_revision.is_empty() {
+                        load(&old_path).map(|icon| (icon, None))
+                    } else {
+                        load_dmi_from_revision(&old_path, &old_revision)
+                            .map(|loaded| (loaded.icon, Some(loaded.commit)))
+                            .map_err(|err| {
+                                format!(
+                                    "{} at {}: {}",
+                                    old_path, old_revision, err
+                                )
+                            })
+                    };
+                    let compared = old.and_then(|(old, commit)| {
+                        load(&new_path).map(|new| {
+                            let diff = diff_icons(old, new);
+                            Comparison {
+                                frame_images: frame_images(&diff),
+                                diff,
+                                commit,
+                            }
+                        })
+                    });
+                    debug!(
+                        "DMIs {} and {} compared in {}ms",
+                        old_path,
+                        new_path,
+                        compare_start.elapsed().as_millis()
+                    );
+                    wrap![DiffMessage::Compared(compared)]
+                })
+            }
+            DiffMessage::Compared(result) => {
+                screen.comparing = false;
+                match result {
+                    Ok(Comparison {
+                        diff,
+                        frame_images,
+                        commit,
+                    }) => {
+                        screen.diff = Some(diff);
+                        screen.frame_images = frame_images;
+                        screen.compared_commit = commit;
+                        Task::none()
+                    }
+                    Err(err) => {
+                        error!("Failed to compare DMIs: {}", err);
+                        Task::done(popup(
+                            format!("Failed to compare DMIs: {}", err),
+                            Some("Compare failed"),
+                            ToastLevel::Error,
+                        ))
+                    }
+                }
+            }
+            DiffMessage::Clear => {
+                screen.diff = None;
+                screen.frame_images.clear();
+                screen.compared_commit = None;
+                Task::none()
+            }
+            DiffMessage::ChangeZoom(zoom) => {
+                screen.zoom = zoom;
+                Task::none()
+            }
+        }
+    }
+
+    fn view<'a>(app: &'a DMIAssistant) -> Element<'a, Message> {
+        let screen = &app.diff_screen;
+
+        if screen.comparing {
+            return container(text("Comparing..."))
+                .style(container::bordered_box)
+                .padding(50)
+                .center_x(Length::Fill)
+                .center_y(Length::Fill)
+                .into();
+        }
+
+        let path_input = |side: DiffSide, label: &'a str, path: &'a str| {
+            row![
+                text(label).width(50),
+                text_input("Input DMI path", path)
+                    .on_input(move |input| {
+                        wrap![DiffMessage::ChangePath(side, input)]
+                    })
+                    .on_paste(move |input| {
+                        wrap![DiffMessage::ChangePath(side, input)]
+                    })
+                    .on_submit(wrap![DiffMessage::Compare])
+                    .padding(10),
+                button(row![icon::iconfile(), text(" Browse Files")])
+                    .on_press(wrap![DiffMessage::OpenedFileExplorer(side)]),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center)
+        };
+
+        let zoom_picker: NumberInput<u32, Message> =
+            NumberInput::new(screen.zoom, 1..=16, |zoom| {
+                wrap![DiffMessage::ChangeZoom(zoom)]
+            })
+            .step(1);
+        let controls = row![
+            button(row![icon::search(), text(" Compare")])
+                .on_press_maybe(
+                    (!screen.old_path.is_empty()
+                        && !screen.new_path.is_empty())
+                    .then_some(wrap![DiffMessage::Compare])
+                )
+                .style(button::success),
+            button(row![icon::resize(), text(" Swap")])
+                .on_press(wrap![DiffMessage::SwapSides])
+                .style(button::secondary),
+            button(row![icon::trash(), text(" Clear")])
+                .on_press(wrap![DiffMessage::Clear])
+                .style(button::danger),
+            icon::magnifying(),
+            text(" Zoom: "),
+            zoom_picker,
+        ]
+        .spacing(10)
+        .align_y(Vertical::Center);
+
+        let old_revision_input = text_input(
+            &format!("Git revision (optional, e.g. {})", DEFAULT_REVISION),
+            &screen.old_revision,
+        )
+        .on_input(|input| wrap![DiffMessage::ChangeOldRevision(input)])
+        .on_paste(|input| wrap![DiffMessage::ChangeOldRevision(input)])
+        .on_submit(wrap![DiffMessage::Compare])
+        .width(250)
+        .padding(10);
+
+        let mut content = column![
+            path_input(DiffSide::Old, "Old:", &screen.old_path)
+                .push(old_revision_input),
+            path_input(DiffSide::New, "New:", &screen.new_path),
+            controls,
+        ]
+        .spacing(10);
+
+        match &screen.diff {
+            None => {
+                content = content.push(
+                    container(bold_text(
+                        "Choose two DMIs to see what changed between them",
+                    ))
+                    .style(container::bordered_box)
+                    .padding(50)
+                    .center_x(Length::Fill),
+                );
+            }
+            Some(diff) => {
+                let mut summary = column![text!(
+                    "Added: {} | Removed: {} | Renamed: {} | Modified: {} | Unchanged: {}",
+                    diff.count(StateChangeKind::Added),
+                    diff.count(StateChangeKind::Removed),
+                    diff.count(StateChangeKind::Renamed),
+                    diff.count(StateChangeKind::Modified),
+                    diff.unchanged
+                )]
+                .spacing(5);
+                if let Some(commit) = &screen.compared_commit {
+                    summary =
+                        summary.push(text!("Old DMI is taken from {}", commit));
+                }
+                if diff.old_size != diff.new_size {
+                    summary = summary.push(
+                        text!(
+                            "Icon size: {}x{} -> {}x{}",
+                            diff.old_size.0,
+                            diff.old_size.1,
+                            diff.new_size.0,
+                            diff.new_size.1
+                        )
+                        .color(color!(0xcde002)),
+                    );
+                }
+                if diff.is_empty() {
+                    summary = summary.push(bold_text("No differences"));
+                }
+                let legend = text(
+                    "Frames are shown as old | new | difference, changed \
+                     pixels are magenta",
+                )
+                .size(12);
+
+                let states: Column<Message> = diff
+                    .states
+                    .iter()
+                    .zip(&screen.frame_images)
+                    .map(|(state, images)| screen.display_state(state, images))
+                    .collect();
+                content = content
+                    .push(summary)
+                    .push(legend)
+                    .push(column![Space::with_height(10), states.spacing(10)]);
+            }
+        }
+
+        container(scrollable(content.padding(10)))
+            .padding(10)
+            .into()
+    }
+}
@@ -1,14 +1,17 @@
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     ffi::OsStr,
     fmt::Display,
-    path::PathBuf,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Instant,
 };
 
 use arboard::Clipboard;
+use chrono::{DateTime, Local};
 use iced::{
-    Element, Font, Length, Task,
+    Element, Font, Length, Subscription, Task,
     advanced::{
         self,
         widget::{Operation, operation},
@@ -18,29 +21,59 @@ use iced::{
     font::Weight,
     keyboard::{Key, Modifiers},
     widget::{
-        self, Column, Container, Space, TextInput, button, column, container,
-        radio, rich_text, row, scrollable, span, text, text_input,
+        self, Column, Container, Space, TextInput, button, checkbox, column,
+        container, radio, rich_text, row, scrollable, span, text, text_input,
+        tooltip,
     },
 };
 use iced_aw::{NumberInput, TabLabel};
 use iced_toasts::ToastLevel;
-use log::{debug, error};
+use image::{DynamicImage, RgbaImage};
+use log::{debug, error, warn};
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
 use crate::{
     DMIAssistant, Message, ViewerMessage,
-    dmi_utils::load_dmi,
+    dm_source::{DMProject, DMReferences},
+    dm_usage::{
+        MissingKind, MissingReport, UnusedReport, find_missing, find_unused,
+        parse_allow_list,
+    },
+    dmi_duplicates::{DuplicateGroup, DuplicateReport, find_duplicates},
+    dmi_edit::{MergeConflictPolicy, merge_dmi_files, split_dmi_file},
+    dmi_index::{
+        ExplorerIndex, ExportedDMI, IndexEntry, export_dmi_entry,
+        exported_dmis_to_csv, index_dmi, is_unchanged,
+    },
+    dmi_lint::{DEFAULT_MAX_ICON_SIZE, LintIssue, count_issues, lint_icon},
+    dmi_model::StateWarning,
+    dmi_similar::{
+        SIMILAR_STATES_LIMIT, SimilarState, find_similar, perceptual_hash,
+    },
+    dmi_utils::{
+        DEFAULT_EXPORT_NAME_TEMPLATE, ExportSummary, export_dmi, load_dmi,
+    },
     icon,
+    query::Query,
     screens::{Screen, Screens},
-    utils::{bold_text, popup},
+    search::{Match, MatchMode, Matcher},
+    utils::{bold_text, format_size, highlighted_text, lint_issue_text, popup},
+    watcher::{WatchedPaths, watch_paths},
     wrap,
 };
 
 const DEFAULT_PAGE_SIZE: usize = 20;
 const DEFAULT_DELIMETER: &str = ", ";
 const DEFAULT_RECURSION_DEPTH: usize = 20;
+/// Groups of each kind, shown in the duplicates report, the exported report
+/// has all of them.
+const DISPLAYED_DUPLICATE_GROUPS: usize = 100;
+/// States, shown in the unused states report.
+const DISPLAYED_UNUSED_STATES: usize = 500;
+/// References, shown in the missing states report.
+const DISPLAYED_MISSING_REFERENCES: usize = 500;
 
 const MAIN_EXPLORER_SCROLLABLE_ID: &str = "Main Explorer Scrollabe";
 const MAIN_EXPLORER_CONTAINER_ID: &str = "Main Explorer Container";
@@ -51,13 +84,59 @@ pub enum ExplorerMessage {
     OpenedFileExplorer(bool),
 
     LoadDMI(PathBuf),
-    DMILoaded((PathBuf, Result<Vec<String>, String>)),
+    DMILoaded((PathBuf, Result<IndexEntry, String>)),
+    RefreshIndex,
+    IndexChecked(Vec<(PathBuf, bool)>),
+    IndexSaved(Result<(), String>),
+    FilesChanged(Vec<PathBuf>),
+    WatchedFilesIndexed(
+        (Vec<PathBuf>, Vec<(PathBuf, Result<IndexEntry, String>)>),
+    ),
 
     CopyAllDMIText(PathBuf),
     CopyText(String),
     CopyFile(PathBuf),
     OpenInViewer(PathBuf),
 
+    ExportAll(PathBuf),
+    Exported(Result<(PathBuf, ExportSummary), String>),
+    /// Exports the index to JSON or CSV, only the found DMIs and states if
+    /// true
+    ExportIndex(bool),
+    IndexExported(Result<PathBuf, String>),
+
+    ToggleStateSelection(PathBuf, String, bool),
+    MergeWith(PathBuf),
+    Merged(Result<(PathBuf, Vec<String>), String>),
+    SplitSelected(PathBuf),
+    Split(Result<(PathBuf, PathBuf), String>),
+
+    /// Browses for a `.dme`, or for a folder if true
+    OpenProject(bool),
+    ScanProject(PathBuf),
+    RescanProject,
+    ProjectScanned(Result<(DMProject, Arc<DMReferences>), String>),
+    FindUnused,
+    UnusedFound(Result<UnusedReport, String>),
+    ToggleUnusedReport(bool),
+    ExportUnusedReport,
+    UnusedReportExported(Result<PathBuf, String>),
+    FindMissing,
+    MissingFound(MissingReport),
+    ToggleMissingReport(bool),
+
+    FindDuplicates,
+    DuplicatesFound(DuplicateReport),
+    ToggleDuplicatesReport(bool),
+    ExportDuplicatesReport,
+    DuplicatesReportExported(Result<PathBuf, String>),
+    FindSimilar(u64, String),
+    FindSimilarToClipboard,
+    HideSimilar,
+    LintAll,
+    Linted(LintReport),
+    ToggleLintReport(bool),
+
     RemoveDMI(PathBuf),
     ClearAll,
 
@@ -74,14 +153,34 @@ pub enum ExplorerMessage {
     ChangeDelimeter(String),
     ChangeRecursionDepth(usize),
     ChangeSearchFilterMode(SearchFilterMode),
+    ChangeMatchMode(MatchMode),
+    /// Sorts the listing, the current order is reversed
+    ChangeSort(ExplorerSort),
+    ChangeExportNameTemplate(String),
+    ChangeUnusedAllowList(String),
+    ChangeProjectScope(bool),
+    ChangeMergeConflictPolicy(MergeConflictPolicy),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ExplorerSettings {
     pub page_size: usize,
     pub delimeter: String,
     pub recursion_depth: usize,
     pub search_filter_mode: SearchFilterMode,
+    pub match_mode: MatchMode,
+    /// Used by both Explorer and Viewer, see [`export_dmi`]
+    pub export_name_template: String,
+    pub merge_conflict_policy: MergeConflictPolicy,
+    /// Globs of the state names, which are never reported as unused, like
+    /// the ones built at runtime
+    pub unused_allow_list: String,
+    /// Only the DMIs of the opened project are listed and checked
+    pub project_scope: bool,
+    pub sort: ExplorerSort,
+    /// The best, the biggest or the newest DMIs first
+    pub sort_descending: bool,
 }
 
 impl Default for ExplorerSettings {
@@ -91,6 +190,13 @@ impl Default for ExplorerSettings {
             delimeter: DEFAULT_DELIMETER.to_string(),
             recursion_depth: DEFAULT_RECURSION_DEPTH,
             search_filter_mode: SearchFilterMode::default(),
+            match_mode: MatchMode::default(),
+            export_name_template: DEFAULT_EXPORT_NAME_TEMPLATE.to_string(),
+            merge_conflict_policy: MergeConflictPolicy::default(),
+            unused_allow_list: String::new(),
+            project_scope: true,
+            sort: ExplorerSort::default(),
+            sort_descending: true,
         }
     }
 }
@@ -103,6 +209,8 @@ pub enum SearchFilterMode {
     DmiAndState,
     State,
     Dmi,
+    /// Filter is parsed as a [`Query`]
+    Query,
 }
 
 impl Display for SearchFilterMode {
@@ -111,35 +219,500 @@ impl Display for SearchFilterMode {
             SearchFilterMode::DmiAndState => write!(f, "DMI Path & State Name"),
             SearchFilterMode::State => write!(f, "State Name"),
             SearchFilterMode::Dmi => write!(f, "DMI Path"),
+            SearchFilterMode::Query => write!(f, "Query"),
+        }
+    }
+}
+
+/// Order of the Explorer listing. Ties are sorted by path.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq,
+)]
+pub enum ExplorerSort {
+    /// By the search score
+    #[default]
+    Relevance,
+    Path,
+    FileSize,
+    Modified,
+    /// By the icon area
+    Dimensions,
+    States,
+    /// By [`IndexEntry::total_frames`]
+    Frames,
+}
+
+impl ExplorerSort {
+    pub const ALL: [ExplorerSort; 7] = [
+        Self::Relevance,
+        Self::Path,
+        Self::FileSize,
+        Self::Modified,
+        Self::Dimensions,
+        Self::States,
+        Self::Frames,
+    ];
+}
+
+impl Display for ExplorerSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExplorerSort::Relevance => write!(f, "Relevance"),
+            ExplorerSort::Path => write!(f, "Path"),
+            ExplorerSort::FileSize => write!(f, "File Size"),
+            ExplorerSort::Modified => write!(f, "Modified"),
+            ExplorerSort::Dimensions => write!(f, "Dimensions"),
+            ExplorerSort::States => write!(f, "States"),
+            ExplorerSort::Frames => write!(f, "Frames"),
         }
     }
 }
 
+/// Warnings of the states, which have any, by state name.
+pub type StateWarnings = BTreeMap<String, Vec<StateWarning>>;
+
+/// Path of the found DMI, its match, its states with their matches and the
+/// best score.
+type FoundDMI<'a> = (
+    &'a PathBuf,
+    Option<Match>,
+    Vec<(&'a String, Option<Match>)>,
+    i64,
+);
+
+/// Lint issues of every linted DMI, or the reason it could not be loaded.
+pub type LintReport = BTreeMap<PathBuf, Result<Vec<LintIssue>, String>>;
+
 #[derive(Debug, Clone, Default)]
 pub struct ExplorerScreen {
     pub hovered_file: bool,
     pub path_in_input: String,
     pub loading_dmis: BTreeSet<PathBuf>,
     pub parsed_dmis: BTreeMap<PathBuf, Vec<String>>,
+    /// States checked for splitting, by DMI
+    pub selected_states: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// Missing frames of the states, found on loading
+    pub state_warnings: BTreeMap<PathBuf, StateWarnings>,
+    pub linting: bool,
+    pub lint_report: LintReport,
+    pub lint_report_visible: bool,
+    pub finding_duplicates: bool,
+    pub duplicates_report: Option<DuplicateReport>,
+    pub duplicates_report_visible: bool,
+    /// What was looked for and the found states
+    pub similar_states: Option<(String, Vec<SimilarState>)>,
     pub filtered_text: String,
+    /// Compiled `filtered_text`
+    pub filter: Matcher,
+    /// Parsed `filtered_text` in [`SearchFilterMode::Query`]
+    pub query: Query,
+    /// Why `filtered_text` can not be compiled, nothing is filtered then
+    pub filter_error: Option<String>,
     pub filter_opened: bool,
     pub current_page: usize,
     pub settings: ExplorerSettings,
     pub settings_visible: bool,
+    /// Loaded DMIs, saved into `data_dir` between the sessions
+    pub index: ExplorerIndex,
+    /// `index` was changed since it was saved
+    pub index_modified: bool,
+    /// `index` is being written, see [`Self::save_index`]
+    pub saving_index: bool,
+    /// BYOND project, which code uses the icons
+    pub project: Option<DMProject>,
+    /// Icon references in the `project` code
+    pub references: Option<Arc<DMReferences>>,
+    pub scanning_project: bool,
+    pub finding_unused: bool,
+    pub unused_report: Option<UnusedReport>,
+    pub unused_report_visible: bool,
+    pub finding_missing: bool,
+    pub missing_report: Option<MissingReport>,
+    pub missing_report_visible: bool,
 }
 
 impl ExplorerScreen {
+    /// Explorer showing every DMI of the index.
+    pub fn new(settings: ExplorerSettings, index: ExplorerIndex) -> Self {
+        let mut screen = Self {
+            settings,
+            ..Default::default()
+        };
+        screen.rebuild_filter();
+        for (path, entry) in &index.entries {
+            screen.show_indexed(path.clone(), entry);
+        }
+        screen.index = index;
+        screen
+    }
+
+    /// Shows the DMI and returns the number of states with warnings.
+    fn show_indexed(&mut self, path: PathBuf, entry: &IndexEntry) -> usize {
+        let state_warnings: StateWarnings = entry
+            .states
+            .iter()
+            .filter(|state| !state.warnings.is_empty())
+            .map(|state| (state.name.clone(), state.warnings.clone()))
+            .collect();
+        let broken_states = state_warnings.len();
+        if state_warnings.is_empty() {
+            self.state_warnings.remove(&path);
+        } else {
+            self.state_warnings.insert(path.clone(), state_warnings);
+        }
+        self.parsed_dmis.insert(path, entry.state_names());
+        broken_states
+    }
+
+    /// Removes the DMI from the Explorer and the index. Returns false if it
+    /// was not there.
+    fn forget_dmi(&mut self, path: &PathBuf) -> bool {
+        self.selected_states.remove(path);
+        self.state_warnings.remove(path);
+        self.lint_report.remove(path);
+        if self.index.entries.remove(path).is_some() {
+            self.index_modified = true;
+        }
+        self.parsed_dmis.remove(path).is_some()
+    }
+
+    /// Watches the added folders and the folders of separately added DMIs.
+    pub fn subscription(&self) -> Subscription<Message> {
+        let mut watched: WatchedPaths = self
+            .index
+            .roots
+            .iter()
+            .map(|root| (root.clone(), true))
+            .collect();
+        let parents: BTreeSet<PathBuf> = self
+            .parsed_dmis
+            .keys()
+            .filter(|path| self.index.depth_in_roots(path).is_none())
+            .filter_map(|path| path.parent().map(PathBuf::from))
+            .collect();
+        watched.extend(parents.into_iter().map(|parent| (parent, false)));
+        watch_paths("EXPLORER", watched)
+            .map(|changed| wrap![ExplorerMessage::FilesChanged(changed)])
+    }
+
+    /// The DMI belongs to the opened project, or the project does not limit
+    /// the Explorer.
+    fn in_scope(&self, path: &Path) -> bool {
+        !self.settings.project_scope
+            || self
+                .project
+                .as_ref()
+                .is_none_or(|project| project.contains_dmi(path))
+    }
+
+    /// Path for copying, relative to the project root if there is one.
+    fn copied_path(&self, path: &Path) -> String {
+        match &self.project {
+            Some(project) => project.relative_path(path),
+            None => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// DMIs of the scope, which pass the filter, in the selected order.
+    fn found_dmis(&self) -> Vec<FoundDMI<'_>> {
+        let search_mode = self.settings.search_filter_mode;
+        let mut found_dmis: Vec<FoundDMI> = Vec::new();
+        for (path, dmi) in &self.parsed_dmis {
+            if !self.in_scope(path) {
+                continue;
+            }
+            if search_mode == SearchFilterMode::Query {
+                let Some(found) = self
+                    .index
+                    .entries
+                    .get(path)
+                    .and_then(|entry| self.query.find(path, entry))
+                else {
+                    continue;
+                };
+                let mut states_found: Vec<(&String, Option<Match>)> = found
+                    .states
+                    .into_iter()
+                    .filter_map(|(index, state_found)| {
+                        dmi.get(index).map(|state| (state, Some(state_found)))
+                    })
+                    .collect();
+                states_found.sort_by_key(|(_, found)| {
+                    found.as_ref().map_or(i64::MAX, |found| -found.score)
+                });
+                found_dmis.push((
+                    path,
+                    Some(found.path),
+                    states_found,
+                    found.score,
+                ));
+                continue;
+            }
+            let path_found = if search_mode != SearchFilterMode::State {
+                self.filter.find(&path.to_string_lossy())
+            } else {
+                None
+            };
+            let mut states_found: Vec<(&String, Option<Match>)> = dmi
+                .iter()
+                .map(|state| {
+                    let state_found = if search_mode != SearchFilterMode::Dmi {
+                        self.filter.find(state)
+                    } else {
+                        None
+                    };
+                    (state, state_found)
+                })
+                .collect();
+            let best_state_score = states_found
+                .iter()
+                .filter_map(|(_, found)| {
+                    found.as_ref().map(|found| found.score)
+                })
+                .max();
+            let Some(score) = path_found
+                .as_ref()
+                .map(|found| found.score)
+                .max(best_state_score)
+            else {
+                continue;
+            };
+            // All states are shown for the found DMI, only the found ones
+            // otherwise
+            if path_found.is_none() {
+                states_found.retain(|(_, found)| found.is_some());
+            }
+            // Stable, so the states, which were not found, keep the file order
+            states_found.sort_by_key(|(_, found)| {
+                found.as_ref().map_or(i64::MAX, |found| -found.score)
+            });
+            found_dmis.push((path, path_found, states_found, score));
+        }
+        let entries = &self.index.entries;
+        let number = |path: &PathBuf| -> Option<u64> {
+            let entry = entries.get(path)?;
+            Some(match self.settings.sort {
+                ExplorerSort::FileSize => entry.stamp.size,
+                ExplorerSort::Dimensions => {
+                    entry.width as u64 * entry.height as u64
+                }
+                ExplorerSort::States => entry.states.len() as u64,
+                ExplorerSort::Frames => entry.total_frames(),
+                _ => 0,
+            })
+        };
+        let modified = |path: &PathBuf| {
+            entries.get(path).and_then(|entry| entry.stamp.modified)
+        };
+        found_dmis.sort_by(|first, second| {
+            let order = match self.settings.sort {
+                ExplorerSort::Relevance => first.3.cmp(&second.3),
+                ExplorerSort::Path => first.0.cmp(second.0),
+                ExplorerSort::Modified => {
+                    modified(first.0).cmp(&modified(second.0))
+                }
+                _ => number(first.0).cmp(&number(second.0)),
+            };
+            let order = if self.settings.sort_descending {
+                order.reverse()
+            } else {
+                order
+            };
+            order.then_with(|| first.0.cmp(second.0))
+        });
+        found_dmis
+    }
+
+    fn sort_view<'a>(&self) -> Container<'a, Message> {
+        let sort_picker = ExplorerSort::ALL.iter().fold(
+            row![text("Sort by: ")].spacing(5).align_y(Vertical::Center),
+            |picker, sort| {
+                let selected = *sort == self.settings.sort;
+                let label = match (selected, self.settings.sort_descending) {
+                    (false, _) => sort.to_string(),
+                    (true, true) => format!("{} \u{25BC}", sort),
+                    (true, false) => format!("{} \u{25B2}", sort),
+                };
+                picker.push(
+                    button(text(label))
+                        .on_press(wrap![ExplorerMessage::ChangeSort(*sort)])
+                        .style(if selected {
+                            button::primary
+                        } else {
+                            button::secondary
+                        }),
+                )
+            },
+        );
+        container(sort_picker).padding(5)
+    }
+
+    /// Writes the changed index in background, once nothing is loading.
+    /// Changes made during a save are written after it.
+    fn save_index(&mut self, data_dir: PathBuf) -> Task<Message> {
+        // Saves write the same temporary file, so they must not overlap
+        if !self.index_modified
+            || !self.loading_dmis.is_empty()
+            || self.saving_index
+        {
+            return Task::none();
+        }
+        self.saving_index = true;
+        self.index_modified = false;
+        let index = self.index.clone();
+        Task::future(async move {
+            let saved = index.save(&data_dir).map_err(|err| err.to_string());
+            wrap![ExplorerMessage::IndexSaved(saved)]
+        })
+    }
+
+    fn rebuild_filter(&mut self) {
+        self.filter = Matcher::default();
+        self.query = Query::default();
+        let rebuilt =
+            if self.settings.search_filter_mode == SearchFilterMode::Query {
+                Query::parse(&self.filtered_text, self.settings.match_mode)
+                    .map(|query| self.query = query)
+                    .map_err(|err| err.to_string())
+            } else {
+                Matcher::new(&self.filtered_text, self.settings.match_mode)
+                    .map(|filter| self.filter = filter)
+                    .map_err(|err| err.to_string())
+            };
+        self.filter_error = rebuilt.err();
+    }
+
+    fn project_view<'a>(&self) -> Container<'a, Message> {
+        let status = if self.scanning_project {
+            text("Scanning the project code...")
+        } else {
+            match (&self.project, &self.references) {
+                (Some(project), Some(references)) => text!(
+                    "Project {}: {} DMIs, {} icon references in {} files",
+                    project.name,
+                    project.dmis.len(),
+                    references.references.len(),
+                    project.sources.len()
+                ),
+                _ => text("No project is opened, icon references are unknown"),
+            }
+        };
+        let mut project_row = row![
+            button(row![icon::file(), text(" Open .dme")])
+                .on_press(wrap![ExplorerMessage::OpenProject(false)]),
+            button(row![icon::folder(), text(" Open Project Folder")])
+                .on_press(wrap![ExplorerMessage::OpenProject(true)]),
+            status
+        ]
+        .spacing(10)
+        .align_y(Vertical::Center);
+        if self.index.project.is_some() && !self.scanning_project {
+            project_row = project_row.push(
+                button(row![icon::search(), text(" Rescan")])
+                    .on_press(wrap![ExplorerMessage::RescanProject])
+                    .style(button::secondary),
+            );
+        }
+        if self.references.is_some() && !self.scanning_project {
+            project_row = project_row.push(
+                button(row![
+                    icon::trash(),
+                    text(if self.finding_unused {
+                        " Searching..."
+                    } else {
+                        " Find Unused States"
+                    })
+                ])
+                .on_press_maybe(
+                    (!self.finding_unused)
+                        .then_some(wrap![ExplorerMessage::FindUnused]),
+                )
+                .style(button::secondary),
+            );
+            project_row = project_row.push(
+                button(text(if self.unused_report_visible {
+                    "Hide Unused"
+                } else {
+                    "Show Unused"
+                }))
+                .on_press_maybe(self.unused_report.is_some().then_some(wrap![
+                    ExplorerMessage::ToggleUnusedReport(
+                        !self.unused_report_visible
+                    )
+                ]))
+                .style(button::secondary),
+            );
+            project_row = project_row.push(
+                button(row![
+                    icon::search(),
+                    text(if self.finding_missing {
+                        " Checking..."
+                    } else {
+                        " Find Missing States"
+                    })
+                ])
+                .on_press_maybe(
+                    (!self.finding_missing)
+                        .then_some(wrap![ExplorerMessage::FindMissing]),
+                )
+                .style(button::secondary),
+            );
+            project_row = project_row.push(
+                button(text(if self.missing_report_visible {
+                    "Hide Missing"
+                } else {
+                    "Show Missing"
+                }))
+                .on_press_maybe(self.missing_report.is_some().then_some(wrap![
+                    ExplorerMessage::ToggleMissingReport(
+                        !self.missing_report_visible
+                    )
+                ]))
+                .style(button::secondary),
+            );
+        }
+        container(project_row).padding(5)
+    }
+
     fn filter_view<'a>(&self) -> Container<'a, Message> {
         if self.filter_opened {
+            let mode_picker = MatchMode::ALL.iter().fold(
+                row![text("Match: ")].spacing(10),
+                |picker, mode| {
+                    picker.push(radio(
+                        mode.to_string(),
+                        mode,
+                        Some(&self.settings.match_mode),
+                        |mode| wrap![ExplorerMessage::ChangeMatchMode(*mode)],
+                    ))
+                },
+            );
+            let filter_error: Element<Message> = match &self.filter_error {
+                Some(err) => text(err.clone()).color(color!(0xfc4144)).into(),
+                None => Space::new(0, 0).into(),
+            };
+            let placeholder = if self.settings.search_filter_mode
+                == SearchFilterMode::Query
+            {
+                "Enter query, like path:mob state:walk* dirs:8 frames>1 \
+                 -state:dead..."
+            } else {
+                "Enter text to find..."
+            };
             container(
-                text_input("Enter text to find...", &self.filtered_text)
-                    .on_input(|input| {
-                        wrap![ExplorerMessage::ChangeFilteredText(input)]
-                    })
-                    .on_paste(|input| {
-                        wrap![ExplorerMessage::ChangeFilteredText(input)]
-                    })
-                    .padding(10),
+                column![
+                    text_input(placeholder, &self.filtered_text)
+                        .on_input(|input| {
+                            wrap![ExplorerMessage::ChangeFilteredText(input)]
+                        })
+                        .on_paste(|input| {
+                            wrap![ExplorerMessage::ChangeFilteredText(input)]
+                        })
+                        .padding(10),
+                    mode_picker,
+                    filter_error
+                ]
+                .spacing(5),
             )
             .style(container::bordered_box)
             .padding(10)
@@ -147,11 +720,384 @@ impl ExplorerScreen {
             container("")
         }
     }
+
+    fn similar_view<'a>(&self) -> Container<'a, Message> {
+        let Some((looked_for, similar)) = &self.similar_states else {
+            return container("");
+        };
+        let found: Column<Message> = if similar.is_empty() {
+            column![text("No states found")]
+        } else {
+            similar
+                .iter()
+                .map(|found| {
+                    row![
+                        text!("{:>3.0}%", found.similarity * 100.0).color(
+                            if found.similarity >= 0.9 {
+                                color!(0x89fc41)
+                            } else {
+                                color!(0xcde002)
+                            }
+                        ),
+                        button(icon::search())
+                            .on_press(wrap![ExplorerMessage::OpenInViewer(
+                                found.path.clone()
+                            )])
+                            .style(button::success),
+                        button(icon::save())
+                            .on_press(wrap![ExplorerMessage::CopyText(
+                                found.state.clone()
+                            )])
+                            .style(button::secondary),
+                        bold_text(found.state.clone()),
+                        text(found.path.to_string_lossy().into_owned()),
+                    ]
+                    .spacing(10)
+                    .align_y(Vertical::Center)
+                    .into()
+                })
+                .collect()
+        };
+        container(
+            column![
+                row![
+                    bold_text(format!("Similar to {}", looked_for)),
+                    Space::with_width(Length::Fill),
+                    button(icon::trash())
+                        .on_press(wrap![ExplorerMessage::HideSimilar])
+                        .style(button::secondary),
+                ]
+                .align_y(Vertical::Center),
+                found.spacing(5)
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
+
+    fn unused_report_view<'a>(&self) -> Container<'a, Message> {
+        let Some(report) = &self.unused_report else {
+            return container("");
+        };
+        if !self.unused_report_visible {
+            return container("");
+        }
+        let mut states: Column<Message> = report
+            .states
+            .iter()
+            .take(DISPLAYED_UNUSED_STATES)
+            .map(|unused| {
+                row![
+                    button(icon::search())
+                        .on_press(wrap![ExplorerMessage::OpenInViewer(
+                            unused.path.clone()
+                        )])
+                        .style(button::success),
+                    button(icon::save())
+                        .on_press(wrap![ExplorerMessage::CopyText(
+                            unused.state.clone()
+                        )])
+                        .style(button::secondary),
+                    bold_text(unused.state.clone()),
+                    text(
+                        report
+                            .relative(&unused.path)
+                            .to_string_lossy()
+                            .into_owned()
+                    ),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center)
+                .into()
+            })
+            .collect();
+        if report.states.len() > DISPLAYED_UNUSED_STATES {
+            states = states.push(text!(
+                "... and {} more, export the report to see them all",
+                report.states.len() - DISPLAYED_UNUSED_STATES
+            ));
+        }
+        let failed: Column<Message> = report
+            .failed
+            .iter()
+            .map(|(path, err)| {
+                text!("{} (failed to load): {}", path.to_string_lossy(), err)
+                    .color(color!(0xfc4144))
+                    .into()
+            })
+            .collect();
+        container(
+            column![
+                row![
+                    bold_text(format!(
+                        "Unused states: {} of {} states in {} DMIs",
+                        report.states.len(),
+                        report.checked_states,
+                        report.checked_dmis
+                    )),
+                    button(row![icon::save(), text(" Export Report")])
+                        .on_press(wrap![ExplorerMessage::ExportUnusedReport])
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center),
+                text(
+                    "Nothing in the code or maps names these states. Names, \
+                     built at runtime, are matched by their constant start, \
+                     add the rest to the allow-list in the settings."
+                ),
+                states.spacing(5),
+                failed.spacing(5)
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
+
+    fn missing_report_view<'a>(&self) -> Container<'a, Message> {
+        let Some(report) = &self.missing_report else {
+            return container("");
+        };
+        if !self.missing_report_visible {
+            return container("");
+        }
+        let mut missing: Column<Message> = report
+            .missing
+            .iter()
+            .take(DISPLAYED_MISSING_REFERENCES)
+            .map(|missing| {
+                let reference = &missing.reference;
+                let mut what = format!(
+                    "{} {}",
+                    reference.icon.as_deref().unwrap_or_default(),
+                    reference
+                        .state
+                        .as_ref()
+                        .map(|state| format!("{:?}", state))
+                        .unwrap_or_default()
+                );
+                if reference.inherited {
+                    what += " (icon of the type)";
+                }
+                column![
+                    row![
+                        button(icon::save())
+                            .on_press(wrap![ExplorerMessage::CopyText(
+                                reference.location()
+                            )])
+                            .style(button::secondary),
+                        bold_text(report.location(reference)),
+                        text(what),
+                        text(missing.kind.to_string()).color(
+                            if missing.kind == MissingKind::Dmi {
+                                color!(0xfc4144)
+                            } else {
+                                color!(0xcde002)
+                            }
+                        ),
+                    ]
+                    .spacing(10)
+                    .align_y(Vertical::Center),
+                    text(reference.code.clone()).size(12),
+                ]
+                .spacing(2)
+                .into()
+            })
+            .collect();
+        if report.missing.len() > DISPLAYED_MISSING_REFERENCES {
+            missing = missing.push(text!(
+                "... and {} more",
+                report.missing.len() - DISPLAYED_MISSING_REFERENCES
+            ));
+        }
+        let failed: Column<Message> = report
+            .failed
+            .iter()
+            .map(|(path, err)| {
+                text!("{} (failed to load): {}", path.to_string_lossy(), err)
+                    .color(color!(0xfc4144))
+                    .into()
+            })
+            .collect();
+        container(
+            column![
+                bold_text(format!(
+                    "Missing states: {} of {} references are broken",
+                    report.missing.len(),
+                    report.checked
+                )),
+                missing.spacing(5),
+                failed.spacing(5)
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
+
+    fn duplicates_report_view<'a>(&self) -> Container<'a, Message> {
+        let Some(report) = &self.duplicates_report else {
+            return container("");
+        };
+        if !self.duplicates_report_visible {
+            return container("");
+        }
+        let groups_view = |title: &str, groups: &[DuplicateGroup]| {
+            let mut section: Column<Message> =
+                column![bold_text(format!("{} ({})", title, groups.len()))]
+                    .spacing(5);
+            for group in groups.iter().take(DISPLAYED_DUPLICATE_GROUPS) {
+                let copies: Column<Message> = group
+                    .copies
+                    .iter()
+                    .map(|copy| {
+                        row![
+                            button(icon::search())
+                                .on_press(wrap![ExplorerMessage::OpenInViewer(
+                                    copy.path.clone()
+                                )])
+                                .style(button::success),
+                            text(copy.to_string())
+                        ]
+                        .spacing(10)
+                        .align_y(Vertical::Center)
+                        .into()
+                    })
+                    .collect();
+                section = section.push(column![
+                    text!(
+                        "{} copies of {}x{}, {} images each",
+                        group.copies.len(),
+                        group.width,
+                        group.height,
+                        group.images
+                    )
+                    .color(color!(0xcde002)),
+                    copies.spacing(5).padding([0, 20]),
+                ]);
+            }
+            if groups.len() > DISPLAYED_DUPLICATE_GROUPS {
+                section = section.push(text!(
+                    "... and {} more, export the report to see them all",
+                    groups.len() - DISPLAYED_DUPLICATE_GROUPS
+                ));
+            }
+            section
+        };
+        let failed: Column<Message> = report
+            .failed
+            .iter()
+            .map(|(path, err)| {
+                text!("{} (failed to load): {}", path.to_string_lossy(), err)
+                    .color(color!(0xfc4144))
+                    .into()
+            })
+            .collect();
+        container(
+            column![
+                row![
+                    bold_text(format!(
+                        "Duplicates: {} KiB of raw pixels are repeated",
+                        report.wasted_bytes() / 1024
+                    )),
+                    button(row![icon::save(), text(" Export Report")])
+                        .on_press(wrap![
+                            ExplorerMessage::ExportDuplicatesReport
+                        ])
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center),
+                groups_view("Duplicated states", &report.states),
+                groups_view(
+                    "Frames repeated in different states",
+                    &report.frames
+                ),
+                failed.spacing(5)
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
+
+    fn lint_report_view<'a>(&self) -> Container<'a, Message> {
+        if !self.lint_report_visible || self.lint_report.is_empty() {
+            return container("");
+        }
+        let mut report: Column<Message> = Column::new().spacing(5);
+        let mut clean_dmis: usize = 0;
+        for (path, issues) in &self.lint_report {
+            let (header, issues): (String, Column<Message>) = match issues {
+                Ok(issues) if issues.is_empty() => {
+                    clean_dmis += 1;
+                    continue;
+                }
+                Ok(issues) => {
+                    let (errors, warnings) = count_issues(issues);
+                    (
+                        format!(
+                            "{} ({} errors, {} warnings)",
+                            path.to_string_lossy(),
+                            errors,
+                            warnings
+                        ),
+                        issues
+                            .iter()
+                            .map(|issue| lint_issue_text(issue).into())
+                            .collect(),
+                    )
+                }
+                Err(err) => (
+                    format!("{} (failed to load)", path.to_string_lossy()),
+                    column![text(err.clone()).color(color!(0xfc4144))],
+                ),
+            };
+            report = report.push(column![
+                row![
+                    bold_text(header),
+                    button(row![icon::search(), text(" View")])
+                        .on_press(wrap![ExplorerMessage::OpenInViewer(
+                            path.clone()
+                        )])
+                        .style(button::success),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center),
+                issues.spacing(5),
+                Space::with_height(10)
+            ]);
+        }
+        container(
+            column![
+                bold_text(format!(
+                    "Lint report: {} of {} DMIs have no issues",
+                    clean_dmis,
+                    self.lint_report.len()
+                )),
+                report
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
 }
 
 impl Screen for ExplorerScreen {
     fn label(&self) -> TabLabel {
-        TabLabel::IconText('\u{1F50D}', " Explorer".to_string())
+        let label = match &self.project {
+            Some(project) => format!(" Explorer: {}", project.name),
+            None => " Explorer".to_string(),
+        };
+        TabLabel::IconText('\u{1F50D}', label)
     }
 
     fn update(app: &mut DMIAssistant, message: Message) -> Task<Message> {
@@ -177,6 +1123,8 @@ impl Screen for ExplorerScreen {
 
                     if base_path.is_dir() {
                         screen.hovered_file = false;
+                        screen.index.roots.insert(base_path.clone());
+                        screen.index_modified = true;
                         return Task::batch(
                             WalkDir::new(&base_path)
                                 .max_depth(screen.settings.recursion_depth)
@@ -210,6 +1158,12 @@ impl Screen for ExplorerScreen {
                                 }),
                         );
                     }
+                    if base_path.extension() == Some(OsStr::new("dme")) {
+                        screen.hovered_file = false;
+                        return Task::done(wrap![
+                            ExplorerMessage::ScanProject(base_path)
+                        ]);
+                    }
                     if screen.loading_dmis.contains(&path)
                         || screen.parsed_dmis.contains_key(&path)
                     {
@@ -240,36 +1194,34 @@ impl Screen for ExplorerScreen {
             Message::ExplorerMessage(explorer_message) => {
                 match explorer_message {
                     ExplorerMessage::LoadDMI(path) => {
+                        if screen.index.removed.remove(&path) {
+                            screen.index_modified = true;
+                        }
                         screen.loading_dmis.insert(path.clone());
+                        let previous = screen.index.entries.get(&path).cloned();
                         Task::future(async move {
                             let load_start = Instant::now();
-                            let opened_dmi = load_dmi(path.clone());
-                            if opened_dmi.is_err() {
-                                return wrap![ExplorerMessage::DMILoaded((
-                                    path,
-                                    Err(format!("{}", opened_dmi.unwrap_err()),)
-                                ))];
+                            let indexed = index_dmi(&path, previous.as_ref());
+                            if let Ok((_, reparsed)) = &indexed {
+                                debug!(
+                                    "DMI {} {} in {}ms",
+                                    path.to_string_lossy(),
+                                    if *reparsed {
+                                        "parsed"
+                                    } else {
+                                        "taken from the index"
+                                    },
+                                    load_start.elapsed().as_millis()
+                                );
                             }
-                            let opened_dmi = opened_dmi.unwrap();
-
-                            let existing_states: Vec<String> = opened_dmi
-                                .states
-                                .iter()
-                                .map(|state| state.name.clone())
-                                .collect();
-
-                            debug!(
-                                "DMI {} parsed in {}ms",
-                                path.to_string_lossy(),
-                                load_start.elapsed().as_millis()
-                            );
-                            wrap![ExplorerMessage::DMILoaded((
-                                path,
-                                Ok(existing_states)
-                            ))]
+                            let indexed = indexed
+                                .map(|(entry, _)| entry)
+                                .map_err(|err| err.to_string());
+                            wrap![ExplorerMessage::DMILoaded((path, indexed))]
                         })
                     }
                     ExplorerMessage::DMILoaded((path, loaded)) => {
+                        let data_dir = app.config.data_dir.clone();
                         if let Err(err) = loaded {
                             error!(
                                 "Failed to load DMI {}; Reason: {}",
@@ -277,40 +1229,280 @@ impl Screen for ExplorerScreen {
                                 err
                             );
                             screen.loading_dmis.remove(&path);
-                            return Task::done(popup(
-                                format!(
-                                    "Failed to load DMI: {}; Reason: {}",
-                                    path.to_string_lossy(),
-                                    err
-                                ),
-                                Some("Load failed"),
-                                ToastLevel::Warning,
-                            ));
+                            // Unreadable files may be still being written,
+                            // only deleted ones leave the index
+                            if matches!(path.try_exists(), Ok(false))
+                                && screen.index.entries.contains_key(&path)
+                            {
+                                screen.forget_dmi(&path);
+                            }
+                            return Task::batch([
+                                screen.save_index(data_dir),
+                                Task::done(popup(
+                                    format!(
+                                        "Failed to load DMI: {}; Reason: {}",
+                                        path.to_string_lossy(),
+                                        err
+                                    ),
+                                    Some("Load failed"),
+                                    ToastLevel::Warning,
+                                )),
+                            ]);
                         }
-                        if screen.loading_dmis.remove(&path) {
-                            screen
-                                .parsed_dmis
-                                .insert(path.clone(), loaded.unwrap());
+                        let entry = loaded.unwrap();
+                        if !screen.loading_dmis.remove(&path) {
+                            return Task::none();
                         }
+                        let broken_states =
+                            screen.show_indexed(path.clone(), &entry);
+                        if screen.index.entries.get(&path) != Some(&entry) {
+                            screen.index.entries.insert(path.clone(), entry);
+                            screen.index_modified = true;
+                        }
+                        let save = screen.save_index(data_dir);
 
-                        Task::done(popup(
-                            format!("Loaded {}", path.to_string_lossy(),),
-                            Some("Loaded DMI"),
-                            ToastLevel::Success,
-                        ))
+                        if broken_states > 0 {
+                            return Task::batch([
+                                save,
+                                Task::done(popup(
+                                    format!(
+                                        "Loaded {}, but {} states have \
+                                         missing frames",
+                                        path.to_string_lossy(),
+                                        broken_states
+                                    ),
+                                    Some("Loaded DMI with warnings"),
+                                    ToastLevel::Warning,
+                                )),
+                            ]);
+                        }
+                        Task::batch([
+                            save,
+                            Task::done(popup(
+                                format!("Loaded {}", path.to_string_lossy(),),
+                                Some("Loaded DMI"),
+                                ToastLevel::Success,
+                            )),
+                        ])
                     }
-                    ExplorerMessage::CopyAllDMIText(path) => {
-                        let states = screen
-                            .parsed_dmis
-                            .get(&path)
-                            .unwrap_or(&Vec::new())
-                            .join(&screen.settings.delimeter);
-                        let _ = Clipboard::new().unwrap().set_text(states);
-                        Task::done(popup(
-                            "All states were copied",
-                            Some("Copied"),
-                            ToastLevel::Success,
-                        ))
+                    ExplorerMessage::RefreshIndex => {
+                        let stamps: Vec<_> = screen
+                            .index
+                            .entries
+                            .iter()
+                            .map(|(path, entry)| {
+                                (path.clone(), entry.stamp.clone())
+                            })
+                            .collect();
+                        Task::future(async move {
+                            let changed: Vec<(PathBuf, bool)> = stamps
+                                .into_iter()
+                                .filter(|(path, stamp)| {
+                                    !is_unchanged(path, stamp)
+                                })
+                                .map(|(path, _)| {
+                                    let exists = path.is_file();
+                                    (path, exists)
+                                })
+                                .collect();
+                            wrap![ExplorerMessage::IndexChecked(changed)]
+                        })
+                    }
+                    ExplorerMessage::IndexChecked(changed) => {
+                        let mut reload: Vec<Task<Message>> = Vec::new();
+                        for (path, exists) in changed {
+                            if exists {
+                                reload.push(Task::done(wrap![
+                                    ExplorerMessage::LoadDMI(path)
+                                ]));
+                            } else {
+                                screen.forget_dmi(&path);
+                            }
+                        }
+                        reload.push(
+                            screen.save_index(app.config.data_dir.clone()),
+                        );
+                        Task::batch(reload)
+                    }
+                    ExplorerMessage::FilesChanged(changed) => {
+                        let depth = screen.settings.recursion_depth;
+                        let mut removed: BTreeSet<PathBuf> = BTreeSet::new();
+                        let mut changed_dmis: Vec<(
+                            PathBuf,
+                            Option<IndexEntry>,
+                        )> = Vec::new();
+                        let mut new_dirs: Vec<(PathBuf, usize)> = Vec::new();
+                        for path in changed {
+                            if path.is_dir() {
+                                if let Some(dir_depth) =
+                                    screen.index.depth_in_roots(&path)
+                                    && dir_depth < depth
+                                {
+                                    new_dirs.push((path, depth - dir_depth));
+                                }
+                            } else if path.is_file() {
+                                let known =
+                                    screen.parsed_dmis.contains_key(&path);
+                                if path.extension() == Some(OsStr::new("dmi"))
+                                    && !screen.loading_dmis.contains(&path)
+                                    && !screen.index.removed.contains(&path)
+                                    && (known
+                                        || screen
+                                            .index
+                                            .depth_in_roots(&path)
+                                            .is_some_and(|file_depth| {
+                                                file_depth <= depth
+                                            }))
+                                {
+                                    let previous = screen
+                                        .index
+                                        .entries
+                                        .get(&path)
+                                        .cloned();
+                                    changed_dmis.push((path, previous));
+                                }
+                            } else {
+                                // Removed files and folders
+                                removed.extend(
+                                    screen
+                                        .parsed_dmis
+                                        .keys()
+                                        .filter(|known| {
+                                            known.starts_with(&path)
+                                        })
+                                        .cloned(),
+                                );
+                            }
+                        }
+                        if removed.is_empty()
+                            && changed_dmis.is_empty()
+                            && new_dirs.is_empty()
+                        {
+                            return Task::none();
+                        }
+
+                        let known: BTreeSet<PathBuf> = screen
+                            .parsed_dmis
+                            .keys()
+                            .chain(&screen.index.removed)
+                            .cloned()
+                            .collect();
+                        Task::future(async move {
+                            for (dir, dir_depth) in new_dirs {
+                                changed_dmis.extend(
+                                    WalkDir::new(dir)
+                                        .max_depth(dir_depth)
+                                        .into_iter()
+                                        .filter_map(Result::ok)
+                                        .map(|entry| entry.into_path())
+                                        .filter(|path| {
+                                            path.is_file()
+                                                && path.extension()
+                                                    == Some(OsStr::new("dmi"))
+                                                && !known.contains(path)
+                                        })
+                                        .map(|path| (path, None)),
+                                );
+                            }
+                            let indexed = changed_dmis
+                                .into_iter()
+                                .map(|(path, previous)| {
+                                    let entry =
+                                        index_dmi(&path, previous.as_ref())
+                                            .map(|(entry, _)| entry)
+                                            .map_err(|err| err.to_string());
+                                    (path, entry)
+                                })
+                                .collect();
+                            wrap![ExplorerMessage::WatchedFilesIndexed((
+                                removed.into_iter().collect(),
+                                indexed
+                            ))]
+                        })
+                    }
+                    ExplorerMessage::WatchedFilesIndexed((
+                        removed,
+                        indexed,
+                    )) => {
+                        let removed = removed
+                            .iter()
+                            .filter(|path| screen.forget_dmi(path))
+                            .count();
+                        let (mut updated, mut added, mut failed) = (0, 0, 0);
+                        for (path, indexed) in indexed {
+                            let entry = match indexed {
+                                Ok(entry) => entry,
+                                Err(err) => {
+                                    warn!(
+                                        "Failed to reload changed DMI {}: {}",
+                                        path.to_string_lossy(),
+                                        err
+                                    );
+                                    failed += 1;
+                                    continue;
+                                }
+                            };
+                            match screen.index.entries.get(&path) {
+                                Some(previous) if *previous == entry => {
+                                    continue;
+                                }
+                                Some(previous)
+                                    if previous.stamp.hash
+                                        == entry.stamp.hash => {}
+                                Some(_) => updated += 1,
+                                None => added += 1,
+                            }
+                            screen.lint_report.remove(&path);
+                            screen.show_indexed(path.clone(), &entry);
+                            screen.index.entries.insert(path, entry);
+                            screen.index_modified = true;
+                        }
+                        let save =
+                            screen.save_index(app.config.data_dir.clone());
+                        if updated + added + removed + failed == 0 {
+                            return save;
+                        }
+
+                        let mut summary = format!(
+                            "{} updated, {} added, {} removed",
+                            updated, added, removed
+                        );
+                        if failed > 0 {
+                            summary += &format!(", {} failed to load", failed);
+                        }
+                        Task::batch([
+                            save,
+                            Task::done(popup(
+                                summary,
+                                Some("DMIs changed on disk"),
+                                if failed > 0 {
+                                    ToastLevel::Warning
+                                } else {
+                                    ToastLevel::Info
+                                },
+                            )),
+                        ])
+                    }
+                    ExplorerMessage::IndexSaved(result) => {
+                        screen.saving_index = false;
+                        if let Err(err) = result {
+                            error!("Failed to save Explorer index: {}", err);
+                            return Task::none();
+                        }
+                        screen.save_index(app.config.data_dir.clone())
+                    }
+                    ExplorerMessage::CopyAllDMIText(path) => {
+                        let states = screen
+                            .parsed_dmis
+                            .get(&path)
+                            .unwrap_or(&Vec::new())
+                            .join(&screen.settings.delimeter);
+                        let _ = Clipboard::new().unwrap().set_text(states);
+                        Task::done(popup(
+                            "All states were copied",
+                            Some("Copied"),
+                            ToastLevel::Success,
+                        ))
                     }
                     ExplorerMessage::CopyText(text) => {
                         let _ = Clipboard::new().unwrap().set_text(text);
@@ -321,24 +1513,41 @@ impl Screen for ExplorerScreen {
                         ))
                     }
                     ExplorerMessage::RemoveDMI(path) => {
-                        screen.parsed_dmis.remove(&path);
-                        Task::done(popup(
-                            format!(
-                                "{} was removed from explorer",
-                                path.to_string_lossy()
-                            ),
-                            Some("Removed"),
-                            ToastLevel::Success,
-                        ))
+                        screen.forget_dmi(&path);
+                        // Otherwise the watcher or the project scan bring it
+                        // back
+                        screen.index.removed.insert(path.clone());
+                        screen.index_modified = true;
+                        Task::batch([
+                            screen.save_index(app.config.data_dir.clone()),
+                            Task::done(popup(
+                                format!(
+                                    "{} was removed from explorer",
+                                    path.to_string_lossy()
+                                ),
+                                Some("Removed"),
+                                ToastLevel::Success,
+                            )),
+                        ])
                     }
                     ExplorerMessage::ClearAll => {
                         screen.parsed_dmis.clear();
                         screen.loading_dmis.clear();
-                        Task::done(popup(
-                            "Explorer was cleared",
-                            Some("Removed All"),
-                            ToastLevel::Success,
-                        ))
+                        screen.selected_states.clear();
+                        screen.state_warnings.clear();
+                        screen.lint_report.clear();
+                        screen.index.entries.clear();
+                        screen.index.roots.clear();
+                        screen.index.removed.clear();
+                        screen.index_modified = true;
+                        Task::batch([
+                            screen.save_index(app.config.data_dir.clone()),
+                            Task::done(popup(
+                                "Explorer was cleared",
+                                Some("Removed All"),
+                                ToastLevel::Success,
+                            )),
+                        ])
                     }
                     ExplorerMessage::ChangeInputDMIPath(new_string) => {
                         screen.path_in_input = new_string;
@@ -360,6 +1569,12 @@ impl Screen for ExplorerScreen {
 
                         if let Some(paths) = files {
                             let dummy = PathBuf::new();
+                            for path in
+                                paths.iter().filter(|path| path.is_dir())
+                            {
+                                screen.index.roots.insert(path.clone());
+                                screen.index_modified = true;
+                            }
 
                             Task::batch(paths.into_iter().map(|path| {
                                 if path.is_dir() {
@@ -415,6 +1630,7 @@ impl Screen for ExplorerScreen {
                     }
                     ExplorerMessage::ChangeFilteredText(new_text) => {
                         screen.filtered_text = new_text;
+                        screen.rebuild_filter();
                         let scroll = Box::new(operation::scope(
                             advanced::widget::Id::new(
                                 MAIN_EXPLORER_CONTAINER_ID,
@@ -429,132 +1645,952 @@ impl Screen for ExplorerScreen {
                         scroll.finish();
                         Task::done(wrap![ExplorerMessage::JumpToPage(0, 0)])
                     }
-                    ExplorerMessage::ToggleFilter(status) => {
-                        screen.filter_opened = status;
-                        let scroll = Box::new(
-                            operation::scrollable::snap_to::<Message>(
-                                advanced::widget::Id::new(
-                                    MAIN_EXPLORER_SCROLLABLE_ID,
-                                ),
-                                scrollable::RelativeOffset { x: 0.0, y: 0.0 },
+                    ExplorerMessage::ToggleFilter(status) => {
+                        screen.filter_opened = status;
+                        let scroll = Box::new(
+                            operation::scrollable::snap_to::<Message>(
+                                advanced::widget::Id::new(
+                                    MAIN_EXPLORER_SCROLLABLE_ID,
+                                ),
+                                scrollable::RelativeOffset { x: 0.0, y: 0.0 },
+                            ),
+                        );
+                        scroll.finish();
+                        Task::none()
+                    }
+                    ExplorerMessage::JumpToPage(page, displayed_dmis_count) => {
+                        if page
+                            <= displayed_dmis_count / screen.settings.page_size
+                        {
+                            screen.current_page = page;
+                        }
+
+                        Task::none()
+                    }
+                    ExplorerMessage::ToggleSettingsVisibility(visible) => {
+                        screen.settings_visible = visible;
+                        Task::none()
+                    }
+                    ExplorerMessage::SaveSettings => {
+                        app.config.explorer_settings = screen.settings.clone();
+                        app.config.save();
+                        Task::done(popup(
+                            "Saved settings to Config.toml",
+                            Some("Saved"),
+                            ToastLevel::Success,
+                        ))
+                    }
+                    ExplorerMessage::LoadSettings => {
+                        screen.settings = app.config.explorer_settings.clone();
+                        Task::done(popup(
+                            "Loaded settings from the in-memory config",
+                            Some("Loaded"),
+                            ToastLevel::Success,
+                        ))
+                    }
+                    ExplorerMessage::ResetSettings => {
+                        screen.settings = ExplorerSettings::default();
+                        Task::done(popup(
+                            "Settings were reset to default",
+                            Some("Reset"),
+                            ToastLevel::Success,
+                        ))
+                    }
+                    ExplorerMessage::ChangePageSize(page_size) => {
+                        screen.settings.page_size = page_size;
+                        Task::done(wrap![ExplorerMessage::JumpToPage(0, 0)])
+                    }
+                    ExplorerMessage::ChangeDelimeter(delimeter) => {
+                        screen.settings.delimeter = delimeter;
+                        Task::none()
+                    }
+                    ExplorerMessage::OpenInViewer(path_buf) => Task::batch([
+                        Task::done(Message::ChangeScreen(Screens::Viewer)),
+                        Task::done(wrap![ViewerMessage::ChangeDMIPath(
+                            path_buf.to_string_lossy().into()
+                        )])
+                        .chain(Task::done(wrap![ViewerMessage::LoadDMI])),
+                    ]),
+                    ExplorerMessage::ChangeRecursionDepth(depth) => {
+                        screen.settings.recursion_depth = depth;
+                        Task::none()
+                    }
+                    ExplorerMessage::CopyFile(dmi_path) => {
+                        let clipboard = match &mut app.clipboard {
+                            Some(clipboard) => clipboard,
+                            None => {
+                                let clipboard_creation_result =
+                                    Clipboard::new();
+                                match clipboard_creation_result {
+                                    Ok(clipboard) => {
+                                        app.clipboard = Some(clipboard);
+                                        app.clipboard.as_mut().unwrap()
+                                    }
+                                    Err(err) => {
+                                        error!(
+                                            "Failed to obtain access to the clipboard: {}",
+                                            err
+                                        );
+                                        return Task::done(popup(
+                                            format!(
+                                                "Failed to obtain access to the clipboard: {}",
+                                                err
+                                            ),
+                                            Some("Clipoard inaccessible"),
+                                            ToastLevel::Error,
+                                        ));
+                                    }
+                                }
+                            }
+                        };
+
+                        if let Err(err) =
+                            clipboard.set().file_list(&[&dmi_path])
+                        {
+                            error!(
+                                "Failed to copy the DMI file path {} to the clipboard: {}",
+                                dmi_path.to_string_lossy(),
+                                err
+                            );
+                            return Task::done(popup(
+                                format!(
+                                    "Failed to copy the DMI file path {} to the clipboard: {}",
+                                    dmi_path.to_string_lossy(),
+                                    err
+                                ),
+                                Some("Failed"),
+                                ToastLevel::Error,
+                            ));
+                        }
+                        Task::done(popup(
+                            "DMI file path was copied to the clipboard",
+                            Some("Copied"),
+                            ToastLevel::Success,
+                        ))
+                    }
+                    ExplorerMessage::ExportAll(dmi_path) => {
+                        let Some(output_dir) = FileDialog::new()
+                            .set_title("Choose a folder to export states into")
+                            .set_directory("/")
+                            .pick_folder()
+                        else {
+                            return Task::none();
+                        };
+                        let name_template =
+                            screen.settings.export_name_template.clone();
+                        Task::future(async move {
+                            let export_start = Instant::now();
+                            let dmi_name = dmi_path
+                                .file_stem()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned();
+                            let exported = load_dmi(&dmi_path)
+                                .and_then(|icon| {
+                                    export_dmi(
+                                        &icon,
+                                        &dmi_name,
+                                        &output_dir,
+                                        &name_template,
+                                    )
+                                })
+                                .map(|summary| (output_dir, summary))
+                                .map_err(|err| {
+                                    format!(
+                                        "{}: {}",
+                                        dmi_path.to_string_lossy(),
+                                        err
+                                    )
+                                });
+                            debug!(
+                                "DMI {} exported in {}ms",
+                                dmi_path.to_string_lossy(),
+                                export_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::Exported(exported)]
+                        })
+                    }
+                    ExplorerMessage::Exported(result) => match result {
+                        Ok((output_dir, summary)) => Task::done(popup(
+                            format!(
+                                "Exported {} images and {} GIFs into {}",
+                                summary.images,
+                                summary.gifs,
+                                output_dir.to_string_lossy()
+                            ),
+                            Some("Exported"),
+                            ToastLevel::Success,
+                        )),
+                        Err(err) => {
+                            error!("Failed to export DMI {}", err);
+                            Task::done(popup(
+                                format!("Failed to export DMI {}", err),
+                                Some("Export failed"),
+                                ToastLevel::Error,
+                            ))
+                        }
+                    },
+                    ExplorerMessage::ChangeExportNameTemplate(template) => {
+                        screen.settings.export_name_template = template;
+                        Task::none()
+                    }
+                    ExplorerMessage::ChangeUnusedAllowList(patterns) => {
+                        screen.settings.unused_allow_list = patterns;
+                        Task::none()
+                    }
+                    ExplorerMessage::ChangeSort(sort) => {
+                        if screen.settings.sort == sort {
+                            screen.settings.sort_descending =
+                                !screen.settings.sort_descending;
+                        } else {
+                            screen.settings.sort = sort;
+                            screen.settings.sort_descending =
+                                sort != ExplorerSort::Path;
+                        }
+                        screen.current_page = 0;
+                        Task::none()
+                    }
+                    ExplorerMessage::ChangeProjectScope(project_scope) => {
+                        screen.settings.project_scope = project_scope;
+                        Task::none()
+                    }
+                    ExplorerMessage::ToggleStateSelection(
+                        dmi_path,
+                        state,
+                        selected,
+                    ) => {
+                        let selection =
+                            screen.selected_states.entry(dmi_path).or_default();
+                        if selected {
+                            selection.insert(state);
+                        } else {
+                            selection.remove(&state);
+                        }
+                        Task::none()
+                    }
+                    ExplorerMessage::MergeWith(dmi_path) => {
+                        let directory = dmi_path
+                            .parent()
+                            .map(|parent| parent.to_path_buf())
+                            .unwrap_or_else(|| "/".into());
+                        let Some(others) = FileDialog::new()
+                            .set_title("Choose DMIs to merge into this one")
+                            .set_directory(&directory)
+                            .add_filter("dmi", &["dmi"])
+                            .pick_files()
+                        else {
+                            return Task::none();
+                        };
+                        let Some(output) = FileDialog::new()
+                            .set_title("Save the merged DMI as")
+                            .set_directory(&directory)
+                            .add_filter("dmi", &["dmi"])
+                            .save_file()
+                        else {
+                            return Task::none();
+                        };
+                        let policy = screen.settings.merge_conflict_policy;
+                        Task::future(async move {
+                            let merge_start = Instant::now();
+                            let mut inputs = vec![dmi_path];
+                            inputs.extend(others);
+                            let merged =
+                                merge_dmi_files(&inputs, &output, policy)
+                                    .map(|conflicts| (output, conflicts))
+                                    .map_err(|err| err.to_string());
+                            debug!(
+                                "{} DMIs merged in {}ms",
+                                inputs.len(),
+                                merge_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::Merged(merged)]
+                        })
+                    }
+                    ExplorerMessage::Merged(result) => match result {
+                        Ok((output, conflicts)) => {
+                            for conflict in &conflicts {
+                                debug!("Merge conflict: {}", conflict);
+                            }
+                            Task::batch([
+                                Task::done(wrap![ExplorerMessage::LoadDMI(
+                                    output.clone()
+                                )]),
+                                Task::done(popup(
+                                    format!(
+                                        "Merged into {}, {} conflicting states resolved",
+                                        output.to_string_lossy(),
+                                        conflicts.len()
+                                    ),
+                                    Some("Merged"),
+                                    ToastLevel::Success,
+                                )),
+                            ])
+                        }
+                        Err(err) => {
+                            error!("Failed to merge DMIs: {}", err);
+                            Task::done(popup(
+                                format!("Failed to merge DMIs: {}", err),
+                                Some("Merge failed"),
+                                ToastLevel::Error,
+                            ))
+                        }
+                    },
+                    ExplorerMessage::SplitSelected(dmi_path) => {
+                        let states: Vec<String> = screen
+                            .selected_states
+                            .get(&dmi_path)
+                            .map(|selection| {
+                                selection.iter().cloned().collect()
+                            })
+                            .unwrap_or_default();
+                        if states.is_empty() {
+                            return Task::done(popup(
+                                "Check the states to split off first",
+                                Some("Nothing selected"),
+                                ToastLevel::Warning,
+                            ));
+                        }
+                        let Some(output) = FileDialog::new()
+                            .set_title("Save the selected states as")
+                            .set_directory(
+                                dmi_path
+                                    .parent()
+                                    .map(|parent| parent.to_path_buf())
+                                    .unwrap_or_else(|| "/".into()),
+                            )
+                            .add_filter("dmi", &["dmi"])
+                            .save_file()
+                        else {
+                            return Task::none();
+                        };
+                        Task::future(async move {
+                            let split =
+                                split_dmi_file(&dmi_path, &states, &output)
+                                    .map(|_| (dmi_path, output))
+                                    .map_err(|err| err.to_string());
+                            wrap![ExplorerMessage::Split(split)]
+                        })
+                    }
+                    ExplorerMessage::Split(result) => match result {
+                        Ok((dmi_path, output)) => {
+                            screen.selected_states.remove(&dmi_path);
+                            Task::batch([
+                                Task::done(wrap![ExplorerMessage::LoadDMI(
+                                    dmi_path.clone()
+                                )]),
+                                Task::done(wrap![ExplorerMessage::LoadDMI(
+                                    output.clone()
+                                )]),
+                                Task::done(popup(
+                                    format!(
+                                        "Selected states of {} were moved into {}",
+                                        dmi_path.to_string_lossy(),
+                                        output.to_string_lossy()
+                                    ),
+                                    Some("Split"),
+                                    ToastLevel::Success,
+                                )),
+                            ])
+                        }
+                        Err(err) => {
+                            error!("Failed to split DMI: {}", err);
+                            Task::done(popup(
+                                format!("Failed to split DMI: {}", err),
+                                Some("Split failed"),
+                                ToastLevel::Error,
+                            ))
+                        }
+                    },
+                    ExplorerMessage::LintAll => {
+                        screen.linting = true;
+                        let paths: Vec<PathBuf> = screen
+                            .parsed_dmis
+                            .keys()
+                            .filter(|path| screen.in_scope(path))
+                            .cloned()
+                            .collect();
+                        Task::future(async move {
+                            let lint_start = Instant::now();
+                            let report: LintReport = paths
+                                .into_iter()
+                                .map(|path| {
+                                    let issues = load_dmi(&path)
+                                        .map(|icon| {
+                                            lint_icon(
+                                                &icon,
+                                                DEFAULT_MAX_ICON_SIZE,
+                                            )
+                                        })
+                                        .map_err(|err| err.to_string());
+                                    (path, issues)
+                                })
+                                .collect();
+                            debug!(
+                                "{} DMIs linted in {}ms",
+                                report.len(),
+                                lint_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::Linted(report)]
+                        })
+                    }
+                    ExplorerMessage::Linted(report) => {
+                        screen.linting = false;
+                        let (errors, warnings) = report
+                            .values()
+                            .filter_map(|issues| issues.as_ref().ok())
+                            .map(|issues| count_issues(issues))
+                            .fold((0, 0), |total, count| {
+                                (total.0 + count.0, total.1 + count.1)
+                            });
+                        screen.lint_report = report;
+                        screen.lint_report_visible = true;
+                        Task::done(popup(
+                            format!(
+                                "Found {} errors and {} warnings",
+                                errors, warnings
+                            ),
+                            Some("Linted"),
+                            if errors > 0 {
+                                ToastLevel::Warning
+                            } else {
+                                ToastLevel::Success
+                            },
+                        ))
+                    }
+                    ExplorerMessage::OpenProject(browse_dirs) => {
+                        let picked = if browse_dirs {
+                            FileDialog::new()
+                                .set_title("Open BYOND project folder")
+                                .set_directory("/")
+                                .pick_folder()
+                        } else {
+                            FileDialog::new()
+                                .set_title("Open BYOND environment")
+                                .set_directory("/")
+                                .add_filter("dme", &["dme"])
+                                .pick_file()
+                        };
+                        match picked {
+                            Some(root) => {
+                                Task::done(wrap![ExplorerMessage::ScanProject(
+                                    root
+                                )])
+                            }
+                            None => Task::none(),
+                        }
+                    }
+                    ExplorerMessage::RescanProject => {
+                        match screen.index.project.clone() {
+                            Some(root) => {
+                                Task::done(wrap![ExplorerMessage::ScanProject(
+                                    root
+                                )])
+                            }
+                            None => Task::none(),
+                        }
+                    }
+                    ExplorerMessage::ScanProject(root) => {
+                        screen.scanning_project = true;
+                        Task::future(async move {
+                            let scan_start = Instant::now();
+                            let scanned = DMProject::open(&root)
+                                .map(|mut project| {
+                                    let references =
+                                        DMReferences::scan(&project);
+                                    project.include_referenced(&references);
+                                    (project, Arc::new(references))
+                                })
+                                .map_err(|err| err.to_string());
+                            debug!(
+                                "Project {} scanned in {}ms",
+                                root.to_string_lossy(),
+                                scan_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::ProjectScanned(scanned)]
+                        })
+                    }
+                    ExplorerMessage::ProjectScanned(scanned) => {
+                        screen.scanning_project = false;
+                        match scanned {
+                            Ok((project, references)) => {
+                                let scanned = popup(
+                                    format!(
+                                        "Found {} icon references in {} files",
+                                        references.references.len(),
+                                        project.sources.len()
+                                    ),
+                                    Some("Project scanned"),
+                                    ToastLevel::Success,
+                                );
+                                if screen.index.project.as_deref()
+                                    != Some(project.path())
+                                {
+                                    screen.index.project =
+                                        Some(project.path().to_path_buf());
+                                    screen.index_modified = true;
+                                }
+                                // Project DMIs are searched in the Explorer
+                                let mut tasks: Vec<Task<Message>> = project
+                                    .dmis
+                                    .iter()
+                                    .filter(|path| {
+                                        !screen.parsed_dmis.contains_key(*path)
+                                            && !screen
+                                                .loading_dmis
+                                                .contains(*path)
+                                            && !screen
+                                                .index
+                                                .removed
+                                                .contains(*path)
+                                    })
+                                    .map(|path| {
+                                        Task::done(wrap![
+                                            ExplorerMessage::LoadDMI(
+                                                path.clone()
+                                            )
+                                        ])
+                                    })
+                                    .collect();
+                                screen.project = Some(project);
+                                screen.references = Some(references);
+                                tasks.push(
+                                    screen.save_index(
+                                        app.config.data_dir.clone(),
+                                    ),
+                                );
+                                tasks.push(Task::done(scanned));
+                                Task::batch(tasks)
+                            }
+                            Err(err) => {
+                                error!("Failed to scan the project: {}", err);
+                                Task::done(popup(
+                                    err,
+                                    Some("Failed to scan the project"),
+                                    ToastLevel::Error,
+                                ))
+                            }
+                        }
+                    }
+                    ExplorerMessage::FindUnused => {
+                        let (Some(project), Some(references)) =
+                            (screen.project.clone(), screen.references.clone())
+                        else {
+                            return Task::none();
+                        };
+                        screen.finding_unused = true;
+                        let entries = screen.index.entries.clone();
+                        let allow_list =
+                            screen.settings.unused_allow_list.clone();
+                        Task::future(async move {
+                            let search_start = Instant::now();
+                            let report = parse_allow_list(&allow_list)
+                                .map(|allowed| {
+                                    find_unused(
+                                        &project,
+                                        &references,
+                                        &entries,
+                                        &allowed,
+                                    )
+                                })
+                                .map_err(|err| {
+                                    format!("Invalid allow-list: {}", err)
+                                });
+                            debug!(
+                                "Unused states of {} DMIs found in {}ms",
+                                project.dmis.len(),
+                                search_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::UnusedFound(report)]
+                        })
+                    }
+                    ExplorerMessage::UnusedFound(report) => {
+                        screen.finding_unused = false;
+                        match report {
+                            Ok(report) => {
+                                let found = popup(
+                                    format!(
+                                        "{} of {} states are never referenced",
+                                        report.states.len(),
+                                        report.checked_states
+                                    ),
+                                    Some("Unused states found"),
+                                    if report.states.is_empty() {
+                                        ToastLevel::Success
+                                    } else {
+                                        ToastLevel::Info
+                                    },
+                                );
+                                screen.unused_report = Some(report);
+                                screen.unused_report_visible = true;
+                                Task::done(found)
+                            }
+                            Err(err) => Task::done(popup(
+                                err,
+                                Some("Failed to find unused states"),
+                                ToastLevel::Error,
+                            )),
+                        }
+                    }
+                    ExplorerMessage::ToggleUnusedReport(visible) => {
+                        screen.unused_report_visible = visible;
+                        Task::none()
+                    }
+                    ExplorerMessage::ExportUnusedReport => {
+                        let Some(report) = screen.unused_report.clone() else {
+                            return Task::none();
+                        };
+                        let Some(path) = FileDialog::new()
+                            .set_title("Export unused states report")
+                            .add_filter("text", &["txt"])
+                            .add_filter("csv", &["csv"])
+                            .set_file_name("unused_states.txt")
+                            .save_file()
+                        else {
+                            return Task::none();
+                        };
+                        Task::future(async move {
+                            let content = if path.extension()
+                                == Some(OsStr::new("csv"))
+                            {
+                                report.to_csv()
+                            } else {
+                                report.to_text()
+                            };
+                            let exported = fs::write(&path, content)
+                                .map_err(|err| err.to_string())
+                                .map(|_| path);
+                            wrap![ExplorerMessage::UnusedReportExported(
+                                exported
+                            )]
+                        })
+                    }
+                    ExplorerMessage::UnusedReportExported(result) => {
+                        match result {
+                            Ok(path) => Task::done(popup(
+                                format!(
+                                    "Report was saved to {}",
+                                    path.to_string_lossy()
+                                ),
+                                Some("Exported"),
+                                ToastLevel::Success,
+                            )),
+                            Err(err) => {
+                                error!(
+                                    "Failed to export unused states report: {}",
+                                    err
+                                );
+                                Task::done(popup(
+                                    format!(
+                                        "Failed to export the report: {}",
+                                        err
+                                    ),
+                                    Some("Export failed"),
+                                    ToastLevel::Error,
+                                ))
+                            }
+                        }
+                    }
+                    ExplorerMessage::FindMissing => {
+                        let (Some(root), Some(references)) = (
+                            screen
+                                .project
+                                .as_ref()
+                                .map(|project| project.root.clone()),
+                            screen.references.clone(),
+                        ) else {
+                            return Task::none();
+                        };
+                        screen.finding_missing = true;
+                        let entries = screen.index.entries.clone();
+                        Task::future(async move {
+                            let search_start = Instant::now();
+                            let report =
+                                find_missing(&references, &root, &entries);
+                            debug!(
+                                "{} references checked in {}ms",
+                                report.checked,
+                                search_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::MissingFound(report)]
+                        })
+                    }
+                    ExplorerMessage::MissingFound(report) => {
+                        screen.finding_missing = false;
+                        let found = popup(
+                            format!(
+                                "{} of {} references point to missing DMIs \
+                                 or states",
+                                report.missing.len(),
+                                report.checked
+                            ),
+                            Some("References checked"),
+                            if report.missing.is_empty() {
+                                ToastLevel::Success
+                            } else {
+                                ToastLevel::Warning
+                            },
+                        );
+                        screen.missing_report = Some(report);
+                        screen.missing_report_visible = true;
+                        Task::done(found)
+                    }
+                    ExplorerMessage::ToggleMissingReport(visible) => {
+                        screen.missing_report_visible = visible;
+                        Task::none()
+                    }
+                    ExplorerMessage::ExportIndex(only_found) => {
+                        let Some(path) = FileDialog::new()
+                            .set_title("Export Explorer index")
+                            .add_filter("json", &["json"])
+                            .add_filter("csv", &["csv"])
+                            .set_file_name("explorer_index.json")
+                            .save_file()
+                        else {
+                            return Task::none();
+                        };
+                        let exported: Vec<ExportedDMI> = if only_found {
+                            screen
+                                .found_dmis()
+                                .into_iter()
+                                .filter_map(|(dmi, _, states_found, _)| {
+                                    let entry =
+                                        screen.index.entries.get(dmi)?;
+                                    let found: HashSet<&String> = states_found
+                                        .into_iter()
+                                        .map(|(state, _)| state)
+                                        .collect();
+                                    Some(export_dmi_entry(
+                                        dmi,
+                                        entry,
+                                        |state| found.contains(&state.name),
+                                    ))
+                                })
+                                .collect()
+                        } else {
+                            screen
+                                .index
+                                .entries
+                                .iter()
+                                .filter(|(dmi, _)| screen.in_scope(dmi))
+                                .map(|(dmi, entry)| {
+                                    export_dmi_entry(dmi, entry, |_| true)
+                                })
+                                .collect()
+                        };
+                        Task::future(async move {
+                            let content = if path.extension()
+                                == Some(OsStr::new("csv"))
+                            {
+                                Ok(exported_dmis_to_csv(&exported).into_bytes())
+                            } else {
+                                serde_json::to_vec_pretty(&exported)
+                                    .map_err(|err| err.to_string())
+                            };
+                            let exported = content
+                                .and_then(|content| {
+                                    fs::write(&path, content)
+                                        .map_err(|err| err.to_string())
+                                })
+                                .map(|_| path);
+                            wrap![ExplorerMessage::IndexExported(exported)]
+                        })
+                    }
+                    ExplorerMessage::IndexExported(result) => match result {
+                        Ok(path) => Task::done(popup(
+                            format!(
+                                "Index was saved to {}",
+                                path.to_string_lossy()
+                            ),
+                            Some("Exported"),
+                            ToastLevel::Success,
+                        )),
+                        Err(err) => {
+                            error!("Failed to export Explorer index: {}", err);
+                            Task::done(popup(
+                                format!("Failed to export the index: {}", err),
+                                Some("Export failed"),
+                                ToastLevel::Error,
+                            ))
+                        }
+                    },
+                    ExplorerMessage::FindDuplicates => {
+                        screen.finding_duplicates = true;
+                        let paths: Vec<PathBuf> = screen
+                            .parsed_dmis
+                            .keys()
+                            .filter(|path| screen.in_scope(path))
+                            .cloned()
+                            .collect();
+                        Task::future(async move {
+                            let search_start = Instant::now();
+                            let report = find_duplicates(&paths);
+                            debug!(
+                                "Duplicates of {} DMIs found in {}ms",
+                                paths.len(),
+                                search_start.elapsed().as_millis()
+                            );
+                            wrap![ExplorerMessage::DuplicatesFound(report)]
+                        })
+                    }
+                    ExplorerMessage::DuplicatesFound(report) => {
+                        screen.finding_duplicates = false;
+                        let found = popup(
+                            format!(
+                                "Found {} duplicated states and {} frames \
+                                 repeated in different states",
+                                report.states.len(),
+                                report.frames.len()
                             ),
+                            Some("Duplicates found"),
+                            if report.states.is_empty()
+                                && report.frames.is_empty()
+                            {
+                                ToastLevel::Success
+                            } else {
+                                ToastLevel::Info
+                            },
                         );
-                        scroll.finish();
-                        Task::none()
+                        screen.duplicates_report = Some(report);
+                        screen.duplicates_report_visible = true;
+                        Task::done(found)
                     }
-                    ExplorerMessage::JumpToPage(page, displayed_dmis_count) => {
-                        if page
-                            <= displayed_dmis_count / screen.settings.page_size
-                        {
-                            screen.current_page = page;
-                        }
-
+                    ExplorerMessage::ToggleDuplicatesReport(visible) => {
+                        screen.duplicates_report_visible = visible;
                         Task::none()
                     }
-                    ExplorerMessage::ToggleSettingsVisibility(visible) => {
-                        screen.settings_visible = visible;
-                        Task::none()
+                    ExplorerMessage::ExportDuplicatesReport => {
+                        let Some(report) = screen.duplicates_report.clone()
+                        else {
+                            return Task::none();
+                        };
+                        let Some(path) = FileDialog::new()
+                            .set_title("Export duplicates report")
+                            .add_filter("json", &["json"])
+                            .set_file_name("duplicates.json")
+                            .save_file()
+                        else {
+                            return Task::none();
+                        };
+                        Task::future(async move {
+                            let exported = serde_json::to_vec_pretty(&report)
+                                .map_err(|err| err.to_string())
+                                .and_then(|json| {
+                                    fs::write(&path, json)
+                                        .map_err(|err| err.to_string())
+                                })
+                                .map(|_| path);
+                            wrap![ExplorerMessage::DuplicatesReportExported(
+                                exported
+                            )]
+                        })
                     }
-                    ExplorerMessage::SaveSettings => {
-                        app.config.explorer_settings = screen.settings.clone();
-                        app.config.save();
-                        Task::done(popup(
-                            "Saved settings to Config.toml",
-                            Some("Saved"),
-                            ToastLevel::Success,
-                        ))
+                    ExplorerMessage::DuplicatesReportExported(result) => {
+                        match result {
+                            Ok(path) => Task::done(popup(
+                                format!(
+                                    "Report was saved to {}",
+                                    path.to_string_lossy()
+                                ),
+                                Some("Exported"),
+                                ToastLevel::Success,
+                            )),
+                            Err(err) => {
+                                error!(
+                                    "Failed to export duplicates report: {}",
+                                    err
+                                );
+                                Task::done(popup(
+                                    format!(
+                                        "Failed to export the report: {}",
+                                        err
+                                    ),
+                                    Some("Export failed"),
+                                    ToastLevel::Error,
+                                ))
+                            }
+                        }
                     }
-                    ExplorerMessage::LoadSettings => {
-                        screen.settings = app.config.explorer_settings.clone();
-                        Task::done(popup(
-                            "Loaded settings from the in-memory config",
-                            Some("Loaded"),
-                            ToastLevel::Success,
-                        ))
+                    ExplorerMessage::FindSimilar(hash, looked_for) => {
+                        if screen.index.entries.is_empty() {
+                            return Task::done(popup(
+                                "Load some DMIs into the Explorer to compare \
+                                 with",
+                                Some("Nothing to compare with"),
+                                ToastLevel::Info,
+                            ));
+                        }
+                        let similar = find_similar(
+                            hash,
+                            screen
+                                .index
+                                .entries
+                                .iter()
+                                .filter(|(path, _)| screen.in_scope(path)),
+                            SIMILAR_STATES_LIMIT,
+                        );
+                        screen.similar_states = Some((looked_for, similar));
+                        Task::none()
                     }
-                    ExplorerMessage::ResetSettings => {
-                        screen.settings = ExplorerSettings::default();
-                        Task::done(popup(
-                            "Settings were reset to default",
-                            Some("Reset"),
-                            ToastLevel::Success,
-                        ))
+                    ExplorerMessage::FindSimilarToClipboard => {
+                        let image =
+                            Clipboard::new()
+                                .and_then(|mut clipboard| clipboard.get_image())
+                                .map_err(|err| err.to_string())
+                                .and_then(|image| {
+                                    RgbaImage::from_raw(
+                                        image.width as u32,
+                                        image.height as u32,
+                                        image.bytes.into_owned(),
+                                    )
+                                    .ok_or_else(|| {
+                                        "image size does not match its data"
+                                            .to_string()
+                                    })
+                                });
+                        match image {
+                            Ok(image) => {
+                                Task::done(wrap![ExplorerMessage::FindSimilar(
+                                    perceptual_hash(&DynamicImage::from(image)),
+                                    "the clipboard image".to_string()
+                                )])
+                            }
+                            Err(err) => Task::done(popup(
+                                format!(
+                                    "Failed to get an image from the \
+                                     clipboard: {}",
+                                    err
+                                ),
+                                Some("No image"),
+                                ToastLevel::Warning,
+                            )),
+                        }
                     }
-                    ExplorerMessage::ChangePageSize(page_size) => {
-                        screen.settings.page_size = page_size;
-                        Task::done(wrap![ExplorerMessage::JumpToPage(0, 0)])
+                    ExplorerMessage::HideSimilar => {
+                        screen.similar_states = None;
+                        Task::none()
                     }
-                    ExplorerMessage::ChangeDelimeter(delimeter) => {
-                        screen.settings.delimeter = delimeter;
+                    ExplorerMessage::ToggleLintReport(visible) => {
+                        screen.lint_report_visible = visible;
                         Task::none()
                     }
-                    ExplorerMessage::OpenInViewer(path_buf) => Task::batch([
-                        Task::done(Message::ChangeScreen(Screens::Viewer)),
-                        Task::done(wrap![ViewerMessage::ChangeDMIPath(
-                            path_buf.to_string_lossy().into()
-                        )])
-                        .chain(Task::done(wrap![ViewerMessage::LoadDMI])),
-                    ]),
-                    ExplorerMessage::ChangeRecursionDepth(depth) => {
-                        screen.settings.recursion_depth = depth;
+                    ExplorerMessage::ChangeMergeConflictPolicy(policy) => {
+                        screen.settings.merge_conflict_policy = policy;
                         Task::none()
                     }
-                    ExplorerMessage::CopyFile(dmi_path) => {
-                        let clipboard = match &mut app.clipboard {
-                            Some(clipboard) => clipboard,
-                            None => {
-                                let clipboard_creation_result =
-                                    Clipboard::new();
-                                match clipboard_creation_result {
-                                    Ok(clipboard) => {
-                                        app.clipboard = Some(clipboard);
-                                        app.clipboard.as_mut().unwrap()
-                                    }
-                                    Err(err) => {
-                                        error!(
-                                            "Failed to obtain access to the clipboard: {}",
-                                            err
-                                        );
-                                        return Task::done(popup(
-                                            format!(
-                                                "Failed to obtain access to the clipboard: {}",
-                                                err
-                                            ),
-                                            Some("Clipoard inaccessible"),
-                                            ToastLevel::Error,
-                                        ));
-                                    }
-                                }
-                            }
-                        };
-
-                        if let Err(err) =
-                            clipboard.set().file_list(&[&dmi_path])
-                        {
-                            error!(
-                                "Failed to copy the DMI file path {} to the clipboard: {}",
-                                dmi_path.to_string_lossy(),
-                                err
-                            );
-                            return Task::done(popup(
-                                format!(
-                                    "Failed to copy the DMI file path {} to the clipboard: {}",
-                                    dmi_path.to_string_lossy(),
-                                    err
-                                ),
-                                Some("Failed"),
-                                ToastLevel::Error,
-                            ));
+                    ExplorerMessage::ChangeMatchMode(mode) => {
+                        if mode != screen.settings.match_mode {
+                            screen.settings.match_mode = mode;
+                            screen.rebuild_filter();
+                            return Task::done(wrap![
+                                ExplorerMessage::JumpToPage(0, 0)
+                            ]);
                         }
-                        Task::done(popup(
-                            "DMI file path was copied to the clipboard",
-                            Some("Copied"),
-                            ToastLevel::Success,
-                        ))
+                        Task::none()
                     }
                     ExplorerMessage::ChangeSearchFilterMode(mode) => {
                         if mode != screen.settings.search_filter_mode {
                             screen.settings.search_filter_mode = mode;
+                            screen.rebuild_filter();
                             return Task::done(wrap![
                                 ExplorerMessage::JumpToPage(0, 0)
                             ]);
@@ -671,6 +2707,58 @@ impl Screen for ExplorerScreen {
             .align_y(Vertical::Center)
             .spacing(5);
 
+            let export_template_picker = row![
+                icon::save(),
+                text("File Name Template For Export All: "),
+                container(
+                    text_input(
+                        "Enter the template...",
+                        &screen.settings.export_name_template
+                    )
+                    .on_input(|input| {
+                        wrap![ExplorerMessage::ChangeExportNameTemplate(input)]
+                    })
+                    .on_paste(|input| {
+                        wrap![ExplorerMessage::ChangeExportNameTemplate(input)]
+                    })
+                    .width(300)
+                    .padding(5),
+                ),
+                text("({dmi}, {index}, {state}, {dir}, {frame})"),
+            ]
+            .align_y(Vertical::Center)
+            .spacing(5);
+
+            let project_scope_toggle = checkbox(
+                "Only Show and Check the Project DMIs",
+                screen.settings.project_scope,
+            )
+            .on_toggle(|project_scope| {
+                wrap![ExplorerMessage::ChangeProjectScope(project_scope)]
+            });
+
+            let unused_allow_list_picker = row![
+                icon::trash(),
+                text("Unused States Allow-List: "),
+                container(
+                    text_input(
+                        "Enter the globs...",
+                        &screen.settings.unused_allow_list
+                    )
+                    .on_input(|input| {
+                        wrap![ExplorerMessage::ChangeUnusedAllowList(input)]
+                    })
+                    .on_paste(|input| {
+                        wrap![ExplorerMessage::ChangeUnusedAllowList(input)]
+                    })
+                    .width(300)
+                    .padding(5),
+                ),
+                text("(separated by spaces, like walk_* *_open)"),
+            ]
+            .align_y(Vertical::Center)
+            .spacing(5);
+
             let recusion_depth_picker = row![
                 icon::folder(),
                 text("Recursion Depth: "),
@@ -690,6 +2778,7 @@ impl Screen for ExplorerScreen {
                 SearchFilterMode::DmiAndState,
                 SearchFilterMode::State,
                 SearchFilterMode::Dmi,
+                SearchFilterMode::Query,
             ]
             .iter()
             .map(|mode| {
@@ -710,6 +2799,36 @@ impl Screen for ExplorerScreen {
             ]
             .spacing(5);
 
+            let mut merge_policy_picker: Column<Message> = [
+                MergeConflictPolicy::Rename,
+                MergeConflictPolicy::KeepFirst,
+                MergeConflictPolicy::KeepLast,
+                MergeConflictPolicy::Fail,
+            ]
+            .iter()
+            .map(|policy| {
+                radio(
+                    policy.to_string(),
+                    policy,
+                    Some(&screen.settings.merge_conflict_policy),
+                    |policy| {
+                        wrap![ExplorerMessage::ChangeMergeConflictPolicy(
+                            *policy
+                        )]
+                    },
+                )
+                .into()
+            })
+            .collect();
+            merge_policy_picker = column![
+                row![
+                    icon::iconfile(),
+                    bold_text(" On state name conflict when merging:")
+                ],
+                merge_policy_picker.spacing(5)
+            ]
+            .spacing(5);
+
             let save_settings = button(row![icon::save(), " Save Settings"])
                 .on_press(wrap![ExplorerMessage::SaveSettings])
                 .style(button::success);
@@ -724,15 +2843,102 @@ impl Screen for ExplorerScreen {
             settings_bar = column![
                 page_size_picker,
                 delimeter_picker,
+                export_template_picker,
+                unused_allow_list_picker,
+                project_scope_toggle,
                 recusion_depth_picker,
                 filter_mode_picker,
+                merge_policy_picker,
                 row![save_settings, load_settings, reset_settings].spacing(10)
             ]
             .spacing(10);
         }
 
-        let output_controls =
-            row![button_search, clear_all].padding(5).spacing(5);
+        let lint_all = button(row![
+            icon::search(),
+            text(if screen.linting {
+                " Linting..."
+            } else {
+                " Lint All"
+            })
+        ])
+        .on_press_maybe(
+            (!screen.linting && !screen.parsed_dmis.is_empty())
+                .then_some(wrap![ExplorerMessage::LintAll]),
+        )
+        .style(button::secondary);
+        let lint_report_toggle = button(text(if screen.lint_report_visible {
+            "Hide Lint Report"
+        } else {
+            "Show Lint Report"
+        }))
+        .on_press_maybe((!screen.lint_report.is_empty()).then_some(wrap![
+            ExplorerMessage::ToggleLintReport(!screen.lint_report_visible)
+        ]))
+        .style(button::secondary);
+
+        let find_duplicates = button(row![
+            icon::file(),
+            text(if screen.finding_duplicates {
+                " Searching..."
+            } else {
+                " Find Duplicates"
+            })
+        ])
+        .on_press_maybe(
+            (!screen.finding_duplicates && !screen.parsed_dmis.is_empty())
+                .then_some(wrap![ExplorerMessage::FindDuplicates]),
+        )
+        .style(button::secondary);
+        let duplicates_report_toggle =
+            button(text(if screen.duplicates_report_visible {
+                "Hide Duplicates"
+            } else {
+                "Show Duplicates"
+            }))
+            .on_press_maybe(screen.duplicates_report.is_some().then_some(
+                wrap![ExplorerMessage::ToggleDuplicatesReport(
+                    !screen.duplicates_report_visible
+                )],
+            ))
+            .style(button::secondary);
+        let find_similar_to_clipboard =
+            button(row![icon::search(), text(" Find Similar to Clipboard")])
+                .on_press(wrap![ExplorerMessage::FindSimilarToClipboard])
+                .style(button::secondary);
+
+        let export_index = button(row![icon::save(), text(" Export Index")])
+            .on_press_maybe(
+                (!screen.index.entries.is_empty())
+                    .then_some(wrap![ExplorerMessage::ExportIndex(false)]),
+            )
+            .style(button::secondary);
+        let filtered =
+            if screen.settings.search_filter_mode == SearchFilterMode::Query {
+                !screen.query.is_empty()
+            } else {
+                !screen.filter.is_empty()
+            };
+        let export_found = button(row![icon::save(), text(" Export Found")])
+            .on_press_maybe(
+                (filtered && !screen.index.entries.is_empty())
+                    .then_some(wrap![ExplorerMessage::ExportIndex(true)]),
+            )
+            .style(button::secondary);
+
+        let output_controls = row![
+            button_search,
+            lint_all,
+            lint_report_toggle,
+            find_duplicates,
+            duplicates_report_toggle,
+            find_similar_to_clipboard,
+            export_index,
+            export_found,
+            clear_all
+        ]
+        .padding(5)
+        .spacing(5);
 
         if !screen.loading_dmis.is_empty() {
             let mut tooltip =
@@ -745,6 +2951,7 @@ impl Screen for ExplorerScreen {
             return container(
                 column![
                     input_controls,
+                    screen.project_view(),
                     settings_bar,
                     container(tooltip)
                         .style(container::bordered_box)
@@ -762,6 +2969,7 @@ impl Screen for ExplorerScreen {
             return container(
                 column![
                     input_controls,
+                    screen.project_view(),
                     settings_bar,
                     container(bold_text(
                         "... or drop your icon files or folders there!"
@@ -777,120 +2985,189 @@ impl Screen for ExplorerScreen {
             .into();
         }
 
+        let search_mode = screen.settings.search_filter_mode;
+        let found_dmis = screen.found_dmis();
+
         let mut parsed_dmis_column: Column<Message> = Column::new();
         let mut displayed_dmis_count: usize = 0;
 
-        for (path, dmi) in &screen.parsed_dmis {
-            let mut dmi_states_column: Column<Message> = Column::new();
-
-            let filter_selected_dmi =
-                path.to_string_lossy().contains(&screen.filtered_text);
-            let mut filter_selected_state = false;
+        for (path, path_found, states_found, _) in found_dmis {
+            displayed_dmis_count += 1;
 
-            for state in dmi {
-                let mut filter_selected_this_state = false;
-                if state.contains(&screen.filtered_text) {
-                    filter_selected_state = true;
-                    filter_selected_this_state = true;
-                }
-                if (filter_selected_dmi
-                    && screen.settings.search_filter_mode
-                        != SearchFilterMode::State)
-                    || (filter_selected_this_state
-                        && screen.settings.search_filter_mode
-                            != SearchFilterMode::Dmi)
-                {
-                    let selected_mark: text::Rich<Message> = if screen
-                        .settings
-                        .search_filter_mode
-                        == SearchFilterMode::Dmi
-                    {
-                        rich_text([span("? ").color(color!(0xcde002)).size(20)])
-                    } else if screen.filtered_text.is_empty() {
-                        rich_text([span("")])
-                    } else if filter_selected_this_state {
-                        rich_text([span("+  ")
-                            .color(color!(0x89fc41))
-                            .size(20)])
-                    } else {
-                        rich_text([span("-  ")
-                            .color(color!(0xfc4144))
-                            .size(20)])
-                    };
-                    dmi_states_column = dmi_states_column.push(row![
-                        row![selected_mark, text!("{}  ", state)],
-                        button(icon::save())
-                            .on_press(wrap![ExplorerMessage::CopyText(
-                                state.clone()
-                            )])
-                            .style(button::secondary)
-                    ])
-                }
-            }
-            if (filter_selected_dmi
-                && screen.settings.search_filter_mode
-                    != SearchFilterMode::State)
-                || (filter_selected_state
-                    && screen.settings.search_filter_mode
-                        != SearchFilterMode::Dmi)
+            if displayed_dmis_count / screen.settings.page_size
+                != screen.current_page
             {
-                displayed_dmis_count += 1;
+                continue;
+            }
 
-                if displayed_dmis_count / screen.settings.page_size
-                    != screen.current_page
-                {
-                    continue;
-                }
+            let mut dmi_states_column: Column<Message> = Column::new();
+            let selection = screen.selected_states.get(path);
+            let dmi_warnings = screen.state_warnings.get(path);
 
-                let selected_mark: text::Rich<Message> = if screen
-                    .settings
-                    .search_filter_mode
-                    == SearchFilterMode::State
+            for (state, state_found) in states_found {
+                let selected_mark: text::Rich<Message> = if search_mode
+                    == SearchFilterMode::Dmi
                 {
                     rich_text([span("? ").color(color!(0xcde002)).size(20)])
-                } else if screen.filtered_text.is_empty() {
+                } else if screen.filter.is_empty() {
                     rich_text([span("")])
-                } else if filter_selected_dmi {
+                } else if state_found.is_some() {
                     rich_text([span("+  ").color(color!(0x89fc41)).size(20)])
                 } else {
                     rich_text([span("-  ").color(color!(0xfc4144)).size(20)])
                 };
-                parsed_dmis_column =
-                    parsed_dmis_column.push(container(column![
-                        row![selected_mark, bold_text(path.to_string_lossy())],
-                        row![
-                            button(row![icon::search(), text(" View")])
-                                .on_press(wrap![ExplorerMessage::OpenInViewer(
-                                    path.clone()
-                                )])
-                                .style(button::success),
-                            button(row![icon::folder(), text(" Copy DMI")])
-                                .on_press(wrap![ExplorerMessage::CopyFile(
-                                    path.clone()
-                                )])
-                                .style(widget::button::success),
-                            button(row![icon::save(), text(" Copy All")])
-                                .on_press(wrap![
-                                    ExplorerMessage::CopyAllDMIText(
-                                        path.clone()
-                                    )
-                                ]),
-                            button(row![icon::save(), text(" Copy Path")])
-                                .on_press(wrap![ExplorerMessage::CopyText(
-                                    path.to_string_lossy().to_string()
-                                )])
-                                .style(button::secondary),
-                            button(row![icon::trash(), text(" Clear")])
-                                .on_press(wrap![ExplorerMessage::RemoveDMI(
-                                    path.clone()
-                                )])
-                                .style(button::danger),
-                        ]
-                        .spacing(8),
-                        dmi_states_column,
-                        Space::with_height(20)
-                    ]));
+                let warnings_badge: Element<Message> = match dmi_warnings
+                    .and_then(|warnings| warnings.get(state))
+                {
+                    Some(warnings) => tooltip(
+                        text!("\u{26A0} {}  ", warnings.len())
+                            .color(color!(0xcde002)),
+                        container(
+                            warnings
+                                .iter()
+                                .map(|warning| text(warning.to_string()).into())
+                                .collect::<Column<Message>>()
+                                .spacing(5),
+                        )
+                        .style(container::bordered_box)
+                        .padding(10),
+                        tooltip::Position::Bottom,
+                    )
+                    .into(),
+                    None => Space::new(0, 0).into(),
+                };
+                let positions = state_found
+                    .as_ref()
+                    .map_or(&[][..], |found| found.positions.as_slice());
+                let (checkbox_path, checkbox_state) =
+                    (path.clone(), state.clone());
+                dmi_states_column = dmi_states_column.push(row![
+                    checkbox(
+                        "",
+                        selection
+                            .is_some_and(|selection| selection.contains(state))
+                    )
+                    .on_toggle(move |selected| {
+                        wrap![ExplorerMessage::ToggleStateSelection(
+                            checkbox_path.clone(),
+                            checkbox_state.clone(),
+                            selected
+                        )]
+                    }),
+                    row![
+                        selected_mark,
+                        highlighted_text(state, positions, false),
+                        text("  "),
+                        warnings_badge
+                    ],
+                    button(icon::save())
+                        .on_press(wrap![ExplorerMessage::CopyText(
+                            state.clone()
+                        )])
+                        .style(button::secondary)
+                ])
             }
+
+            let selected_mark: text::Rich<Message> =
+                if search_mode == SearchFilterMode::State {
+                    rich_text([span("? ").color(color!(0xcde002)).size(20)])
+                } else if screen.filter.is_empty() {
+                    rich_text([span("")])
+                } else if path_found.is_some() {
+                    rich_text([span("+  ").color(color!(0x89fc41)).size(20)])
+                } else {
+                    rich_text([span("-  ").color(color!(0xfc4144)).size(20)])
+                };
+            let path_positions = path_found
+                .as_ref()
+                .map_or(&[][..], |found| found.positions.as_slice());
+            let metadata = screen
+                .index
+                .entries
+                .get(path)
+                .map(|entry| {
+                    let mut metadata = format!(
+                        "{}x{}, {} states, {} frames, {}",
+                        entry.width,
+                        entry.height,
+                        entry.states.len(),
+                        entry.total_frames(),
+                        format_size(entry.stamp.size)
+                    );
+                    if let Some(modified) = entry.stamp.modified {
+                        metadata += &DateTime::<Local>::from(modified)
+                            .format(", modified %Y-%m-%d %H:%M")
+                            .to_string();
+                    }
+                    metadata
+                })
+                .unwrap_or_default();
+            parsed_dmis_column = parsed_dmis_column.push(container(column![
+                row![
+                    selected_mark,
+                    highlighted_text(
+                        &path.to_string_lossy(),
+                        path_positions,
+                        true
+                    )
+                ],
+                text(metadata).color(color!(0x9a9a9a)),
+                row![
+                    button(row![icon::search(), text(" View")])
+                        .on_press(wrap![ExplorerMessage::OpenInViewer(
+                            path.clone()
+                        )])
+                        .style(button::success),
+                    button(row![icon::folder(), text(" Copy DMI")])
+                        .on_press(wrap![ExplorerMessage::CopyFile(
+                            path.clone()
+                        )])
+                        .style(widget::button::success),
+                    button(row![icon::save(), text(" Copy All")]).on_press(
+                        wrap![ExplorerMessage::CopyAllDMIText(path.clone())]
+                    ),
+                    button(row![icon::video(), text(" Export All")])
+                        .on_press(wrap![ExplorerMessage::ExportAll(
+                            path.clone()
+                        )])
+                        .style(button::secondary),
+                    button(row![icon::file(), text(" Merge With...")])
+                        .on_press(wrap![ExplorerMessage::MergeWith(
+                            path.clone()
+                        )])
+                        .style(button::secondary),
+                    button(row![
+                        icon::resize(),
+                        text!(
+                            " Split Selected ({})",
+                            selection.map_or(0, |selection| selection.len())
+                        )
+                    ])
+                    .on_press_maybe(
+                        selection
+                            .is_some_and(|selection| !selection.is_empty())
+                            .then(|| {
+                                wrap![ExplorerMessage::SplitSelected(
+                                    path.clone()
+                                )]
+                            })
+                    )
+                    .style(button::secondary),
+                    button(row![icon::save(), text(" Copy Path")])
+                        .on_press(wrap![ExplorerMessage::CopyText(
+                            screen.copied_path(path)
+                        )])
+                        .style(button::secondary),
+                    button(row![icon::trash(), text(" Clear")])
+                        .on_press(wrap![ExplorerMessage::RemoveDMI(
+                            path.clone()
+                        )])
+                        .style(button::danger),
+                ]
+                .spacing(8),
+                dmi_states_column,
+                Space::with_height(20)
+            ]));
         }
 
         let upper_page_controls =
@@ -1029,9 +3306,16 @@ impl Screen for ExplorerScreen {
             scrollable(
                 column![
                     input_controls,
+                    screen.project_view(),
                     output_controls,
                     screen.filter_view(),
                     settings_bar,
+                    screen.unused_report_view(),
+                    screen.missing_report_view(),
+                    screen.lint_report_view(),
+                    screen.duplicates_report_view(),
+                    screen.similar_view(),
+                    screen.sort_view(),
                     upper_page_controls,
                     parsed_dmis_column,
                     lower_page_controls,
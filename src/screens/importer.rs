@@ -0,0 +1,536 @@
+use std::{collections::BTreeSet, path::PathBuf, time::Instant};
+
+use iced::{
+    Alignment, Element, Length, Task,
+    alignment::Vertical,
+    color,
+    widget::{
+        Column, Image, Space, button, column, container, radio, row,
+        scrollable, text, toggler,
+    },
+};
+use iced_aw::{NumberInput, TabLabel};
+use iced_toasts::ToastLevel;
+use log::{debug, error};
+use rfd::FileDialog;
+
+use crate::{
+    DMIAssistant, Message, ViewerMessage,
+    dmi_import::{
+        ImportedState, SourceImage, build_icon, group_states, scan_folder,
+    },
+    dmi_utils::save_dmi,
+    icon,
+    screens::{Screen, Screens},
+    utils::{bold_text, popup},
+    wrap,
+};
+
+const DEFAULT_ICON_SIZE: u32 = 32;
+
+#[derive(Debug, Clone)]
+pub enum ImporterMessage {
+    OpenedFileExplorer,
+    ScanFolder(PathBuf),
+    FolderScanned((PathBuf, Result<(Vec<SourceImage>, Vec<String>), String>)),
+
+    ChangeIconWidth(u32),
+    ChangeIconHeight(u32),
+
+    ChangeStateDirs(usize, u8),
+    ChangeStateDelay(usize, f32),
+    ChangeStateLoop(usize, u32),
+    ToggleStateRewind(usize, bool),
+    ToggleStateMovement(usize, bool),
+    ToggleStateSplit(usize, bool),
+
+    BuildDMI,
+    DMIBuilt(Result<(PathBuf, Vec<String>), String>),
+    Clear,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImporterScreen {
+    pub hovered_file: bool,
+    pub folder: Option<PathBuf>,
+    pub scanning: bool,
+
+    pub sources: Vec<SourceImage>,
+    /// Files, which could not be read as images
+    pub scan_errors: Vec<String>,
+
+    pub icon_width: u32,
+    pub icon_height: u32,
+
+    pub states: Vec<ImportedState>,
+    /// Parsed state names, which files are not split by their suffixes
+    pub whole_names: BTreeSet<String>,
+    /// Problems found while grouping sources into states
+    pub warnings: Vec<String>,
+}
+
+impl Default for ImporterScreen {
+    fn default() -> Self {
+        Self {
+            hovered_file: false,
+            folder: None,
+            scanning: false,
+            sources: Vec::new(),
+            scan_errors: Vec::new(),
+            icon_width: DEFAULT_ICON_SIZE,
+            icon_height: DEFAULT_ICON_SIZE,
+            states: Vec::new(),
+            whole_names: BTreeSet::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl ImporterScreen {
+    fn regroup(&mut self) {
+        let (states, warnings) = group_states(
+            &self.sources,
+            self.icon_width,
+            self.icon_height,
+            &self.states,
+            &self.whole_names,
+        );
+        self.states = states;
+        self.warnings = warnings;
+    }
+
+    fn display_state<'a>(
+        &'a self,
+        index: usize,
+        state: &'a ImportedState,
+    ) -> Element<'a, Message> {
+        let preview: Element<Message> = match state.images.values().next() {
+            Some(image) => Image::new(iced::widget::image::Handle::from_rgba(
+                image.width(),
+                image.height(),
+                image.to_rgba8().into_raw(),
+            ))
+            .width(self.icon_width.max(32) as f32)
+            .height(self.icon_height.max(32) as f32)
+            .into(),
+            None => text("?").into(),
+        };
+
+        let dirs_picker = row([1u8, 4, 8].into_iter().map(|dirs| {
+            radio(
+                dirs.to_string(),
+                dirs,
+                Some(state.settings.dirs),
+                move |dirs| {
+                    wrap![ImporterMessage::ChangeStateDirs(index, dirs)]
+                },
+            )
+            .into()
+        }))
+        .spacing(10);
+
+        let delay_picker: NumberInput<f32, Message> =
+            NumberInput::new(state.settings.delay, 0.1..=100.0, move |delay| {
+                wrap![ImporterMessage::ChangeStateDelay(index, delay)]
+            })
+            .step(0.5);
+
+        let loop_picker: NumberInput<u32, Message> = NumberInput::new(
+            state.settings.loop_count,
+            0..=1000,
+            move |count| wrap![ImporterMessage::ChangeStateLoop(index, count)],
+        )
+        .step(1);
+
+        container(
+            row![
+                preview,
+                column![
+                    bold_text(state.name.clone()),
+                    text!(
+                        "Frames: {} | Images: {} | Dirs in files: {}",
+                        state.frames,
+                        state.images.len(),
+                        state.detected_dirs()
+                    ),
+                    row![text("Dirs: "), dirs_picker].align_y(Vertical::Center),
+                    row![text("Delay (ticks): "), delay_picker]
+                        .align_y(Vertical::Center)
+                        .spacing(5),
+                    row![text("Loop (0 - indefinitely): "), loop_picker]
+                        .align_y(Vertical::Center)
+                        .spacing(5),
+                    toggler(state.settings.rewind).label("Rewind").on_toggle(
+                        move |rewind| {
+                            wrap![ImporterMessage::ToggleStateRewind(
+                                index, rewind
+                            )]
+                        }
+                    ),
+                    toggler(state.settings.movement)
+                        .label("Movement")
+                        .on_toggle(move |movement| {
+                            wrap![ImporterMessage::ToggleStateMovement(
+                                index, movement
+                            )]
+                        }),
+                ]
+                .push_maybe(state.has_suffixes.then(|| {
+                    toggler(!self.whole_names.contains(&state.parsed_name))
+                        .label("Split dir and frame suffixes of file names")
+                        .on_toggle(move |split| {
+                            wrap![ImporterMessage::ToggleStateSplit(
+                                index, split
+                            )]
+                        })
+                }))
+                .spacing(5)
+            ]
+            .spacing(20)
+            .align_y(Alignment::Start),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+impl Screen for ImporterScreen {
+    fn label(&self) -> TabLabel {
+        TabLabel::IconText('\u{1F4C2}', " Importer".to_string())
+    }
+
+    fn update(app: &mut DMIAssistant, message: Message) -> Task<Message> {
+        let screen = &mut app.importer_screen;
+        match message {
+            Message::Window(_id, event) => match event {
+                iced::window::Event::FileHovered(_) => {
+                    screen.hovered_file = true;
+                    Task::none()
+                }
+                iced::window::Event::FilesHoveredLeft => {
+                    screen.hovered_file = false;
+                    Task::none()
+                }
+                iced::window::Event::FileDropped(path) => {
+                    screen.hovered_file = false;
+                    if path.is_dir() {
+                        Task::done(wrap![ImporterMessage::ScanFolder(path)])
+                    } else {
+                        Task::done(popup(
+                            "Drop a folder with PNGs to import it",
+                            Some("Not a folder"),
+                            ToastLevel::Warning,
+                        ))
+                    }
+                }
+                _ => Task::none(),
+            },
+            Message::ImporterMessage(importer_message) => {
+                match importer_message {
+                    ImporterMessage::OpenedFileExplorer => {
+                        match FileDialog::new()
+                            .set_title("Open folder with PNGs")
+                            .set_directory("/")
+                            .pick_folder()
+                        {
+                            Some(folder) => {
+                                Task::done(wrap![ImporterMessage::ScanFolder(
+                                    folder
+                                )])
+                            }
+                            None => Task::none(),
+                        }
+                    }
+                    ImporterMessage::ScanFolder(folder) => {
+                        screen.scanning = true;
+                        Task::future(async move {
+                            let scan_start = Instant::now();
+                            let scanned = scan_folder(&folder)
+                                .map_err(|err| err.to_string());
+                            debug!(
+                                "Folder {} scanned in {}ms",
+                                folder.to_string_lossy(),
+                                scan_start.elapsed().as_millis()
+                            );
+                            wrap![ImporterMessage::FolderScanned((
+                                folder, scanned
+                            ))]
+                        })
+                    }
+                    ImporterMessage::FolderScanned((folder, result)) => {
+                        screen.scanning = false;
+                        let (sources, scan_errors) = match result {
+                            Ok(scanned) => scanned,
+                            Err(err) => {
+                                error!(
+                                    "Failed to scan folder {}: {}",
+                                    folder.to_string_lossy(),
+                                    err
+                                );
+                                return Task::done(popup(
+                                    format!("Failed to scan folder: {}", err),
+                                    Some("Scan failed"),
+                                    ToastLevel::Error,
+                                ));
+                            }
+                        };
+                        if let Some(first) = sources.first() {
+                            screen.icon_height = first.image.height();
+                            screen.icon_width = first.image.height();
+                        }
+                        screen.folder = Some(folder);
+                        screen.sources = sources;
+                        screen.scan_errors = scan_errors;
+                        screen.states.clear();
+                        screen.whole_names.clear();
+                        screen.regroup();
+                        Task::done(popup(
+                            format!(
+                                "Found {} images in {} states",
+                                screen.sources.len(),
+                                screen.states.len()
+                            ),
+                            Some("Scanned"),
+                            ToastLevel::Success,
+                        ))
+                    }
+                    ImporterMessage::ChangeIconWidth(width) => {
+                        screen.icon_width = width;
+                        screen.regroup();
+                        Task::none()
+                    }
+                    ImporterMessage::ChangeIconHeight(height) => {
+                        screen.icon_height = height;
+                        screen.regroup();
+                        Task::none()
+                    }
+                    ImporterMessage::ChangeStateDirs(index, dirs) => {
+                        if let Some(state) = screen.states.get_mut(index) {
+                            state.settings.dirs = dirs;
+                        }
+                        Task::none()
+                    }
+                    ImporterMessage::ChangeStateDelay(index, delay) => {
+                        if let Some(state) = screen.states.get_mut(index) {
+                            state.settings.delay = delay;
+                        }
+                        Task::none()
+                    }
+                    ImporterMessage::ChangeStateLoop(index, loop_count) => {
+                        if let Some(state) = screen.states.get_mut(index) {
+                            state.settings.loop_count = loop_count;
+                        }
+                        Task::none()
+                    }
+                    ImporterMessage::ToggleStateRewind(index, rewind) => {
+                        if let Some(state) = screen.states.get_mut(index) {
+                            state.settings.rewind = rewind;
+                        }
+                        Task::none()
+                    }
+                    ImporterMessage::ToggleStateMovement(index, movement) => {
+                        if let Some(state) = screen.states.get_mut(index) {
+                            state.settings.movement = movement;
+                        }
+                        Task::none()
+                    }
+                    ImporterMessage::ToggleStateSplit(index, split) => {
+                        if let Some(state) = screen.states.get(index) {
+                            let name = state.parsed_name.clone();
+                            if split {
+                                screen.whole_names.remove(&name);
+                            } else {
+                                screen.whole_names.insert(name);
+                            }
+                            screen.regroup();
+                        }
+                        Task::none()
+                    }
+                    ImporterMessage::BuildDMI => {
+                        if screen.states.is_empty() {
+                            return Task::done(popup(
+                                "There are no states to build DMI from",
+                                Some("Nothing to build"),
+                                ToastLevel::Error,
+                            ));
+                        }
+                        let mut dialog = FileDialog::new()
+                            .set_title("Save built DMI as")
+                            .add_filter("dmi", &["dmi"]);
+                        if let Some(folder) = &screen.folder {
+                            dialog = dialog.set_directory(folder);
+                        }
+                        let Some(path) = dialog.save_file() else {
+                            return Task::none();
+                        };
+                        let width = screen.icon_width;
+                        let height = screen.icon_height;
+                        let states = screen.states.clone();
+                        Task::future(async move {
+                            let built = build_icon(width, height, &states)
+                                .map_err(|err| err.to_string())
+                                .and_then(|(icon, warnings)| {
+                                    save_dmi(&icon, &path)
+                                        .map(|_| (path, warnings))
+                                        .map_err(|err| err.to_string())
+                                });
+                            wrap![ImporterMessage::DMIBuilt(built)]
+                        })
+                    }
+                    ImporterMessage::DMIBuilt(result) => match result {
+                        Ok((path, warnings)) => {
+                            for warning in &warnings {
+                                debug!("[IMPORT] {}", warning);
+                            }
+                            Task::batch([
+                                Task::done(popup(
+                                    format!(
+                                        "Built {} with {} warnings",
+                                        path.to_string_lossy(),
+                                        warnings.len()
+                                    ),
+                                    Some("Built DMI"),
+                                    ToastLevel::Success,
+                                )),
+                                Task::done(Message::ChangeScreen(
+                                    Screens::Viewer,
+                                )),
+                                Task::done(wrap![
+                                    ViewerMessage::ChangeDMIPath(
+                                        path.to_string_lossy().into()
+                                    )
+                                ])
+                                .chain(
+                                    Task::done(wrap![ViewerMessage::LoadDMI]),
+                                ),
+                            ])
+                        }
+                        Err(err) => {
+                            error!("Failed to build DMI: {}", err);
+                            Task::done(popup(
+                                format!("Failed to build DMI: {}", err),
+                                Some("Build failed"),
+                                ToastLevel::Error,
+                            ))
+                        }
+                    },
+                    ImporterMessage::Clear => {
+                        *screen = ImporterScreen::default();
+                        Task::none()
+                    }
+                }
+            }
+            _ => Task::none(),
+        }
+    }
+
+    fn view<'a>(app: &'a DMIAssistant) -> Element<'a, Message> {
+        let screen = &app.importer_screen;
+
+        if screen.hovered_file {
+            return container("Drop the folder with PNGs here")
+                .style(container::bordered_box)
+                .padding(50)
+                .center_x(Length::Fill)
+                .center_y(Length::Fill)
+                .into();
+        }
+        if screen.scanning {
+            return container(text("Reading images..."))
+                .style(container::bordered_box)
+                .padding(50)
+                .center_x(Length::Fill)
+                .center_y(Length::Fill)
+                .into();
+        }
+
+        let button_folder_explorer =
+            button(row![icon::folder(), text(" Browse Folder")])
+                .on_press(wrap![ImporterMessage::OpenedFileExplorer]);
+        let folder_text = match &screen.folder {
+            Some(folder) => bold_text(folder.to_string_lossy()),
+            None => bold_text("No folder opened"),
+        };
+        let input_controls = row![button_folder_explorer, folder_text]
+            .align_y(Vertical::Center)
+            .spacing(10);
+
+        if screen.sources.is_empty() {
+            return container(
+                column![
+                    input_controls,
+                    container(bold_text(
+                        "... or drop a folder with PNG frames or strips named \
+                         like state[_dir][_frame].png there!"
+                    ))
+                    .style(container::bordered_box)
+                    .padding(50)
+                    .center_x(Length::Fill)
+                    .center_y(Length::Fill)
+                ]
+                .spacing(10),
+            )
+            .padding(20)
+            .into();
+        }
+
+        let width_picker: NumberInput<u32, Message> =
+            NumberInput::new(screen.icon_width, 1..=1024, |width| {
+                wrap![ImporterMessage::ChangeIconWidth(width)]
+            })
+            .step(1);
+        let height_picker: NumberInput<u32, Message> =
+            NumberInput::new(screen.icon_height, 1..=1024, |height| {
+                wrap![ImporterMessage::ChangeIconHeight(height)]
+            })
+            .step(1);
+        let build_button = button(row![icon::save(), text(" Build DMI")])
+            .on_press(wrap![ImporterMessage::BuildDMI])
+            .style(button::success);
+        let clear_button = button(row![icon::trash(), text(" Clear")])
+            .on_press(wrap![ImporterMessage::Clear])
+            .style(button::danger);
+        let icon_controls = row![
+            icon::resize_width(),
+            text(" Icon width: "),
+            width_picker,
+            icon::resize_height(),
+            text(" Icon height: "),
+            height_picker,
+            build_button,
+            clear_button
+        ]
+        .align_y(Vertical::Center)
+        .spacing(10);
+
+        let warnings: Column<Message> = screen
+            .scan_errors
+            .iter()
+            .chain(&screen.warnings)
+            .map(|warning| text(warning).color(color!(0xcde002)).into())
+            .collect();
+
+        let states: Column<Message> = screen
+            .states
+            .iter()
+            .enumerate()
+            .map(|(index, state)| screen.display_state(index, state))
+            .collect();
+
+        container(scrollable(
+            column![
+                input_controls,
+                icon_controls,
+                warnings.spacing(5),
+                Space::with_height(10),
+                states.spacing(10)
+            ]
+            .spacing(10)
+            .padding(10),
+        ))
+        .padding(10)
+        .into()
+    }
+}
@@ -0,0 +1,440 @@
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+
+use dmi::icon::IconState;
+use iced::{
+    Element, Length, Task,
+    alignment::Vertical,
+    color,
+    widget::{
+        Column, Image, button, column, container, image::FilterMethod, radio,
+        row, scrollable, text, text_input,
+    },
+};
+use iced_aw::TabLabel;
+use iced_toasts::ToastLevel;
+use log::{debug, error};
+use rfd::FileDialog;
+
+use crate::{
+    DMIAssistant, Message,
+    dmi_merge::{
+        MergeSide, StateConflict, StateKey, ThreeWayMerge, merge_three_way,
+    },
+    dmi_utils::{Directions, get_state_frame, load_dmi, save_dmi},
+    icon,
+    screens::Screen,
+    utils::{bold_text, fitted_delays_popup, popup},
+    wrap,
+};
+
+const PREVIEW_ZOOM: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeFile {
+    Base,
+    Ours,
+    Theirs,
+    Output,
+}
+
+#[derive(Debug, Clone)]
+pub enum MergeMessage {
+    ChangePath(MergeFile, String),
+    OpenedFileExplorer(MergeFile),
+
+    LoadDMIs,
+    DMIsLoaded(Result<ThreeWayMerge, String>),
+
+    ChooseSide(StateKey, MergeSide),
+    ChooseAll(MergeSide),
+
+    WriteMerged,
+    MergedWritten(Result<PathBuf, String>),
+    Clear,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MergeScreen {
+    pub base_path: String,
+    pub ours_path: String,
+    pub theirs_path: String,
+    /// Defaults to `ours_path`, like git's %A
+    pub output_path: String,
+
+    pub loading: bool,
+    pub merge: Option<ThreeWayMerge>,
+    /// Chosen versions of the conflicting states
+    pub choices: HashMap<StateKey, MergeSide>,
+}
+
+impl MergeScreen {
+    fn path_mut(&mut self, file: MergeFile) -> &mut String {
+        match file {
+            MergeFile::Base => &mut self.base_path,
+            MergeFile::Ours => &mut self.ours_path,
+            MergeFile::Theirs => &mut self.theirs_path,
+            MergeFile::Output => &mut self.output_path,
+        }
+    }
+
+    fn display_version<'a>(
+        &self,
+        conflict: &StateConflict,
+        side: MergeSide,
+    ) -> Element<'a, Message> {
+        let state: Option<&IconState> = conflict.get(side);
+        let preview: Element<Message> = match state
+            .and_then(|state| get_state_frame(state, Directions::South, 0))
+        {
+            Some(image) => Image::new(iced::widget::image::Handle::from_rgba(
+                image.width(),
+                image.height(),
+                image.to_rgba8().into_raw(),
+            ))
+            .filter_method(FilterMethod::Nearest)
+            .width((image.width() * PREVIEW_ZOOM) as f32)
+            .height((image.height() * PREVIEW_ZOOM) as f32)
+            .into(),
+            None => text("-").into(),
+        };
+        let description = match state {
+            Some(state) => format!(
+                "dirs: {} | frames: {}\ndelay: {:?}",
+                state.dirs,
+                state.frames,
+                state.delay.as_deref().unwrap_or_default()
+            ),
+            None => "does not exist".to_string(),
+        };
+        let key = conflict.key.clone();
+        column![
+            radio(
+                side.to_string(),
+                side,
+                self.choices.get(&conflict.key).copied(),
+                move |side| {
+                    wrap![MergeMessage::ChooseSide(key.clone(), side)]
+                },
+            ),
+            preview,
+            text(description).size(12),
+        ]
+        .spacing(5)
+        .width(250)
+        .into()
+    }
+
+    fn display_conflict<'a>(
+        &self,
+        conflict: &StateConflict,
+    ) -> Element<'a, Message> {
+        let chosen_color = if self.choices.contains_key(&conflict.key) {
+            color!(0x89fc41)
+        } else {
+            color!(0xfc4144)
+        };
+        container(
+            column![
+                row![
+                    bold_text(conflict.key.to_string()).color(chosen_color),
+                    text(conflict.describe())
+                ]
+                .spacing(10),
+                row![
+                    self.display_version(conflict, MergeSide::Base),
+                    self.display_version(conflict, MergeSide::Ours),
+                    self.display_version(conflict, MergeSide::Theirs),
+                ]
+                .spacing(20),
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+impl Screen for MergeScreen {
+    fn label(&self) -> TabLabel {
+        TabLabel::IconText('\u{1F500}', " Merge".to_string())
+    }
+
+    fn update(app: &mut DMIAssistant, message: Message) -> Task<Message> {
+        let screen = &mut app.merge_screen;
+        let Message::MergeMessage(merge_message) = message else {
+            return Task::none();
+        };
+        match merge_message {
+            MergeMessage::ChangePath(file, path) => {
+                if file == MergeFile::Ours
+                    && (screen.output_path.is_empty()
+                        || screen.output_path == screen.ours_path)
+                {
+                    screen.output_path = path.clone();
+                }
+                *screen.path_mut(file) = path;
+                Task::none()
+            }
+            MergeMessage::OpenedFileExplorer(file) => {
+                let dialog = FileDialog::new()
+                    .set_directory("/")
+                    .add_filter("dmi", &["dmi"]);
+                let path = match file {
+                    MergeFile::Base => {
+                        dialog.set_title("Open the base DMI").pick_file()
+                    }
+                    MergeFile::Ours => {
+                        dialog.set_title("Open our DMI").pick_file()
+                    }
+                    MergeFile::Theirs => {
+                        dialog.set_title("Open their DMI").pick_file()
+                    }
+                    MergeFile::Output => {
+                        dialog.set_title("Save the merged DMI as").save_file()
+                    }
+                };
+                match path {
+                    Some(path) => Task::done(wrap![MergeMessage::ChangePath(
+                        file,
+                        path.to_string_lossy().into()
+                    )]),
+                    None => Task::none(),
+                }
+            }
+            MergeMessage::LoadDMIs => {
+                screen.loading = true;
+                let paths = [
+                    screen.base_path.clone(),
+                    screen.ours_path.clone(),
+                    screen.theirs_path.clone(),
+                ];
+                Task::future(async move {
+                    let merge_start = Instant::now();
+                    let load = |path: &String| {
+                        load_dmi(path)
+                            .map_err(|err| format!("{}: {}", path, err))
+                    };
+                    let merged = load(&paths[0]).and_then(|base| {
+                        let ours = load(&paths[1])?;
+                        let theirs = load(&paths[2])?;
+                        merge_three_way(base, ours, theirs)
+                            .map_err(|err| err.to_string())
+                    });
+                    debug!(
+                        "Three-way merge done in {}ms",
+                        merge_start.elapsed().as_millis()
+                    );
+                    wrap![MergeMessage::DMIsLoaded(merged)]
+                })
+            }
+            MergeMessage::DMIsLoaded(result) => {
+                screen.loading = false;
+                screen.choices.clear();
+                match result {
+                    Ok(merge) => {
+                        let conflicts = merge.conflict_count();
+                        screen.merge = Some(merge);
+                        Task::done(popup(
+                            format!("{} conflicting states", conflicts),
+                            Some("Merged"),
+                            if conflicts == 0 {
+                                ToastLevel::Success
+                            } else {
+                                ToastLevel::Warning
+                            },
+                        ))
+                    }
+                    Err(err) => {
+                        error!("Failed to merge DMIs: {}", err);
+                        screen.merge = None;
+                        Task::done(popup(
+                            format!("Failed to merge DMIs: {}", err),
+                            Some("Merge failed"),
+                            ToastLevel::Error,
+                        ))
+                    }
+                }
+            }
+            MergeMessage::ChooseSide(state, side) => {
+                screen.choices.insert(state, side);
+                Task::none()
+            }
+            MergeMessage::ChooseAll(side) => {
+                if let Some(merge) = &screen.merge {
+                    for conflict in merge.conflicts() {
+                        screen.choices.insert(conflict.key.clone(), side);
+                    }
+                }
+                Task::none()
+            }
+            MergeMessage::WriteMerged => {
+                let Some(merge) = &screen.merge else {
+                    return Task::none();
+                };
+                let output = PathBuf::from(&screen.output_path);
+                let mut fitted = None;
+                let written = merge
+                    .resolve(&screen.choices, None)
+                    .map_err(|err| err.to_string())
+                    .and_then(|icon| {
+                        fitted = fitted_delays_popup(&icon);
+                        save_dmi(&icon, &output)
+                            .map(|_| output)
+                            .map_err(|err| err.to_string())
+                    });
+                Task::batch(fitted.map(Task::done).into_iter().chain([
+                    Task::done(wrap![MergeMessage::MergedWritten(written)]),
+                ]))
+            }
+            MergeMessage::MergedWritten(result) => match result {
+                Ok(path) => Task::done(popup(
+                    format!(
+                        "Merged DMI was written to {}",
+                        path.to_string_lossy()
+                    ),
+                    Some("Saved"),
+                    ToastLevel::Success,
+                )),
+                Err(err) => {
+                    error!("Failed to write merged DMI: {}", err);
+                    Task::done(popup(
+                        format!("Failed to write merged DMI: {}", err),
+                        Some("Save failed"),
+                        ToastLevel::Error,
+                    ))
+                }
+            },
+            MergeMessage::Clear => {
+                *screen = MergeScreen::default();
+                Task::none()
+            }
+        }
+    }
+
+    fn view<'a>(app: &'a DMIAssistant) -> Element<'a, Message> {
+        let screen = &app.merge_screen;
+
+        if screen.loading {
+            return container(text("Merging..."))
+                .style(container::bordered_box)
+                .padding(50)
+                .center_x(Length::Fill)
+                .center_y(Length::Fill)
+                .into();
+        }
+
+        let path_input = |file: MergeFile, label: &'a str, path: &'a str| {
+            row![
+                text(label).width(70),
+                text_input("Input DMI path", path)
+                    .on_input(move |input| {
+                        wrap![MergeMessage::ChangePath(file, input)]
+                    })
+                    .on_paste(move |input| {
+                        wrap![MergeMessage::ChangePath(file, input)]
+                    })
+                    .padding(10),
+                button(row![icon::iconfile(), text(" Browse Files")])
+                    .on_press(wrap![MergeMessage::OpenedFileExplorer(file)]),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center)
+        };
+
+        let can_load = !screen.base_path.is_empty()
+            && !screen.ours_path.is_empty()
+            && !screen.theirs_path.is_empty();
+        let unresolved = screen.merge.as_ref().map_or(0, |merge| {
+            merge
+                .conflicts()
+                .filter(|conflict| !screen.choices.contains_key(&conflict.key))
+                .count()
+        });
+        let can_write = screen.merge.is_some()
+            && unresolved == 0
+            && !screen.output_path.is_empty();
+
+        let controls = row![
+            button(row![icon::search(), text(" Merge")])
+                .on_press_maybe(
+                    can_load.then_some(wrap![MergeMessage::LoadDMIs])
+                )
+                .style(button::success),
+            button(row![icon::save(), text(" Write Merged DMI")])
+                .on_press_maybe(
+                    can_write.then_some(wrap![MergeMessage::WriteMerged])
+                )
+                .style(button::success),
+            button(row![icon::trash(), text(" Clear")])
+                .on_press(wrap![MergeMessage::Clear])
+                .style(button::danger),
+        ]
+        .spacing(10)
+        .align_y(Vertical::Center);
+
+        let mut content = column![
+            path_input(MergeFile::Base, "Base:", &screen.base_path),
+            path_input(MergeFile::Ours, "Ours:", &screen.ours_path),
+            path_input(MergeFile::Theirs, "Theirs:", &screen.theirs_path),
+            path_input(MergeFile::Output, "Output:", &screen.output_path),
+            controls,
+        ]
+        .spacing(10);
+
+        match &screen.merge {
+            None => {
+                content = content.push(
+                    container(bold_text(
+                        "Choose the base, our and their versions of the DMI \
+                         to merge them",
+                    ))
+                    .style(container::bordered_box)
+                    .padding(50)
+                    .center_x(Length::Fill),
+                );
+            }
+            Some(merge) => {
+                let summary = text!(
+                    "Merged automatically: {} | Conflicts: {} | Unresolved: {}",
+                    merge.entries.len() - merge.conflict_count(),
+                    merge.conflict_count(),
+                    unresolved
+                );
+                let choose_all = row![
+                    text("Use for all conflicts: "),
+                    button("Base")
+                        .on_press(wrap![MergeMessage::ChooseAll(
+                            MergeSide::Base
+                        )])
+                        .style(button::secondary),
+                    button("Ours")
+                        .on_press(wrap![MergeMessage::ChooseAll(
+                            MergeSide::Ours
+                        )])
+                        .style(button::secondary),
+                    button("Theirs")
+                        .on_press(wrap![MergeMessage::ChooseAll(
+                            MergeSide::Theirs
+                        )])
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center);
+                let conflicts: Column<Message> = merge
+                    .conflicts()
+                    .map(|conflict| screen.display_conflict(conflict))
+                    .collect();
+                content = content
+                    .push(summary)
+                    .push(choose_all)
+                    .push(conflicts.spacing(10));
+            }
+        }
+
+        container(scrollable(content.padding(10)))
+            .padding(10)
+            .into()
+    }
+}
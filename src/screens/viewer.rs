@@ -1,9 +1,15 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::fs;
 use std::fs::OpenOptions;
 use std::io::Cursor;
 use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
+use std::time::SystemTime;
 
 use arboard::Clipboard;
 use dmi::icon::Icon;
@@ -14,10 +20,13 @@ use iced::Color;
 use iced::Element;
 use iced::Length;
 use iced::Shadow;
+use iced::Subscription;
 use iced::Task;
 use iced::alignment::Horizontal;
 use iced::alignment::Vertical;
 use iced::border::Radius;
+use iced::color;
+use iced::futures::channel::mpsc;
 use iced::keyboard::Key;
 use iced::keyboard::Modifiers;
 use iced::widget;
@@ -31,6 +40,7 @@ use iced::widget::button;
 use iced::widget::column;
 use iced::widget::container;
 use iced::widget::container::Style;
+use iced::widget::mouse_area;
 use iced::widget::radio;
 use iced::widget::row;
 use iced::widget::scrollable;
@@ -39,6 +49,7 @@ use iced::widget::scrollable::Scrollbar;
 use iced::widget::text;
 use iced::widget::text_input;
 use iced::widget::toggler;
+use iced::widget::tooltip;
 use iced_aw::Grid;
 use iced_aw::GridRow;
 use iced_aw::NumberInput;
@@ -51,6 +62,8 @@ use image::imageops::FilterType;
 use log::debug;
 use log::error;
 use log::warn;
+use rayon::iter::IntoParallelIterator;
+use rayon::iter::ParallelIterator;
 use rfd::FileDialog;
 use serde::Deserialize;
 use serde::Serialize;
@@ -59,23 +72,82 @@ use super::Screen;
 
 use crate::DMIAssistant;
 use crate::Message;
+use crate::dm_source::DMReference;
+use crate::dmi_edit::delete_state;
+use crate::dmi_edit::duplicate_state;
+use crate::dmi_edit::move_state;
+use crate::dmi_edit::rename_state;
+use crate::dmi_edit::state_index;
+use crate::dmi_git::DEFAULT_REVISION;
+use crate::dmi_lint::DEFAULT_MAX_ICON_SIZE;
+use crate::dmi_lint::LintIssue;
+use crate::dmi_lint::count_issues;
+use crate::dmi_lint::lint_icon;
 use crate::dmi_model::ParsedDMI;
+use crate::dmi_model::ParsedState;
+use crate::dmi_model::StateId;
+use crate::dmi_similar::perceptual_hash;
 use crate::dmi_utils::CustomFilterType;
 use crate::dmi_utils::Directions;
+use crate::dmi_utils::ExportSummary;
+use crate::dmi_utils::export_dmi;
 use crate::dmi_utils::load_dmi;
+use crate::dmi_utils::save_dmi;
 use crate::icon;
+use crate::screens::Screens;
+use crate::screens::diff::DiffMessage;
+use crate::screens::diff::DiffSide;
+use crate::screens::explorer::ExplorerMessage;
+use crate::search::Match;
+use crate::search::MatchMode;
+use crate::search::Matcher;
 use crate::utils::bold_text;
+use crate::utils::fitted_delays_popup;
+use crate::utils::highlighted_text;
+use crate::utils::lint_issue_text;
 use crate::utils::popup;
+use crate::watcher::watch_paths;
 use crate::wrap;
 
+/// How many states around the estimated scroll position are prepared for
+/// displaying, see [`ViewerScreen::prepare_visible`].
+const PREPARED_STATES_WINDOW: usize = 32;
+/// How many of them are taken before the estimated position.
+const PREPARED_STATES_MARGIN: usize = 8;
+
 #[derive(Debug, Clone)]
 pub enum ViewerMessage {
     ChangeDMIPath(String),
     LoadDMI,
     DMILoaded(Result<(Icon, ParsedDMI), String>),
+    Scrolled(f32),
+    StatePrepared(StateId),
+    FilesChanged(Vec<PathBuf>),
     OpenedFileExplorer,
-    CopyImage(String, bool, bool, Directions, Option<usize>),
+    CopyImage(StateId, bool, bool, Directions, Option<usize>),
+    /// Looks for the states, similar to the frame, in the Explorer
+    FindSimilar(StateId, Directions, usize),
     CopyFile,
+    ExportAll,
+    Exported(Result<(PathBuf, ExportSummary), String>),
+    CompareWith,
+    ChangeCompareRevision(String),
+    CompareWithRevision,
+    SaveDMI,
+    SaveDMIAs,
+    /// Saved path and the saved `dmi_version`
+    DMISaved(Result<(PathBuf, u64), String>),
+    ToggleLintReport(bool),
+    /// Lists the code, which uses the state, None hides the list
+    ShowReferences(Option<String>),
+
+    StartRenameState(StateId),
+    ChangeRenameInput(String),
+    ConfirmRenameState,
+    CancelRenameState,
+    DeleteState(StateId),
+    DuplicateState(StateId),
+    MoveState(StateId, isize),
 
     ToggleSettingsVisibility(bool),
     SaveSettings,
@@ -95,17 +167,47 @@ pub enum ViewerMessage {
     ColorChange(ColorPickerType, Color),
     //
     ChangeFilteredText(String),
+    ChangeFilterMode(MatchMode),
     ToggleFilter(bool),
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct ViewerScreen {
     pub dmi_path: String,
+    /// `dmi_path` made absolute with the symlinks resolved, when the DMI was
+    /// loaded or saved
+    pub dmi_canonical_path: Option<PathBuf>,
     pub dmi_raw_icon: Icon,
     pub parsed_dmi: ParsedDMI,
+    /// `dmi_raw_icon` was changed since it was loaded or saved
+    pub dmi_modified: bool,
+    /// Grows with every edit and load, tells whether a saved snapshot is
+    /// still the current icon
+    pub dmi_version: u64,
+    /// A save is being written, only one runs at a time
+    pub saving: bool,
+    /// Another save was requested while one was being written
+    pub save_pending: bool,
+    /// Modification time of `dmi_path`, when it was loaded or saved
+    pub dmi_file_modified: Option<SystemTime>,
+    /// Renamed state and the new name in the input
+    pub renaming_state: Option<(StateId, String)>,
+    /// State, which will be deleted on the next press of its delete button
+    pub pending_state_deletion: Option<StateId>,
+    /// Git revision to compare the working copy with
+    pub compare_revision: String,
+    /// Lint issues of `dmi_raw_icon`, updated after every change
+    pub lint_issues: Vec<LintIssue>,
+    pub lint_report_visible: bool,
+    /// State, which references in the project code are listed
+    pub references_shown: Option<String>,
 
     pub loading_dmi_in_progress: bool,
     pub hovered_file: bool,
+    /// Relative vertical scroll position, from 0 to 1
+    pub scroll_offset: f32,
+    /// States, which frames and animations are being made in background
+    pub preparing_states: HashSet<StateId>,
 
     pub settings_visible: bool,
 
@@ -115,21 +217,259 @@ pub struct ViewerScreen {
     pub display_settings: DisplaySettings,
 
     pub filtered_text: String,
+    pub filter_mode: MatchMode,
+    /// Compiled `filtered_text`
+    pub filter: Matcher,
+    /// Why `filtered_text` can not be compiled, nothing is filtered then
+    pub filter_error: Option<String>,
     pub filter_opened: bool,
 }
 
 impl ViewerScreen {
+    /// Watches the folder of the opened DMI, so the file is noticed even if
+    /// an editor saves it by replacing.
+    pub fn subscription(&self) -> Subscription<Message> {
+        let Some(parent) = self
+            .dmi_canonical_path
+            .as_deref()
+            .unwrap_or(Path::new(&self.dmi_path))
+            .parent()
+            .filter(|parent| parent.is_dir())
+        else {
+            return Subscription::none();
+        };
+        watch_paths("VIEWER", vec![(parent.to_path_buf(), false)])
+            .map(|changed| wrap![ViewerMessage::FilesChanged(changed)])
+    }
+
+    fn file_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.dmi_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn rebuild_filter(&mut self) {
+        match Matcher::new(&self.filtered_text, self.filter_mode) {
+            Ok(filter) => {
+                self.filter = filter;
+                self.filter_error = None;
+            }
+            Err(err) => {
+                self.filter = Matcher::default();
+                self.filter_error = Some(err.to_string());
+            }
+        }
+    }
+
+    /// States, which match the filter, in the order of relevance. Without
+    /// the filter, all states are in the file order.
+    fn filtered_states(&self) -> Vec<(StateId, Match)> {
+        let mut filtered: Vec<(StateId, Match)> = self
+            .dmi_raw_icon
+            .states
+            .iter()
+            .filter_map(|state| {
+                self.filter
+                    .find(&state.name)
+                    .map(|found| (StateId::of(state), found))
+            })
+            .collect();
+        // Stable, so equal matches keep the file order
+        filtered.sort_by_key(|(_, found)| -found.score);
+        filtered
+    }
+
+    fn relint(&mut self) {
+        self.lint_issues = lint_icon(&self.dmi_raw_icon, DEFAULT_MAX_ICON_SIZE);
+    }
+
+    /// Call after every change of `dmi_raw_icon`.
+    fn mark_edited(&mut self) {
+        self.dmi_modified = true;
+        self.dmi_version += 1;
+        self.relint();
+    }
+
+    /// Makes frames and animations of the states, which are probably
+    /// visible, on all CPU cores. Each state is sent back as soon as it is
+    /// ready. Layout of the states is not known before drawing, so their
+    /// position is estimated by the scroll offset.
+    fn prepare_visible(&mut self) -> Task<Message> {
+        let displayed_states: Vec<StateId> = self
+            .filtered_states()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        let first = ((displayed_states.len() as f32 * self.scroll_offset)
+            as usize)
+            .saturating_sub(PREPARED_STATES_MARGIN);
+
+        let mut unprepared: Vec<(StateId, Arc<ParsedState>, bool, bool)> =
+            Vec::new();
+        for id in displayed_states
+            .into_iter()
+            .skip(first)
+            .take(PREPARED_STATES_WINDOW)
+        {
+            let Some(state) = self.parsed_dmi.states.get(&id) else {
+                continue;
+            };
+            let settings = self.get_statebox_settings(&id.name);
+            if self.preparing_states.contains(&id)
+                || state.is_prepared(settings.show_resized, settings.animated)
+            {
+                continue;
+            }
+            unprepared.push((
+                id.clone(),
+                Arc::clone(state),
+                settings.show_resized,
+                settings.animated,
+            ));
+        }
+
+        if unprepared.is_empty() {
+            return Task::none();
+        }
+        self.preparing_states
+            .extend(unprepared.iter().map(|(id, ..)| id.clone()));
+        let (sender, receiver) = mpsc::unbounded::<StateId>();
+        rayon::spawn(move || {
+            unprepared.into_par_iter().for_each_with(
+                sender,
+                |sender, (id, state, resized, animated)| {
+                    state.prepare(resized, animated);
+                    let _ = sender.unbounded_send(id);
+                },
+            );
+        });
+        Task::run(receiver, |id| wrap![ViewerMessage::StatePrepared(id)])
+    }
+
+    fn lint_report_view<'a>(&self) -> Container<'a, Message> {
+        if !self.lint_report_visible {
+            return container("");
+        }
+        let issues: Column<Message> = if self.lint_issues.is_empty() {
+            column![text("No issues found")]
+        } else {
+            self.lint_issues
+                .iter()
+                .map(|issue| lint_issue_text(issue).into())
+                .collect()
+        };
+        container(
+            column![bold_text("Lint report"), issues.spacing(5)].spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
+
+    /// Where the shown state is used, paths are relative to the project
+    /// root.
+    fn references_view<'a>(
+        &self,
+        references: &[&DMReference],
+        root: Option<&Path>,
+    ) -> Container<'a, Message> {
+        let Some(state_name) = &self.references_shown else {
+            return container("");
+        };
+        let listing: Column<Message> = if references.is_empty() {
+            column![text("The state is not referenced in the project code")]
+        } else {
+            references
+                .iter()
+                .map(|reference| {
+                    let file = root
+                        .and_then(|root| reference.file.strip_prefix(root).ok())
+                        .unwrap_or(&reference.file);
+                    let location = format!(
+                        "{}:{}",
+                        file.to_string_lossy(),
+                        reference.line
+                    );
+                    let mut notes: Vec<&str> = Vec::new();
+                    if reference.inherited {
+                        notes.push("icon of the type");
+                    }
+                    if reference.dynamic {
+                        notes.push("name is built at runtime");
+                    }
+                    if reference.icon.is_none() {
+                        notes.push("icon is unknown");
+                    }
+                    row![
+                        button(icon::save())
+                            .on_press(wrap![ExplorerMessage::CopyText(
+                                reference.location()
+                            )])
+                            .style(button::secondary),
+                        bold_text(location),
+                        text(reference.code.clone()),
+                        text(notes.join(", ")).color(color!(0xcde002)),
+                    ]
+                    .spacing(10)
+                    .align_y(Vertical::Center)
+                    .into()
+                })
+                .collect()
+        };
+        container(
+            column![
+                row![
+                    bold_text(format!(
+                        "References to {} ({})",
+                        state_name,
+                        references.len()
+                    )),
+                    Space::with_width(Length::Fill),
+                    button(icon::trash())
+                        .on_press(wrap![ViewerMessage::ShowReferences(None)])
+                        .style(button::secondary),
+                ]
+                .align_y(Vertical::Center),
+                listing.spacing(5)
+            ]
+            .spacing(10),
+        )
+        .style(container::bordered_box)
+        .padding(10)
+        .width(Length::Fill)
+    }
+
     fn filter_view<'a>(&self) -> Container<'a, Message> {
         if self.filter_opened {
+            let mode_picker = MatchMode::ALL.iter().fold(
+                row![text("Match: ")].spacing(10),
+                |picker, mode| {
+                    picker.push(radio(
+                        mode.to_string(),
+                        mode,
+                        Some(&self.filter_mode),
+                        |mode| wrap![ViewerMessage::ChangeFilterMode(*mode)],
+                    ))
+                },
+            );
+            let filter_error: Element<Message> = match &self.filter_error {
+                Some(err) => text(err.clone()).color(color!(0xfc4144)).into(),
+                None => Space::new(0, 0).into(),
+            };
             container(
-                text_input("Enter text to find...", &self.filtered_text)
-                    .on_input(|input| {
-                        wrap![ViewerMessage::ChangeFilteredText(input)]
-                    })
-                    .on_paste(|input| {
-                        wrap![ViewerMessage::ChangeFilteredText(input)]
-                    })
-                    .padding(10),
+                column![
+                    text_input("Enter text to find...", &self.filtered_text)
+                        .on_input(|input| {
+                            wrap![ViewerMessage::ChangeFilteredText(input)]
+                        })
+                        .on_paste(|input| {
+                            wrap![ViewerMessage::ChangeFilteredText(input)]
+                        })
+                        .padding(10),
+                    mode_picker,
+                    filter_error
+                ]
+                .spacing(5),
             )
             .style(container::bordered_box)
             .padding(10)
@@ -150,24 +490,63 @@ impl ViewerScreen {
 
     fn display_statebox<'a>(
         &'a self,
-        state_name: &String,
+        state_id: &StateId,
+        found: &Match,
+        references: Option<usize>,
     ) -> Container<'a, Message> {
-        if !state_name.contains(&self.filtered_text) {
-            return container("");
-        }
-        let state = self.parsed_dmi.states.get(state_name);
+        let state = self.parsed_dmi.states.get(state_id);
         if state.is_none() {
             return container(text!(
                 "State {} does not exist. It's probably a bug.",
-                state_name
+                state_id
             ));
         }
         let state = state.unwrap();
-        let settings = self.get_statebox_settings(state_name);
+        let settings = self.get_statebox_settings(&state_id.name);
+        let all_warnings = state.all_warnings();
+        let warnings_badge: Element<Message> = if all_warnings.is_empty() {
+            Space::new(0, 0).into()
+        } else {
+            let warnings: Column<Message> = all_warnings
+                .iter()
+                .map(|warning| text(warning.to_string()).into())
+                .collect();
+            tooltip(
+                text!("\u{26A0} {}", all_warnings.len())
+                    .color(color!(0xcde002)),
+                container(warnings.spacing(5))
+                    .style(container::bordered_box)
+                    .padding(10),
+                tooltip::Position::Bottom,
+            )
+            .into()
+        };
+        // Unknown without the scanned project
+        let references_badge: Element<Message> = match references {
+            Some(count) => button(text!("{} refs", count))
+                .on_press(wrap![ViewerMessage::ShowReferences(Some(
+                    state.name.clone()
+                ))])
+                .style(if count == 0 {
+                    button::secondary
+                } else {
+                    button::primary
+                })
+                .padding([0, 5])
+                .into(),
+            None => Space::new(0, 0).into(),
+        };
         let header: Column<Message> = if settings.debug {
             column![
                 Space::new(1, 3),
-                row![text("State: "), bold_text(state.name.clone())],
+                row![
+                    text("State: "),
+                    highlighted_text(&state.name, &found.positions, true),
+                    Space::with_width(5),
+                    warnings_badge,
+                    Space::with_width(5),
+                    references_badge
+                ],
                 Space::new(1, 3),
                 text!("Delay: {:?}", state.delay),
                 text!("Frames: {}", state.frames),
@@ -180,103 +559,203 @@ impl ViewerScreen {
             .padding(5)
             .spacing(5)
         } else {
-            column![bold_text(state.name.clone()), Space::new(1, 10)]
-                .padding(5)
-                .spacing(5)
-                .align_x(Horizontal::Center)
+            column![
+                row![
+                    highlighted_text(&state.name, &found.positions, true),
+                    Space::with_width(5),
+                    warnings_badge,
+                    Space::with_width(5),
+                    references_badge
+                ],
+                Space::new(1, 10)
+            ]
+            .padding(5)
+            .spacing(5)
+            .align_x(Horizontal::Center)
         };
 
-        let display: Grid<Message> = {
-            let mut dirs: VecDeque<GridRow<Message>> = state
-                .dirs
-                .keys()
-                .map(|direction| {
-                    let mut row: GridRow<Message> = GridRow::default();
-                    row = row.push(text(direction.to_string()));
-                    if settings.animated {
-                        let animated = {
-                            if settings.show_resized {
-                                state.get_animated(direction)
-                            } else {
-                                state.get_original_animated(direction)
-                            }
-                        };
-                        if let Some(gif) = animated {
-                            let gif = Gif::new(&gif.frames);
-                            let gif = button(gif)
-                                .on_press(wrap![ViewerMessage::CopyImage(
-                                    state.name.clone(),
-                                    true,
-                                    settings.show_resized,
-                                    *direction,
-                                    None
-                                )])
-                                .style(|_theme, _status| button::Style {
-                                    background: None,
-                                    ..Default::default()
-                                });
-                            row = row.push(gif);
-                        }
+        let edit_bar = match &self.renaming_state {
+            Some((renamed_state, input)) if renamed_state == state_id => {
+                row![
+                    text_input("Enter new state name...", input)
+                        .on_input(|input| {
+                            wrap![ViewerMessage::ChangeRenameInput(input)]
+                        })
+                        .on_paste(|input| {
+                            wrap![ViewerMessage::ChangeRenameInput(input)]
+                        })
+                        .on_submit(wrap![ViewerMessage::ConfirmRenameState])
+                        .width(200)
+                        .padding(5),
+                    button(icon::save())
+                        .on_press(wrap![ViewerMessage::ConfirmRenameState])
+                        .style(button::success),
+                    button(icon::trash())
+                        .on_press(wrap![ViewerMessage::CancelRenameState])
+                        .style(button::secondary),
+                ]
+            }
+            _ => {
+                let delete_button =
+                    if self.pending_state_deletion.as_ref() == Some(state_id) {
+                        button(row![icon::trash(), text(" Confirm")])
                     } else {
-                        for frame in 0..state.frames {
-                            let icon = {
+                        button(row![icon::trash()])
+                    };
+                row![
+                    button(icon::edit()).on_press(wrap![
+                        ViewerMessage::StartRenameState(state_id.clone())
+                    ]),
+                    button(icon::file()).on_press(wrap![
+                        ViewerMessage::DuplicateState(state_id.clone())
+                    ]),
+                    button("<")
+                        .on_press(wrap![ViewerMessage::MoveState(
+                            state_id.clone(),
+                            -1
+                        )])
+                        .style(button::secondary),
+                    button(">")
+                        .on_press(wrap![ViewerMessage::MoveState(
+                            state_id.clone(),
+                            1
+                        )])
+                        .style(button::secondary),
+                    delete_button
+                        .on_press(wrap![ViewerMessage::DeleteState(
+                            state_id.clone()
+                        )])
+                        .style(button::danger),
+                ]
+            }
+        }
+        .spacing(5)
+        .align_y(Vertical::Center);
+
+        // Frames are made in background, see `prepare_visible`
+        let display: Element<Message> = if state
+            .is_prepared(settings.show_resized, settings.animated)
+        {
+            let grid: Grid<Message> = {
+                let mut dirs: VecDeque<GridRow<Message>> = state
+                    .dirs
+                    .keys()
+                    .map(|direction| {
+                        let mut row: GridRow<Message> = GridRow::default();
+                        row = row.push(text(direction.to_string()));
+                        if settings.animated {
+                            let animated = {
                                 if settings.show_resized {
-                                    state.get_frame(direction, frame as usize)
+                                    state.get_animated(direction)
                                 } else {
-                                    state.get_original_frame(
-                                        direction,
-                                        frame as usize,
-                                    )
+                                    state.get_original_animated(direction)
                                 }
                             };
-                            if let Some(icon) = icon {
-                                let image_widget: Image = Image::new(
-                                    iced::widget::image::Handle::from_rgba(
-                                        icon.width(),
-                                        icon.height(),
-                                        icon.clone().into_bytes(),
-                                    ),
-                                );
-                                let image_widget = button(image_widget)
+                            if let Some(gif) = animated {
+                                let gif = Gif::new(&gif.frames);
+                                let gif = button(gif)
                                     .on_press(wrap![ViewerMessage::CopyImage(
-                                        state.name.clone(),
-                                        false,
+                                        state_id.clone(),
+                                        true,
                                         settings.show_resized,
                                         *direction,
-                                        Some(frame as usize)
+                                        None
                                     )])
                                     .style(|_theme, _status| button::Style {
                                         background: None,
                                         ..Default::default()
                                     });
-                                row = row.push(image_widget);
-                            } else {
-                                row = row.push(text("?"));
+                                let gif =
+                                    mouse_area(gif).on_right_press(wrap![
+                                        ViewerMessage::FindSimilar(
+                                            state_id.clone(),
+                                            *direction,
+                                            0
+                                        )
+                                    ]);
+                                row = row.push(gif);
+                            }
+                        } else {
+                            for frame in 0..state.frames {
+                                let icon = {
+                                    if settings.show_resized {
+                                        state.get_frame(
+                                            direction,
+                                            frame as usize,
+                                        )
+                                    } else {
+                                        state.get_original_frame(
+                                            direction,
+                                            frame as usize,
+                                        )
+                                    }
+                                };
+                                if let Some(icon) = icon {
+                                    let image_widget: Image = Image::new(
+                                        iced::widget::image::Handle::from_rgba(
+                                            icon.width(),
+                                            icon.height(),
+                                            icon.clone().into_bytes(),
+                                        ),
+                                    );
+                                    let image_widget = button(image_widget)
+                                        .on_press(wrap![
+                                            ViewerMessage::CopyImage(
+                                                state_id.clone(),
+                                                false,
+                                                settings.show_resized,
+                                                *direction,
+                                                Some(frame as usize)
+                                            )
+                                        ])
+                                        .style(|_theme, _status| {
+                                            button::Style {
+                                                background: None,
+                                                ..Default::default()
+                                            }
+                                        });
+                                    let image_widget = mouse_area(image_widget)
+                                        .on_right_press(wrap![
+                                            ViewerMessage::FindSimilar(
+                                                state_id.clone(),
+                                                *direction,
+                                                frame as usize
+                                            )
+                                        ]);
+                                    row = row.push(image_widget);
+                                } else {
+                                    row = row.push(text("?"));
+                                }
                             }
                         }
+                        row
+                    })
+                    .collect();
+                if !settings.animated && state.frames > 1 {
+                    let mut delay_row: GridRow<Message> = GridRow::new();
+                    delay_row = delay_row.push(text("Delay"));
+                    for delay in state.delay.as_ref().unwrap_or(&Vec::new()) {
+                        delay_row = delay_row.push(text(delay))
                     }
-                    row
-                })
-                .collect();
-            if !settings.animated && state.frames > 1 {
-                let mut delay_row: GridRow<Message> = GridRow::new();
-                delay_row = delay_row.push(text("Delay"));
-                for delay in state.delay.as_ref().unwrap_or(&Vec::new()) {
-                    delay_row = delay_row.push(text(delay))
+                    dirs.push_front(delay_row);
                 }
-                dirs.push_front(delay_row);
-            }
-            Grid::with_rows(dirs.into())
-                .column_width(self.parsed_dmi.displayed_width as f32 * 1.2)
-                .horizontal_alignment(Horizontal::Center)
-                .spacing(10)
+                Grid::with_rows(dirs.into())
+                    .column_width(self.parsed_dmi.displayed_width as f32 * 1.2)
+                    .horizontal_alignment(Horizontal::Center)
+                    .spacing(10)
+            };
+            Scrollable::with_direction(
+                grid,
+                Direction::Horizontal(Scrollbar::default()),
+            )
+            .into()
+        } else {
+            container(text("Loading..."))
+                .center_x(self.parsed_dmi.displayed_width.max(64) as f32)
+                .center_y(self.parsed_dmi.displayed_height.max(64) as f32)
+                .into()
         };
-
-        let display = Scrollable::with_direction(
-            display,
-            Direction::Horizontal(Scrollbar::default()),
-        );
-        container(column![header, display])
+        container(column![header, edit_bar, Space::new(1, 10), display])
             .padding(10)
             .style(|_theme| Style {
                 text_color: Some(settings.text_color),
@@ -320,6 +799,7 @@ impl Screen for ViewerScreen {
                 }
                 ViewerMessage::LoadDMI => {
                     screen.loading_dmi_in_progress = true;
+                    screen.references_shown = None;
                     let path = screen.dmi_path.clone();
                     let filter_type: FilterType = screen
                         .display_settings
@@ -369,12 +849,90 @@ impl Screen for ViewerScreen {
                     let (raw, parsed) = result.unwrap();
                     screen.dmi_raw_icon = raw;
                     screen.parsed_dmi = parsed;
+                    screen.dmi_modified = false;
+                    screen.dmi_version += 1;
+                    screen.save_pending = false;
+                    screen.dmi_canonical_path =
+                        fs::canonicalize(&screen.dmi_path).ok();
+                    screen.dmi_file_modified = screen.file_modified();
+                    screen.relint();
+                    screen.renaming_state = None;
+                    screen.pending_state_deletion = None;
                     screen.loading_dmi_in_progress = false;
-                    Task::done(popup(
-                        "Successfully loaded DMI",
-                        Some("Loaded"),
-                        ToastLevel::Success,
-                    ))
+                    let states_with_warnings =
+                        screen.parsed_dmi.states_with_warnings();
+                    let loaded_popup = if states_with_warnings > 0 {
+                        popup(
+                            format!(
+                                "Loaded DMI, but {} states have problems, \
+                                 hover their \u{26A0} marks to see them",
+                                states_with_warnings
+                            ),
+                            Some("Loaded with warnings"),
+                            ToastLevel::Warning,
+                        )
+                    } else {
+                        popup(
+                            "Successfully loaded DMI",
+                            Some("Loaded"),
+                            ToastLevel::Success,
+                        )
+                    };
+                    Task::batch([
+                        screen.prepare_visible(),
+                        Task::done(loaded_popup),
+                    ])
+                }
+                ViewerMessage::Scrolled(offset) => {
+                    screen.scroll_offset = if offset.is_finite() {
+                        offset.clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+                    screen.prepare_visible()
+                }
+                ViewerMessage::StatePrepared(state_id) => {
+                    screen.preparing_states.remove(&state_id);
+                    screen.prepare_visible()
+                }
+                ViewerMessage::FilesChanged(changed) => {
+                    if screen.loading_dmi_in_progress
+                        || !screen
+                            .dmi_canonical_path
+                            .as_ref()
+                            .is_some_and(|path| changed.contains(path))
+                    {
+                        return Task::none();
+                    }
+                    if !Path::new(&screen.dmi_path).is_file() {
+                        screen.dmi_file_modified = None;
+                        return Task::done(popup(
+                            format!(
+                                "{} was removed from disk, save it to keep \
+                                 it",
+                                screen.dmi_path
+                            ),
+                            Some("DMI removed"),
+                            ToastLevel::Warning,
+                        ));
+                    }
+                    let modified = screen.file_modified();
+                    if modified == screen.dmi_file_modified {
+                        return Task::none();
+                    }
+                    if screen.dmi_modified {
+                        screen.dmi_file_modified = modified;
+                        return Task::done(popup(
+                            format!(
+                                "{} was changed on disk, reload it to see the \
+                                 changes, your unsaved edits will be lost",
+                                screen.dmi_path
+                            ),
+                            Some("DMI changed on disk"),
+                            ToastLevel::Warning,
+                        ));
+                    }
+                    Task::done(wrap![ViewerMessage::LoadDMI])
                 }
                 ViewerMessage::OpenedFileExplorer => {
                     let file = FileDialog::new()
@@ -446,12 +1004,12 @@ impl Screen for ViewerScreen {
                 }
                 ViewerMessage::ToggleAnimated(active) => {
                     screen.display_settings.statebox_default.animated = active;
-                    Task::none()
+                    screen.prepare_visible()
                 }
                 ViewerMessage::ToggleResizeDisplay(active) => {
                     screen.display_settings.statebox_default.show_resized =
                         active;
-                    Task::none()
+                    screen.prepare_visible()
                 }
                 ViewerMessage::ChangeResize(resizing) => {
                     screen.display_settings.statebox_default.resize = resizing;
@@ -472,7 +1030,8 @@ impl Screen for ViewerScreen {
                             .unwrap_or_default()
                             .into(),
                     );
-                    Task::done(popup(
+                    let prepare = screen.prepare_visible();
+                    let resized_popup = popup(
                         format!(
                             "Performed resize to {:#?} with filter {:#?}",
                             screen.display_settings.statebox_default.resize,
@@ -483,10 +1042,41 @@ impl Screen for ViewerScreen {
                         ),
                         Some("Resized"),
                         ToastLevel::Success,
-                    ))
+                    );
+                    Task::batch([prepare, Task::done(resized_popup)])
+                }
+                ViewerMessage::FindSimilar(state_id, direction, frame) => {
+                    let Some(image) =
+                        screen.parsed_dmi.states.get(&state_id).and_then(
+                            |state| state.get_original_frame(&direction, frame),
+                        )
+                    else {
+                        return Task::done(popup(
+                            format!(
+                                "Failed to get frame {} of state {} with \
+                                 direction {}",
+                                frame, state_id, direction
+                            ),
+                            Some("Failed"),
+                            ToastLevel::Error,
+                        ));
+                    };
+                    let looked_for = format!(
+                        "{} ({}, frame {})",
+                        state_id,
+                        direction,
+                        frame + 1
+                    );
+                    Task::batch([
+                        Task::done(Message::ChangeScreen(Screens::Explorer)),
+                        Task::done(wrap![ExplorerMessage::FindSimilar(
+                            perceptual_hash(image),
+                            looked_for
+                        )]),
+                    ])
                 }
                 ViewerMessage::CopyImage(
-                    state_name,
+                    state_id,
                     animated,
                     original,
                     direction,
@@ -503,17 +1093,18 @@ impl Screen for ViewerScreen {
                         ));
                     }
 
-                    let state = screen.parsed_dmi.states.get(&state_name);
+                    let state = screen.parsed_dmi.states.get(&state_id);
                     if state.is_none() {
                         return Task::done(popup(
-                            format!("Failed to get state {}", state_name),
+                            format!("Failed to get state {}", state_id),
                             Some("Failed"),
                             ToastLevel::Error,
                         ));
                     }
                     let state = state.unwrap();
 
-                    let mut file_path = app.config.cache_dir.join(&state_name);
+                    let mut file_path =
+                        app.config.cache_dir.join(&state_id.name);
                     file_path.set_extension(".gif");
 
                     let temporary_file = OpenOptions::new()
@@ -543,7 +1134,7 @@ impl Screen for ViewerScreen {
                         (true, true) => state.get_original_animated(&direction).ok_or_else(|| {
                             format!(
                                 "failed to get original animated view of state {} with direction {}",
-                                &state_name,
+                                &state_id,
                                 direction
                             )
                         }).map(|animated| animated.bytes.clone()),
@@ -551,7 +1142,7 @@ impl Screen for ViewerScreen {
                         (true, false) => state.get_animated(&direction).ok_or_else(|| {
                             format!(
                                 "failed to get animated view of state {} with direction {}",
-                                &state_name,
+                                &state_id,
                                 direction
                             )
                         }).map(|animated| animated.bytes.clone()),
@@ -560,7 +1151,7 @@ impl Screen for ViewerScreen {
                             format!(
                                 "failed to get original {} frame of state {} with direction {}",
                                 frame.unwrap(),
-                                &state_name,
+                                &state_id,
                                 direction
                             )
                         }).map(|image| {
@@ -572,7 +1163,7 @@ impl Screen for ViewerScreen {
                             format!(
                                 "failed to get {} frame of state {} with direction {}",
                                 frame.unwrap(),
-                                &state_name,
+                                &state_id,
                                 direction
                             )
                         }).map(|image| {
@@ -663,7 +1254,13 @@ impl Screen for ViewerScreen {
                 }
                 ViewerMessage::ChangeFilteredText(new_text) => {
                     screen.filtered_text = new_text;
-                    Task::none()
+                    screen.rebuild_filter();
+                    screen.prepare_visible()
+                }
+                ViewerMessage::ChangeFilterMode(mode) => {
+                    screen.filter_mode = mode;
+                    screen.rebuild_filter();
+                    screen.prepare_visible()
                 }
                 ViewerMessage::ToggleFilter(status) => {
                     screen.filter_opened = status;
@@ -700,6 +1297,406 @@ impl Screen for ViewerScreen {
                         ToastLevel::Success,
                     ))
                 }
+                ViewerMessage::CompareWith => {
+                    let Some(old_path) = FileDialog::new()
+                        .set_title("Choose the DMI to compare with")
+                        .set_directory(
+                            Path::new(&screen.dmi_path)
+                                .parent()
+                                .unwrap_or(Path::new("/")),
+                        )
+                        .add_filter("dmi", &["dmi"])
+                        .pick_file()
+                    else {
+                        return Task::none();
+                    };
+                    Task::batch([
+                        Task::done(Message::ChangeScreen(Screens::Diff)),
+                        Task::done(wrap![DiffMessage::ChangePath(
+                            DiffSide::Old,
+                            old_path.to_string_lossy().into()
+                        )])
+                        .chain(Task::done(wrap![
+                            DiffMessage::ChangeOldRevision(String::new())
+                        ]))
+                        .chain(Task::done(wrap![DiffMessage::ChangePath(
+                            DiffSide::New,
+                            screen.dmi_path.clone()
+                        )]))
+                        .chain(Task::done(wrap![DiffMessage::Compare])),
+                    ])
+                }
+                ViewerMessage::ChangeCompareRevision(revision) => {
+                    screen.compare_revision = revision;
+                    Task::none()
+                }
+                ViewerMessage::CompareWithRevision => {
+                    let revision = match screen.compare_revision.trim() {
+                        "" => DEFAULT_REVISION.to_string(),
+                        revision => revision.to_string(),
+                    };
+                    Task::batch([
+                        Task::done(Message::ChangeScreen(Screens::Diff)),
+                        Task::done(wrap![DiffMessage::ChangePath(
+                            DiffSide::Old,
+                            screen.dmi_path.clone()
+                        )])
+                        .chain(Task::done(wrap![
+                            DiffMessage::ChangeOldRevision(revision)
+                        ]))
+                        .chain(Task::done(wrap![DiffMessage::ChangePath(
+                            DiffSide::New,
+                            screen.dmi_path.clone()
+                        )]))
+                        .chain(Task::done(wrap![DiffMessage::Compare])),
+                    ])
+                }
+                ViewerMessage::ExportAll => {
+                    if screen.dmi_raw_icon.states.is_empty() {
+                        return Task::done(popup(
+                            "There is nothing to export",
+                            Some("Empty DMI"),
+                            ToastLevel::Error,
+                        ));
+                    }
+                    let Some(output_dir) = FileDialog::new()
+                        .set_title("Choose a folder to export states into")
+                        .set_directory("/")
+                        .pick_folder()
+                    else {
+                        return Task::none();
+                    };
+                    let icon = screen.dmi_raw_icon.clone();
+                    let dmi_name = Path::new(&screen.dmi_path)
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned();
+                    // The template is shared with the Explorer settings
+                    let name_template = app
+                        .explorer_screen
+                        .settings
+                        .export_name_template
+                        .clone();
+                    Task::future(async move {
+                        let export_start = Instant::now();
+                        let exported = export_dmi(
+                            &icon,
+                            &dmi_name,
+                            &output_dir,
+                            &name_template,
+                        )
+                        .map(|summary| (output_dir, summary))
+                        .map_err(|err| err.to_string());
+                        debug!(
+                            "DMI {} exported in {}ms",
+                            dmi_name,
+                            export_start.elapsed().as_millis()
+                        );
+                        wrap![ViewerMessage::Exported(exported)]
+                    })
+                }
+                ViewerMessage::Exported(result) => match result {
+                    Ok((output_dir, summary)) => Task::done(popup(
+                        format!(
+                            "Exported {} images and {} GIFs into {}",
+                            summary.images,
+                            summary.gifs,
+                            output_dir.to_string_lossy()
+                        ),
+                        Some("Exported"),
+                        ToastLevel::Success,
+                    )),
+                    Err(err) => {
+                        error!("Failed to export DMI: {}", err);
+                        Task::done(popup(
+                            format!("Failed to export DMI: {}", err),
+                            Some("Export failed"),
+                            ToastLevel::Error,
+                        ))
+                    }
+                },
+                ViewerMessage::SaveDMI => {
+                    if !screen.dmi_modified {
+                        return Task::done(popup(
+                            "There are no changes to save",
+                            Some("Nothing to save"),
+                            ToastLevel::Info,
+                        ));
+                    }
+                    if screen.dmi_path.is_empty() {
+                        return Task::done(wrap![ViewerMessage::SaveDMIAs]);
+                    }
+                    // Saves write the same temporary file, so they must not
+                    // overlap
+                    if screen.saving {
+                        screen.save_pending = true;
+                        return Task::none();
+                    }
+                    screen.saving = true;
+                    let fitted = fitted_delays_popup(&screen.dmi_raw_icon);
+                    let icon = screen.dmi_raw_icon.clone();
+                    let version = screen.dmi_version;
+                    let path = PathBuf::from(&screen.dmi_path);
+                    let save = Task::future(async move {
+                        let saved = save_dmi(&icon, &path)
+                            .map(|_| (path, version))
+                            .map_err(|err| err.to_string());
+                        wrap![ViewerMessage::DMISaved(saved)]
+                    });
+                    Task::batch(
+                        fitted.map(Task::done).into_iter().chain([save]),
+                    )
+                }
+                ViewerMessage::SaveDMIAs => {
+                    if screen.dmi_raw_icon.states.is_empty() {
+                        return Task::done(popup(
+                            "There is nothing to save",
+                            Some("Empty DMI"),
+                            ToastLevel::Error,
+                        ));
+                    }
+                    if screen.saving {
+                        return Task::done(popup(
+                            "The DMI is being saved, try again in a moment",
+                            Some("Saving"),
+                            ToastLevel::Info,
+                        ));
+                    }
+                    let Some(path) = FileDialog::new()
+                        .set_title("Save DMI as")
+                        .add_filter("dmi", &["dmi"])
+                        .set_directory("/")
+                        .save_file()
+                    else {
+                        return Task::none();
+                    };
+                    screen.saving = true;
+                    let icon = screen.dmi_raw_icon.clone();
+                    let version = screen.dmi_version;
+                    let original_path = PathBuf::from(&screen.dmi_path);
+                    let modified = screen.dmi_modified;
+                    let fitted = if !modified && original_path.is_file() {
+                        None
+                    } else {
+                        fitted_delays_popup(&screen.dmi_raw_icon)
+                    };
+                    let save = Task::future(async move {
+                        // Unchanged files are copied as is to keep them
+                        // byte-for-byte identical
+                        let saved = if !modified && original_path.is_file() {
+                            fs::copy(&original_path, &path)
+                                .map(|_| (path, version))
+                                .map_err(|err| err.to_string())
+                        } else {
+                            save_dmi(&icon, &path)
+                                .map(|_| (path, version))
+                                .map_err(|err| err.to_string())
+                        };
+                        wrap![ViewerMessage::DMISaved(saved)]
+                    });
+                    Task::batch(
+                        fitted.map(Task::done).into_iter().chain([save]),
+                    )
+                }
+                ViewerMessage::DMISaved(result) => {
+                    screen.saving = false;
+                    let saved = match result {
+                        Ok((path, version)) => {
+                            // Edits made during the save are still unsaved
+                            if version == screen.dmi_version {
+                                screen.dmi_path =
+                                    path.to_string_lossy().into_owned();
+                                screen.dmi_canonical_path =
+                                    fs::canonicalize(&path).ok();
+                                screen.dmi_modified = false;
+                            }
+                            // Our own write is not a change on disk
+                            if Path::new(&screen.dmi_path) == path {
+                                screen.dmi_file_modified =
+                                    screen.file_modified();
+                            }
+                            popup(
+                                format!(
+                                    "Saved DMI to {}",
+                                    path.to_string_lossy()
+                                ),
+                                Some("Saved"),
+                                ToastLevel::Success,
+                            )
+                        }
+                        Err(err) => {
+                            error!("Failed to save DMI: {}", err);
+                            popup(
+                                format!("Failed to save DMI: {}", err),
+                                Some("Save failed"),
+                                ToastLevel::Error,
+                            )
+                        }
+                    };
+                    if screen.save_pending && screen.dmi_modified {
+                        screen.save_pending = false;
+                        return Task::batch([
+                            Task::done(saved),
+                            Task::done(wrap![ViewerMessage::SaveDMI]),
+                        ]);
+                    }
+                    screen.save_pending = false;
+                    Task::done(saved)
+                }
+                ViewerMessage::ToggleLintReport(visible) => {
+                    screen.lint_report_visible = visible;
+                    Task::none()
+                }
+                ViewerMessage::ShowReferences(state_name) => {
+                    screen.references_shown = state_name;
+                    Task::none()
+                }
+                ViewerMessage::StartRenameState(state_id) => {
+                    let name = state_id.name.clone();
+                    screen.renaming_state = Some((state_id, name));
+                    Task::none()
+                }
+                ViewerMessage::ChangeRenameInput(new_input) => {
+                    if let Some((_, input)) = &mut screen.renaming_state {
+                        *input = new_input;
+                    }
+                    Task::none()
+                }
+                ViewerMessage::CancelRenameState => {
+                    screen.renaming_state = None;
+                    Task::none()
+                }
+                ViewerMessage::ConfirmRenameState => {
+                    let Some((old_id, new_name)) = screen.renaming_state.take()
+                    else {
+                        return Task::none();
+                    };
+                    if let Err(err) = rename_state(
+                        &mut screen.dmi_raw_icon,
+                        &old_id,
+                        &new_name,
+                    ) {
+                        screen.renaming_state = Some((old_id, new_name));
+                        return Task::done(popup(
+                            format!("Failed to rename state: {}", err),
+                            Some("Rename failed"),
+                            ToastLevel::Error,
+                        ));
+                    }
+                    if old_id.name == new_name {
+                        return Task::none();
+                    }
+                    // A broken DMI may hold another state with the same id
+                    let parsed_state =
+                        if state_index(&screen.dmi_raw_icon, &old_id).is_ok() {
+                            screen.parsed_dmi.states.get(&old_id).cloned()
+                        } else {
+                            screen.parsed_dmi.states.remove(&old_id)
+                        };
+                    if let Some(mut parsed_state) = parsed_state {
+                        Arc::make_mut(&mut parsed_state).name =
+                            new_name.clone();
+                        screen
+                            .parsed_dmi
+                            .states
+                            .insert(parsed_state.id(), parsed_state);
+                    }
+                    // The other state with the old name keeps the settings
+                    let old_name_left = screen
+                        .dmi_raw_icon
+                        .states
+                        .iter()
+                        .any(|state| state.name == old_id.name);
+                    if !old_name_left
+                        && let Some(statebox_settings) = screen
+                            .display_settings
+                            .unique_stateboxes
+                            .remove(&old_id.name)
+                    {
+                        screen
+                            .display_settings
+                            .unique_stateboxes
+                            .insert(new_name, statebox_settings);
+                    }
+                    screen.mark_edited();
+                    Task::done(wrap![ViewerMessage::SaveDMI])
+                }
+                ViewerMessage::DeleteState(state_id) => {
+                    if screen.pending_state_deletion.as_ref() != Some(&state_id)
+                    {
+                        screen.pending_state_deletion = Some(state_id);
+                        return Task::none();
+                    }
+                    screen.pending_state_deletion = None;
+                    if let Err(err) =
+                        delete_state(&mut screen.dmi_raw_icon, &state_id)
+                    {
+                        return Task::done(popup(
+                            format!("Failed to delete state: {}", err),
+                            Some("Delete failed"),
+                            ToastLevel::Error,
+                        ));
+                    }
+                    // Several states may share the name and the movement
+                    // flag in a broken DMI
+                    if state_index(&screen.dmi_raw_icon, &state_id).is_err() {
+                        screen.parsed_dmi.states.remove(&state_id);
+                    }
+                    if !screen
+                        .dmi_raw_icon
+                        .states
+                        .iter()
+                        .any(|state| state.name == state_id.name)
+                    {
+                        screen
+                            .display_settings
+                            .unique_stateboxes
+                            .remove(&state_id.name);
+                    }
+                    screen.mark_edited();
+                    Task::done(wrap![ViewerMessage::SaveDMI])
+                }
+                ViewerMessage::DuplicateState(state_id) => {
+                    let copy_id = match duplicate_state(
+                        &mut screen.dmi_raw_icon,
+                        &state_id,
+                    ) {
+                        Ok(copy_id) => copy_id,
+                        Err(err) => {
+                            return Task::done(popup(
+                                format!("Failed to duplicate state: {}", err),
+                                Some("Duplicate failed"),
+                                ToastLevel::Error,
+                            ));
+                        }
+                    };
+                    if let Some(parsed_state) =
+                        screen.parsed_dmi.states.get(&state_id)
+                    {
+                        let mut parsed_copy = ParsedState::clone(parsed_state);
+                        parsed_copy.name = copy_id.name.clone();
+                        screen
+                            .parsed_dmi
+                            .states
+                            .insert(copy_id, Arc::new(parsed_copy));
+                    }
+                    screen.mark_edited();
+                    Task::done(wrap![ViewerMessage::SaveDMI])
+                }
+                ViewerMessage::MoveState(state_id, offset) => {
+                    if let Err(err) =
+                        move_state(&mut screen.dmi_raw_icon, &state_id, offset)
+                    {
+                        return Task::done(popup(
+                            format!("Failed to move state: {}", err),
+                            Some("Move failed"),
+                            ToastLevel::Warning,
+                        ));
+                    }
+                    screen.mark_edited();
+                    Task::done(wrap![ViewerMessage::SaveDMI])
+                }
                 ViewerMessage::CopyFile => {
                     if screen.dmi_path.is_empty() {
                         return Task::done(popup(
@@ -846,7 +1843,63 @@ impl Screen for ViewerScreen {
         let copy_image = button(row![icon::folder(), text(" Copy DMI")])
             .on_press(wrap![ViewerMessage::CopyFile])
             .style(widget::button::success);
-        let bottom_bar = row![button_search, copy_image].spacing(10).padding(5);
+        let export_all = button(row![icon::video(), text(" Export All")])
+            .on_press(wrap![ViewerMessage::ExportAll])
+            .style(widget::button::secondary);
+        let compare_with =
+            button(row![icon::magnifying(), text(" Compare With...")])
+                .on_press(wrap![ViewerMessage::CompareWith])
+                .style(widget::button::secondary);
+        let compare_revision = row![
+            text_input(DEFAULT_REVISION, &screen.compare_revision)
+                .on_input(|input| {
+                    wrap![ViewerMessage::ChangeCompareRevision(input)]
+                })
+                .on_paste(|input| {
+                    wrap![ViewerMessage::ChangeCompareRevision(input)]
+                })
+                .on_submit(wrap![ViewerMessage::CompareWithRevision])
+                .width(150)
+                .padding(5),
+            button(row![icon::magnifying(), text(" Compare With Revision")])
+                .on_press(wrap![ViewerMessage::CompareWithRevision])
+                .style(widget::button::secondary),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center);
+        let (lint_errors, lint_warnings) = count_issues(&screen.lint_issues);
+        let lint = button(row![
+            icon::search(),
+            text!(" Lint ({} errors, {} warnings)", lint_errors, lint_warnings)
+        ])
+        .on_press(wrap![ViewerMessage::ToggleLintReport(
+            !screen.lint_report_visible
+        )])
+        .style(if lint_errors > 0 {
+            widget::button::danger
+        } else {
+            widget::button::secondary
+        });
+        let save = button(row![icon::save(), text(" Save")])
+            .on_press_maybe(
+                screen.dmi_modified.then_some(wrap![ViewerMessage::SaveDMI]),
+            )
+            .style(widget::button::success);
+        let save_as = button(row![icon::save(), text(" Save As")])
+            .on_press(wrap![ViewerMessage::SaveDMIAs])
+            .style(widget::button::secondary);
+        let bottom_bar = row![
+            button_search,
+            copy_image,
+            export_all,
+            compare_with,
+            compare_revision,
+            lint,
+            save,
+            save_as
+        ]
+        .spacing(10)
+        .padding(5);
 
         /*
          *
@@ -1018,21 +2071,48 @@ impl Screen for ViewerScreen {
             .spacing(10)
             .line_spacing(10);
 
-        for state in &screen.parsed_dmi.states {
-            states_wrap = states_wrap.push(screen.display_statebox(state.0))
+        // References of the project, opened in the Explorer
+        let references = app.explorer_screen.references.as_deref();
+        let dmi_path = screen.dmi_canonical_path.as_deref();
+        let state_references = |state_name: &str| {
+            Some(references?.of_state(dmi_path?, state_name))
+        };
+
+        for (state_id, found) in screen.filtered_states() {
+            let references = state_references(&state_id.name)
+                .map(|state_references| state_references.len());
+            states_wrap = states_wrap
+                .push(screen.display_statebox(&state_id, &found, references))
         }
 
+        let shown_references = screen
+            .references_shown
+            .as_deref()
+            .and_then(state_references)
+            .unwrap_or_default();
+        let project_root = app
+            .explorer_screen
+            .project
+            .as_ref()
+            .map(|project| project.root.as_path());
+
         let column = column![
             input_bar,
             bottom_bar,
             screen.filter_view(),
+            screen.lint_report_view(),
+            screen.references_view(&shown_references, project_root),
             settings_bar,
             states_wrap
         ]
         .padding(10)
         .spacing(10);
 
-        container(scrollable(column).spacing(10)).padding(10).into()
+        container(scrollable(column).spacing(10).on_scroll(|viewport| {
+            wrap![ViewerMessage::Scrolled(viewport.relative_offset().y)]
+        }))
+        .padding(10)
+        .into()
     }
 }
 
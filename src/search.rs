@@ -0,0 +1,327 @@
+use std::fmt::Display;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Characters, after which a new word starts.
+const WORD_SEPARATORS: &[char] = &['_', '-', ' ', '.', '/', '\\'];
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq,
+)]
+pub enum MatchMode {
+    /// Case-insensitive substring
+    #[default]
+    Substring,
+    /// Case-insensitive subsequence, ranked by how tight it is
+    Fuzzy,
+    Regex,
+    /// Case-insensitive, must match the whole text
+    Glob,
+}
+
+impl MatchMode {
+    pub const ALL: [MatchMode; 4] =
+        [Self::Substring, Self::Fuzzy, Self::Regex, Self::Glob];
+}
+
+impl Display for MatchMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchMode::Substring => write!(f, "Text"),
+            MatchMode::Fuzzy => write!(f, "Fuzzy"),
+            MatchMode::Regex => write!(f, "Regex"),
+            MatchMode::Glob => write!(f, "Glob"),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum SearchError {
+    /// Regex or glob can not be compiled.
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+}
+
+/// Found match. Better matches have higher score.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    /// Indices of the matched characters, not bytes
+    pub positions: Vec<usize>,
+}
+
+/// Compiled search pattern. Empty pattern matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Matcher {
+    mode: MatchMode,
+    /// Lowercased pattern, used by substring and fuzzy modes
+    pattern: Vec<char>,
+    regex: Option<Regex>,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, mode: MatchMode) -> Result<Self, SearchError> {
+        let regex = match mode {
+            _ if pattern.is_empty() => None,
+            MatchMode::Regex => Some(Regex::new(pattern)?),
+            MatchMode::Glob => Some(
+                RegexBuilder::new(&glob_to_regex(pattern))
+                    .case_insensitive(true)
+                    .build()?,
+            ),
+            MatchMode::Substring | MatchMode::Fuzzy => None,
+        };
+        Ok(Self {
+            mode,
+            pattern: pattern.chars().map(lowercase).collect(),
+            regex,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_empty()
+    }
+
+    /// Matches the text, returns None if it does not match.
+    pub fn find(&self, text: &str) -> Option<Match> {
+        if self.is_empty() {
+            return Some(Match::default());
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let length_penalty = chars.len() as i64 / 4;
+        let found = match (&self.regex, self.mode) {
+            (Some(regex), _) => {
+                let found = regex.find(text)?;
+                let positions: Vec<usize> = text
+                    .char_indices()
+                    .enumerate()
+                    .filter(|(_, (byte, _))| found.range().contains(byte))
+                    .map(|(index, _)| index)
+                    .collect();
+                let start = positions.first().copied().unwrap_or_default();
+                Match {
+                    score: 100 - start as i64,
+                    positions,
+                }
+            }
+            (None, MatchMode::Fuzzy) => fuzzy_match(&self.pattern, &chars)?,
+            (None, _) => substring_match(&self.pattern, &chars)?,
+        };
+        Some(Match {
+            score: found.score - length_penalty,
+            ..found
+        })
+    }
+}
+
+fn lowercase(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+/// The character starts a word, `snake_case`, `path/parts` and `camelCase`
+/// are recognised.
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    index == 0
+        || WORD_SEPARATORS.contains(&chars[index - 1])
+        || (chars[index - 1].is_lowercase() && chars[index].is_uppercase())
+}
+
+fn substring_match(pattern: &[char], chars: &[char]) -> Option<Match> {
+    let lowered: Vec<char> = chars.iter().copied().map(lowercase).collect();
+    let start = lowered
+        .windows(pattern.len())
+        .position(|window| window == pattern)?;
+    let mut score = 100 - start as i64;
+    if is_word_start(chars, start) {
+        score += 25;
+    }
+    if pattern.len() == chars.len() {
+        score += 100;
+    }
+    Some(Match {
+        score,
+        positions: (start..start + pattern.len()).collect(),
+    })
+}
+
+/// Finds the earliest end of the pattern as a subsequence, then goes back
+/// from it to find the tightest start.
+fn fuzzy_match(pattern: &[char], chars: &[char]) -> Option<Match> {
+    let lowered: Vec<char> = chars.iter().copied().map(lowercase).collect();
+    let mut matched = 0;
+    let mut end = None;
+    for (index, character) in lowered.iter().enumerate() {
+        if *character == pattern[matched] {
+            matched += 1;
+            if matched == pattern.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut positions = vec![0; pattern.len()];
+    let mut left = pattern.len();
+    for index in (0..=end).rev() {
+        if lowered[index] == pattern[left - 1] {
+            left -= 1;
+            positions[left] = index;
+            if left == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    for (order, position) in positions.iter().enumerate() {
+        score += 16;
+        if order > 0 && positions[order - 1] + 1 == *position {
+            score += 24;
+        }
+        if is_word_start(chars, *position) {
+            score += 20;
+        }
+    }
+    let gaps = end + 1 - positions[0] - pattern.len();
+    score -= gaps as i64 * 2 + positions[0] as i64;
+    Some(Match { score, positions })
+}
+
+/// `*` is any text, `?` is any character, `[...]` and `[!...]` are
+/// character classes.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+                for class_character in chars.by_ref() {
+                    if class_character == ']' {
+                        break;
+                    }
+                    if class_character == '\\' || class_character == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(class_character);
+                }
+                regex.push(']');
+            }
+            _ => regex.push_str(&regex::escape(&character.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, mode: MatchMode, text: &str) -> Option<Match> {
+        Matcher::new(pattern, mode).unwrap().find(text)
+    }
+
+    fn score(pattern: &str, mode: MatchMode, text: &str) -> i64 {
+        find(pattern, mode, text).unwrap().score
+    }
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        for mode in MatchMode::ALL {
+            assert_eq!(find("", mode, "anything"), Some(Match::default()));
+        }
+    }
+
+    #[test]
+    fn substring_ignores_case() {
+        let found = find("Worn", MatchMode::Substring, "jacket_WORN").unwrap();
+        assert_eq!(found.positions, [7, 8, 9, 10]);
+        assert!(find("worn", MatchMode::Substring, "w_o_r_n").is_none());
+    }
+
+    #[test]
+    fn substring_prefers_exact_and_word_starts() {
+        let exact = score("worn", MatchMode::Substring, "worn");
+        let word_start = score("worn", MatchMode::Substring, "x_worn");
+        let inside = score("worn", MatchMode::Substring, "xyworn");
+        assert!(exact > word_start);
+        assert!(word_start > inside);
+    }
+
+    #[test]
+    fn fuzzy_finds_subsequence() {
+        let found = find("wj", MatchMode::Fuzzy, "Worn_Jacket").unwrap();
+        assert_eq!(found.positions, [0, 5]);
+        assert!(find("jw", MatchMode::Fuzzy, "worn_jacket").is_none());
+    }
+
+    #[test]
+    fn fuzzy_ranks_tight_and_word_start_matches_higher() {
+        let consecutive = score("wor", MatchMode::Fuzzy, "worn");
+        let scattered = score("wor", MatchMode::Fuzzy, "w_xo_xr");
+        assert!(consecutive > scattered);
+
+        let word_starts = score("wj", MatchMode::Fuzzy, "worn_jacket");
+        let inside = score("wj", MatchMode::Fuzzy, "xworxjacket");
+        assert!(word_starts > inside);
+
+        let camel_case = score("wj", MatchMode::Fuzzy, "wornJacket");
+        let lower_case = score("wj", MatchMode::Fuzzy, "wornjacket");
+        assert!(camel_case > lower_case);
+    }
+
+    #[test]
+    fn fuzzy_takes_tightest_start() {
+        let found = find("ab", MatchMode::Fuzzy, "a_xab").unwrap();
+        assert_eq!(found.positions, [3, 4]);
+    }
+
+    #[test]
+    fn glob_matches_whole_text() {
+        assert!(find("worn*", MatchMode::Glob, "WORN_jacket").is_some());
+        assert!(find("worn*", MatchMode::Glob, "jacket_worn").is_none());
+        assert!(find("w?rn", MatchMode::Glob, "warn").is_some());
+        assert!(find("w?rn", MatchMode::Glob, "woorn").is_none());
+        assert!(find("[ab]_*", MatchMode::Glob, "b_x").is_some());
+        assert!(find("[!ab]_*", MatchMode::Glob, "b_x").is_none());
+        assert!(find("a.b", MatchMode::Glob, "axb").is_none());
+        let found = find("w*n", MatchMode::Glob, "worn").unwrap();
+        assert_eq!(found.positions, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn regex_is_case_sensitive() {
+        let found = find("j[a-z]+t", MatchMode::Regex, "worn_jacket").unwrap();
+        assert_eq!(found.positions, [5, 6, 7, 8, 9, 10]);
+        assert!(find("JACKET", MatchMode::Regex, "worn_jacket").is_none());
+        assert!(matches!(
+            Matcher::new("(", MatchMode::Regex),
+            Err(SearchError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn positions_count_characters_not_bytes() {
+        let found = find("ñb", MatchMode::Substring, "añb").unwrap();
+        assert_eq!(found.positions, [1, 2]);
+        let found = find("b", MatchMode::Regex, "ññb").unwrap();
+        assert_eq!(found.positions, [2]);
+    }
+
+    #[test]
+    fn longer_texts_score_lower() {
+        let short = score("worn", MatchMode::Substring, "worn_a");
+        let long = score("worn", MatchMode::Substring, "worn_abcdefghijkl");
+        assert!(short > long);
+    }
+}
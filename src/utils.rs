@@ -4,9 +4,9 @@ use std::path::PathBuf;
 use std::{fs, path::Path};
 
 use directories::ProjectDirs;
-use dmi::icon::Looping;
+use dmi::icon::{Icon, Looping};
 use iced::{
-    Font,
+    Font, color,
     font::Weight,
     widget::{
         self, Image, Text,
@@ -16,10 +16,12 @@ use iced::{
 use iced_toasts::{ToastLevel, toast};
 use image::codecs::gif::{GifEncoder, Repeat};
 use image::{Delay, DynamicImage, ImageError};
-use log::{error, warn};
+use log::warn;
 
 use crate::Message;
 use crate::config::Config;
+use crate::dmi_lint::{LintIssue, LintSeverity};
+use crate::dmi_utils::mismatched_delays;
 
 const MAX_LOGFILES_COUNT: usize = 10;
 
@@ -45,6 +47,24 @@ pub fn popup<T: AsRef<str>>(
     )
 }
 
+/// Warns, that saving the icon changes delays of some states, see
+/// [`crate::dmi_utils::fit_delays`].
+pub fn fitted_delays_popup(icon: &Icon) -> Option<Message> {
+    let states = mismatched_delays(icon);
+    if states.is_empty() {
+        return None;
+    }
+    Some(popup(
+        format!(
+            "Delays of {} do not match the frame count, saving fits them \
+             to it, see the lint report",
+            states.join(", ")
+        ),
+        Some("Delays fitted"),
+        ToastLevel::Warning,
+    ))
+}
+
 pub enum Directories {
     Log,
     Cache,
@@ -163,6 +183,57 @@ where
     })
 }
 
+/// Text with the characters at `positions` highlighted, see
+/// [`crate::search::Match`].
+pub fn highlighted_text<'a>(
+    string: &str,
+    positions: &[usize],
+    bold: bool,
+) -> text::Rich<'a, Message> {
+    let font = Font {
+        weight: if bold { Weight::Bold } else { Weight::Normal },
+        ..Default::default()
+    };
+    let mut spans: Vec<text::Span<'a, Message>> = Vec::new();
+    let mut fragment = String::new();
+    let mut fragment_highlighted = false;
+    for (index, character) in string.chars().enumerate() {
+        let highlighted = positions.contains(&index);
+        if highlighted != fragment_highlighted && !fragment.is_empty() {
+            spans.push(highlighted_span(
+                std::mem::take(&mut fragment),
+                fragment_highlighted,
+                font,
+            ));
+        }
+        fragment_highlighted = highlighted;
+        fragment.push(character);
+    }
+    spans.push(highlighted_span(fragment, fragment_highlighted, font));
+    widget::rich_text(spans)
+}
+
+fn highlighted_span<'a>(
+    fragment: String,
+    highlighted: bool,
+    font: Font,
+) -> text::Span<'a, Message> {
+    let span = widget::span(fragment).font(font);
+    if highlighted {
+        span.color(color!(0xcde002)).underline(true)
+    } else {
+        span
+    }
+}
+
+/// Lint issue, colored by its severity.
+pub fn lint_issue_text<'a>(issue: &LintIssue) -> Text<'a> {
+    Text::new(issue.to_string()).color(match issue.severity {
+        LintSeverity::Error => color!(0xfc4144),
+        LintSeverity::Warning => color!(0xcde002),
+    })
+}
+
 pub fn animate(
     frames: Vec<DynamicImage>,
     loop_flag: &Looping,
@@ -170,13 +241,11 @@ pub fn animate(
 ) -> Result<Vec<u8>, ImageError> {
     let mut animated: Vec<u8> = Vec::new();
     let mut animated_encoder = GifEncoder::new_with_speed(&mut animated, 10);
-    animated_encoder
-        .set_repeat(match loop_flag {
-            Looping::Indefinitely => Repeat::Infinite,
-            // interesting fact - iced_gif does not support finite looping. Oopsie.
-            Looping::NTimes(num) => Repeat::Finite(num.get() as u16),
-        })
-        .unwrap_or_else(|err| error!("Error setting repeat: {err}"));
+    animated_encoder.set_repeat(match loop_flag {
+        Looping::Indefinitely => Repeat::Infinite,
+        // interesting fact - iced_gif does not support finite looping. Oopsie.
+        Looping::NTimes(num) => Repeat::Finite(num.get() as u16),
+    })?;
     let result = animated_encoder.encode_frames(
         frames.into_iter().enumerate().map(|(i, frame)| {
             image::Frame::from_parts(
@@ -200,3 +269,24 @@ pub fn animate(
 
     result.and(Ok(animated))
 }
+
+/// Quotes the CSV field, if it needs to be.
+pub fn csv_field<T: AsRef<str>>(field: T) -> String {
+    let field = field.as_ref();
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Size in bytes, KiB or MiB.
+pub fn format_size(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    const MIB: u64 = KIB * 1024;
+    match bytes {
+        ..KIB => format!("{} B", bytes),
+        ..MIB => format!("{:.1} KiB", bytes as f64 / KIB as f64),
+        _ => format!("{:.1} MiB", bytes as f64 / MIB as f64),
+    }
+}
@@ -0,0 +1,80 @@
+use std::{path::PathBuf, time::Duration};
+
+use iced::{
+    Subscription,
+    futures::{SinkExt, StreamExt, channel::mpsc},
+    stream,
+};
+use log::{error, warn};
+use notify_debouncer_mini::{
+    DebounceEventResult, new_debouncer, notify::RecursiveMode,
+};
+
+/// Changes closer to each other than this are reported together.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Paths to watch. Directories are watched with their subdirectories, if the
+/// flag is set.
+pub type WatchedPaths = Vec<(PathBuf, bool)>;
+
+/// Reports changed, created and removed paths under the watched ones. The
+/// watcher is restarted whenever `paths` change, `tag` separates watchers of
+/// different screens.
+pub fn watch_paths(
+    tag: &'static str,
+    paths: WatchedPaths,
+) -> Subscription<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Subscription::none();
+    }
+    let id = (tag, paths.clone());
+    Subscription::run_with_id(
+        id,
+        stream::channel(16, move |mut output| async move {
+            let (sender, mut receiver) = mpsc::unbounded();
+            let debouncer = new_debouncer(
+                WATCH_DEBOUNCE,
+                move |result: DebounceEventResult| match result {
+                    Ok(events) => {
+                        let mut changed: Vec<PathBuf> = events
+                            .into_iter()
+                            .map(|event| event.path)
+                            .collect();
+                        changed.sort();
+                        changed.dedup();
+                        let _ = sender.unbounded_send(changed);
+                    }
+                    Err(err) => warn!("[{}] Watching failed: {}", tag, err),
+                },
+            );
+            let mut debouncer = match debouncer {
+                Ok(debouncer) => debouncer,
+                Err(err) => {
+                    error!("[{}] Failed to start watching: {}", tag, err);
+                    return;
+                }
+            };
+            for (path, recursive) in &paths {
+                let mode = if *recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                if let Err(err) = debouncer.watcher().watch(path, mode) {
+                    warn!(
+                        "[{}] Failed to watch {}: {}",
+                        tag,
+                        path.to_string_lossy(),
+                        err
+                    );
+                }
+            }
+
+            while let Some(changed) = receiver.next().await {
+                if output.send(changed).await.is_err() {
+                    break;
+                }
+            }
+        }),
+    )
+}